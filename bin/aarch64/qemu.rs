@@ -9,13 +9,15 @@ extern crate alloc;
 use alloc::vec;
 use core::{
     arch::global_asm,
-    cell::UnsafeCell,
+    cell::{Cell, UnsafeCell},
     fmt::{self, Write},
     ptr,
+    sync::atomic::{AtomicPtr, Ordering},
 };
 use log::{self, debug, info, LevelFilter, Log, Metadata, Record};
 
-use caliga_bootloader::developing_modules::{aarch64::system_registers::{current_exception_level, physical_address_width}, io::Io, mmio::Mmio};
+use caliga_bootloader::developing_modules::{aarch64::{generic_timer::GenericTimer, system_registers::{current_exception_level, physical_address_width}}, arch::halt, dtb::Dtb, mmio::Mmio, timer::Timer};
+use caliga_bootloader::register_block;
 
 // The start procedure
 global_asm!(include_str!("start.S"));
@@ -23,6 +25,17 @@ global_asm!(include_str!("start.S"));
 /// Address of UART0 on default QEMU for aarch64
 pub const UART0_ADDR: usize = 0x0900_0000;
 
+/// Frequency of the reference clock of UART0 on default QEMU for aarch64
+pub const UART0_CLOCK: u32 = 24_000_000;
+
+/// Baud rate that UART0 is initialized with
+pub const UART0_BAUD_RATE: u32 = 115_200;
+
+/// Address of the devicetree blob on default QEMU for aarch64
+///
+/// QEMU places the blob at the start of RAM when booting a bare-metal image.
+pub const DTB_ADDR: usize = 0x4000_0000;
+
 // An unimplemented allocator to see how it may be structured
 //mod bump_allocator {
 use core::alloc::{GlobalAlloc, Layout};
@@ -46,6 +59,10 @@ extern "C" {
 
 /// The current pointer used by the bump allocator
 static mut BUMP_ALLOC_PTR: *const u8 = unsafe { &PROGRAM_END as *const u8 };
+/// The end of the memory used by the bump allocator
+///
+/// Allocations are not bounded until this is set.
+static mut BUMP_ALLOC_END: Option<usize> = None;
 const BUMP_ALLOC_ALIGNMENT: usize = 8;
 
 /// An extremely simple bump allocator.
@@ -56,17 +73,13 @@ struct BumpAllocator;
 
 unsafe impl GlobalAlloc for BumpAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        // TODO: This allocator needs to return a null pointer if it does not have enough memory for the
-        //       allocation. This requires the boot loader to know where the memory ends. Not sure how this
-        //       is done on ARM, yet.
-
         // Ensure pointer is aligned
         let offset = BUMP_ALLOC_PTR.align_offset(BUMP_ALLOC_ALIGNMENT);
-        BUMP_ALLOC_PTR = BUMP_ALLOC_PTR.add(offset);
+        let mut allocated = BUMP_ALLOC_PTR.wrapping_add(offset);
 
         // Ensure that pointer is aligned according to `layout`
         if layout.align() > BUMP_ALLOC_ALIGNMENT {
-            let offset = BUMP_ALLOC_PTR.align_offset(layout.align());
+            let offset = allocated.align_offset(layout.align());
 
             // Return null if the alignment is invalid
             if offset == usize::MAX {
@@ -74,16 +87,21 @@ unsafe impl GlobalAlloc for BumpAllocator {
             }
 
             // Offset the pointer so that it's properly aligned
-            BUMP_ALLOC_PTR = BUMP_ALLOC_PTR.add(offset);
+            allocated = allocated.wrapping_add(offset);
         }
 
-        // Save the pointer to return later
-        let allocated = BUMP_ALLOC_PTR;
+        // Return null if the end of RAM would be reached
+        let allocation_end = (allocated as usize).checked_add(layout.size());
+        match (allocation_end, BUMP_ALLOC_END) {
+            (None, _) => return ptr::null_mut(),
+            (Some(allocation_end), Some(end)) if allocation_end > end => return ptr::null_mut(),
+            _ => {}
+        }
 
         // Bump the current pointer by the allocation's size
-        // TODO: Panic if the end of RAM is reached
-        BUMP_ALLOC_PTR = BUMP_ALLOC_PTR.add(layout.size());
+        BUMP_ALLOC_PTR = allocated.add(layout.size());
 
+        #[cfg(feature = "verbose-alloc")]
         debug!(
             "ALLOC@{:p} with size: {:#x} and align: {}",
             allocated,
@@ -99,11 +117,33 @@ unsafe impl GlobalAlloc for BumpAllocator {
 }
 //}
 
-#[repr(packed)]
-pub struct Pl011Uart {
-    data: Mmio<u8>,
+register_block! {
+    /// The registers of a PL011 UART
+    pub struct Pl011Uart {
+        0x00 => data: RW Mmio<u32>,
+        0x18 => flags: RO Mmio<u32>,
+        0x24 => integer_baud_rate: RW Mmio<u32>,
+        0x28 => fractional_baud_rate: RW Mmio<u32>,
+        0x2c => line_control: RW Mmio<u32>,
+        0x30 => control: RW Mmio<u32>,
+    }
 }
 
+/// Set in the flag register while the UART is transmitting data
+const PL011_FLAG_BUSY: u32 = 1 << 3;
+/// Set in the flag register while the transmit FIFO is full
+const PL011_FLAG_TRANSMIT_FULL: u32 = 1 << 5;
+/// Enables the FIFOs in the line control register
+const PL011_LINE_CONTROL_FIFO_ENABLE: u32 = 1 << 4;
+/// Sets a word length of 8 bits in the line control register
+///
+/// Parity and a second stop bit are disabled by leaving their bits clear, so this is 8N1.
+const PL011_LINE_CONTROL_8_BITS: u32 = 0b11 << 5;
+/// Enables the UART in the control register
+const PL011_CONTROL_ENABLE: u32 = 1 << 0;
+/// Enables transmitting in the control register
+const PL011_CONTROL_TRANSMIT_ENABLE: u32 = 1 << 8;
+
 impl Pl011Uart {
     /// Returns a [`Pl011Uart`] reference using a `base` address
     ///
@@ -122,12 +162,54 @@ impl Pl011Uart {
     pub unsafe fn new(base: usize) -> &'static mut Pl011Uart {
         &mut *(base as *mut Pl011Uart)
     }
+
+    /// Configures the UART to transmit at `baud` with 8 data bits, no parity and 1 stop bit
+    ///
+    /// `base_clock` is the frequency of the UART's reference clock in Hz.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `baud` is zero, or if it is too fast or too slow for `base_clock`.
+    pub fn init(&mut self, base_clock: u32, baud: u32) {
+        assert_ne!(baud, 0, "Baud rate cannot be zero");
+
+        // The baud rate divisor is `base_clock / (16 * baud)`, with 6 fractional bits. It is
+        // calculated in 64ths and rounded to the nearest 64th.
+        let divisor = (4 * base_clock as u64 + baud as u64 / 2) / baud as u64;
+        let (integer, fractional) = (divisor >> 6, divisor & 0x3f);
+        assert!(
+            (1..=0xffff).contains(&integer),
+            "Baud rate {baud} is not supported with a clock of {base_clock} Hz"
+        );
+
+        // The UART must be disabled and finished transmitting before it is reconfigured
+        self.control.write(0);
+        while self.flags.read() & PL011_FLAG_BUSY != 0 {
+            core::hint::spin_loop();
+        }
+        // Flush the transmit FIFO by disabling it
+        self.line_control
+            .modify(|value| value & !PL011_LINE_CONTROL_FIFO_ENABLE);
+
+        // The baud rate is only updated once the line control register is written
+        self.integer_baud_rate.write(integer as u32);
+        self.fractional_baud_rate.write(fractional as u32);
+        self.line_control
+            .write(PL011_LINE_CONTROL_8_BITS | PL011_LINE_CONTROL_FIFO_ENABLE);
+
+        self.control
+            .write(PL011_CONTROL_ENABLE | PL011_CONTROL_TRANSMIT_ENABLE);
+    }
 }
 
 impl Write for Pl011Uart {
     fn write_str(&mut self, out_string: &str) -> fmt::Result {
         for out_byte in out_string.bytes() {
-            self.data.write(out_byte);
+            // Wait for space in the transmit FIFO
+            while self.flags.read() & PL011_FLAG_TRANSMIT_FULL != 0 {
+                core::hint::spin_loop();
+            }
+            self.data.write(out_byte as u32);
         }
         Ok(())
     }
@@ -142,14 +224,38 @@ impl Write for Pl011Uart {
 /// Internally, it uses an [`UnsafeCell`] to contain the UART struct because the method `log` would disallow
 /// interior mutability, otherwise. Since this bootloader will always run on a single thread, there should be
 /// no problems with race conditions.
+///
+/// # Timestamps
+///
+/// Each log starts with the milliseconds since boot, read from the timer given to [`Self::set_timer`]. Logs
+/// that are written before a timer is set use `?` instead.
 struct UartPl011Logger {
     uart: UnsafeCell<&'static mut Pl011Uart>,
+    timer: Cell<Option<&'static dyn Timer>>,
 }
 
 // Implement traits that are needed for `Log`
 unsafe impl Sync for UartPl011Logger {}
 unsafe impl Send for UartPl011Logger {}
 
+impl UartPl011Logger {
+    /// Returns a mutable reference to the logger's UART
+    ///
+    /// # Safety
+    ///
+    /// No other reference to the UART can be in use. This holds as long as the bootloader is single
+    /// threaded, unless the reference is taken while a log is being written (such as when panicking
+    /// in the middle of a log).
+    unsafe fn uart(&self) -> &mut Pl011Uart {
+        *self.uart.get()
+    }
+
+    /// Starts timestamping logs with `timer`
+    fn set_timer(&self, timer: &'static dyn Timer) {
+        self.timer.set(Some(timer));
+    }
+}
+
 impl Log for UartPl011Logger {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
         metadata.level().to_level_filter() <= log::max_level()
@@ -163,9 +269,13 @@ impl Log for UartPl011Logger {
     // TODO: Deal with all the calls to `unwrap`
     fn log(&self, record: &Record<'_>) {
         // Get a mutable reference to the UART
-        let uart = unsafe { &mut *self.uart.get() };
+        let uart = unsafe { self.uart() };
 
-        // Write log level and args
+        // Write timestamp, log level and args
+        match self.timer.get() {
+            Some(timer) => write!(uart, "[{:5}ms]", timer.now_ms()).unwrap(),
+            None => uart.write_str("[    ?ms]").unwrap(),
+        }
         write!(uart, "[{}] {}", record.level().as_str(), record.args()).unwrap();
 
         // Try to write log file and line without any allocations
@@ -179,14 +289,24 @@ impl Log for UartPl011Logger {
     fn flush(&self) {}
 }
 
+/// The logger that panic logs are written to
+///
+/// This is null until the default logger is installed.
+static PANIC_SINK: AtomicPtr<UartPl011Logger> = AtomicPtr::new(ptr::null_mut());
+
 #[panic_handler]
 fn handle_panic(info: &core::panic::PanicInfo) -> ! {
-    // Re-initialize UART0 and print a panic log
-    // TODO: Try to use already existing UART so that no stack allocation occurs
-    let uart = unsafe { Pl011Uart::new(UART0_ADDR) };
-    // TODO: Maybe halt if this returns an error
-    writeln!(uart, "[PANIC] {}", info).unwrap();
-    loop {}
+    // Print a panic log using the logger's UART, so that it does not need to be re-initialized
+    let logger = PANIC_SINK.load(Ordering::Acquire);
+    let uart = if logger.is_null() {
+        // The logger was never installed, so fall back to a new reference to UART0
+        unsafe { Pl011Uart::new(UART0_ADDR) }
+    } else {
+        unsafe { (*logger).uart() }
+    };
+    // Halt even if the log could not be written
+    let _ = writeln!(uart, "[PANIC] {}", info);
+    halt()
 }
 
 #[alloc_error_handler]
@@ -198,27 +318,59 @@ fn handle_out_of_memory(layout: core::alloc::Layout) -> ! {
 // The default logger
 static mut LOGGER: Option<UartPl011Logger> = None;
 
+// The timer used for log timestamps
+static mut TIMER: Option<GenericTimer> = None;
+
 #[no_mangle]
 #[link_section = ".text.boot"]
 pub unsafe extern "C" fn qemu_entry() {
     // Initialize UART0
-    // The only other place it should be initialized is during a panic for emergency serial output
+    // The only other place a reference to it is made is during a panic, if the logger has not been
+    // installed yet
     let uart = unsafe { Pl011Uart::new(UART0_ADDR) };
+    uart.init(UART0_CLOCK, UART0_BAUD_RATE);
 
     // Initialize logger using UART0
     let logger = {
-        LOGGER = Some(UartPl011Logger { uart: uart.into() });
+        LOGGER = Some(UartPl011Logger {
+            uart: uart.into(),
+            timer: Cell::new(None),
+        });
         LOGGER.as_ref().unwrap()
     };
     log::set_logger(logger).unwrap();
+    PANIC_SINK.store(logger as *const _ as *mut _, Ordering::Release);
     log::set_max_level(LevelFilter::Debug);
     info!("Default logger is UART at address: {:#x}", UART0_ADDR);
 
+    // The generic timer starts counting at reset, so it can timestamp logs right away
+    let timer = {
+        TIMER = Some(unsafe { GenericTimer::new() });
+        TIMER.as_ref().unwrap()
+    };
+    logger.set_timer(timer);
+    info!("Generic timer frequency: {} Hz", timer.ticks_per_second());
+
     // Print out program address and size
     debug!("PROGRAM_START: {:p}", &PROGRAM_START);
     debug!("PROGRAM_END  : {:p}", &PROGRAM_END);
     debug!("PROGRAM_SIZE : {:#x}", &PROGRAM_SIZE as *const u8 as usize);
 
+    // Read memory range from DTB file
+    let dtb = unsafe { Dtb::from_addr(DTB_ADDR) }.expect("Failed to parse DTB");
+    let program_end = &PROGRAM_END as *const u8 as usize;
+    let memory_regions = dtb.memory_regions().expect("Failed to read memory regions from DTB");
+    for (base, size) in memory_regions {
+        info!("Memory region: {base:#x}, size: {size:#x}");
+
+        // Bound the allocator by the region that it starts in
+        let (base, end) = (base as usize, (base + size) as usize);
+        if (base..end).contains(&program_end) {
+            BUMP_ALLOC_END = Some(end);
+        }
+    }
+    debug!("BUMP_ALLOC_END: {:#x?}", BUMP_ALLOC_END);
+
     // Test out allocator
     {
         let v1 = vec!['a', 'b', 'c', 'd'];
@@ -234,6 +386,16 @@ pub unsafe extern "C" fn qemu_entry() {
     info!("Current exception level: {:?}", unsafe { current_exception_level() });
     info!("Physical address width: {}", unsafe { physical_address_width() });
 
+    // Test that the allocator returns null once the end of RAM is reached
+    {
+        let layout = Layout::from_size_align(0x10_0000, BUMP_ALLOC_ALIGNMENT).unwrap();
+        let mut allocations = 0;
+        while !GLOBAL_ALLOCATOR.alloc(layout).is_null() {
+            allocations += 1;
+        }
+        info!("Allocator exhausted after {allocations} allocations of {:#x} bytes", layout.size());
+    }
+
     // TODO: Run kernel
     panic!("End of bootloader reached. Press 'CTRL+A' and then 'X' to exit.");
 }
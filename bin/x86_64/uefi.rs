@@ -5,17 +5,190 @@
 
 extern crate alloc;
 
-use core::{arch::x86_64::has_cpuid, ops::DerefMut, panic::PanicInfo};
+use alloc::{vec, vec::Vec};
+use core::{
+    arch::x86_64::has_cpuid,
+    fmt::Write,
+    mem,
+    ops::DerefMut,
+    panic::PanicInfo,
+};
 use log::{debug, error, info, warn};
-use uefi::{self, prelude::*, proto::loaded_image::LoadedImage};
+use uefi::{
+    self,
+    prelude::*,
+    proto::{
+        console::gop::{self, GraphicsOutput},
+        loaded_image::LoadedImage,
+    },
+    table::{
+        boot::{MemoryDescriptor, MemoryType, OpenProtocolAttributes, OpenProtocolParams},
+        cfg::{ACPI2_GUID, ACPI_GUID},
+    },
+};
 use uefi_services::println;
 
-use caliga_bootloader::developing_modules::x86_64::cpuid::{cpuid_address_width, cpuid_max_values};
+use caliga_bootloader::developing_modules::{
+    addressing::normalize_memory_map,
+    arch::halt,
+    boot_info::{BootInfo, Framebuffer, PixelFormat},
+    console::FramebufferConsole,
+    x86_64::{
+        cpuid::{cpuid_address_width, cpuid_hypervisor_vendor, cpuid_max_values},
+        uart_16550::{Uart16550, COM1, MAX_BAUD_RATE},
+    },
+};
 
 #[panic_handler]
 fn handle_panic(info: &PanicInfo) -> ! {
     println!("[PANIC]: {}", info);
-    loop {}
+    halt()
+}
+
+/// The size of a page in the UEFI memory map.
+const UEFI_PAGE_SIZE: usize = 0x1000;
+
+/// Returns the `(physical_start, byte_length)` of every region of conventional memory in the UEFI
+/// memory map.
+fn memory_map(bt: &BootServices) -> Vec<(usize, usize)> {
+    let mut buffer = memory_map_buffer(bt);
+    let offset = buffer
+        .as_ptr()
+        .align_offset(mem::align_of::<MemoryDescriptor>());
+
+    let (_, descriptors) = bt
+        .memory_map(&mut buffer[offset..])
+        .expect("Could not get UEFI memory map");
+    descriptors.filter_map(conventional_region).collect()
+}
+
+/// Returns a buffer that is large enough to store the UEFI memory map, including any padding
+/// needed to align the memory descriptors.
+fn memory_map_buffer(bt: &BootServices) -> Vec<u8> {
+    let map_size = bt.memory_map_size();
+
+    // Allocating the buffer can add entries to the memory map, so leave room for a few more
+    let buffer_size =
+        map_size.map_size + map_size.entry_size * 4 + mem::align_of::<MemoryDescriptor>();
+    vec![0u8; buffer_size]
+}
+
+/// Returns the `(physical_start, byte_length)` of `descriptor` if it is conventional memory.
+fn conventional_region(descriptor: &MemoryDescriptor) -> Option<(usize, usize)> {
+    if descriptor.ty != MemoryType::CONVENTIONAL {
+        return None;
+    }
+
+    Some((
+        descriptor.phys_start as usize,
+        descriptor.page_count as usize * UEFI_PAGE_SIZE,
+    ))
+}
+
+/// Returns the address of the ACPI Root System Description Pointer (RSDP) from the UEFI
+/// configuration table.
+///
+/// The ACPI 2.0 RSDP is preferred over the ACPI 1.0 RSDP when both are present.
+fn find_rsdp(system_table: &SystemTable<Boot>) -> Option<usize> {
+    let config_table = system_table.config_table();
+    let find_entry = |guid| {
+        config_table
+            .iter()
+            .find(|entry| entry.guid == guid)
+            .map(|entry| entry.address as usize)
+    };
+
+    if let Some(rsdp) = find_entry(ACPI2_GUID) {
+        debug!("Found ACPI 2.0 RSDP: {rsdp:#x}");
+        Some(rsdp)
+    } else if let Some(rsdp) = find_entry(ACPI_GUID) {
+        debug!("Found ACPI 1.0 RSDP: {rsdp:#x}");
+        Some(rsdp)
+    } else {
+        debug!("No ACPI RSDP found");
+        None
+    }
+}
+
+/// Exits the firmware's boot services and returns the information to hand off to a kernel.
+///
+/// The returned memory map is retrieved while exiting boot services, so it is the final memory
+/// map. Nothing that uses boot services (including logging and allocation) can be used after
+/// this is called.
+fn exit_boot_services(
+    system_table: SystemTable<Boot>,
+    image_handle: Handle,
+    framebuffer: Option<Framebuffer>,
+) -> BootInfo {
+    let rsdp = find_rsdp(&system_table);
+
+    // The buffer is leaked, as it cannot be freed after boot services are exited
+    let buffer = memory_map_buffer(system_table.boot_services()).leak();
+    let offset = buffer
+        .as_ptr()
+        .align_offset(mem::align_of::<MemoryDescriptor>());
+
+    let (_runtime_table, descriptors) = system_table
+        .exit_boot_services(image_handle, &mut buffer[offset..])
+        .expect("Could not exit boot services");
+
+    // Any regions that do not fit in the memory map are left unused
+    let mut boot_info = BootInfo::new(framebuffer, rsdp);
+    for (addr, size) in descriptors.filter_map(conventional_region) {
+        if !boot_info.push_memory_region(addr, size) {
+            break;
+        }
+    }
+
+    boot_info
+}
+
+/// Returns the framebuffer of the current Graphics Output Protocol (GOP) mode.
+///
+/// Returns `None` if there is no GOP, or if the current mode does not have a framebuffer.
+fn get_framebuffer(bt: &BootServices, image_handle: Handle) -> Option<Framebuffer> {
+    let gop_handle = bt.get_handle_for_protocol::<GraphicsOutput>().ok()?;
+    // The GOP is only queried, so it is not opened exclusively. Doing so would disconnect the
+    // firmware's graphics console, and any further logs would stop appearing on screen.
+    //
+    // `gop` is dropped at the end of this function, and nothing in between can uninstall the
+    // protocol, so it stays valid for as long as it is in use.
+    let mut gop = unsafe {
+        bt.open_protocol::<GraphicsOutput>(
+            OpenProtocolParams {
+                handle: gop_handle,
+                agent: image_handle,
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+
+    let mode_info = gop.current_mode_info();
+    let pixel_format = match mode_info.pixel_format() {
+        gop::PixelFormat::Rgb => PixelFormat::Rgb,
+        gop::PixelFormat::Bgr => PixelFormat::Bgr,
+        gop::PixelFormat::Bitmask => {
+            let bitmask = mode_info.pixel_bitmask()?;
+            PixelFormat::Bitmask {
+                red: bitmask.red,
+                green: bitmask.green,
+                blue: bitmask.blue,
+            }
+        }
+        // There is no framebuffer in this mode
+        gop::PixelFormat::BltOnly => return None,
+    };
+    let (width, height) = mode_info.resolution();
+
+    Some(Framebuffer {
+        base: gop.frame_buffer().as_mut_ptr() as usize,
+        width: width as u32,
+        height: height as u32,
+        stride: mode_info.stride() as u32,
+        pixel_format,
+    })
 }
 
 #[entry]
@@ -74,6 +247,24 @@ fn boot_uefi_entry(image_handle: Handle, mut system_table: SystemTable<Boot>) ->
         "Addressing Width {{ physical: {}, linear: {} }}",
         physical, linear
     );
+    match unsafe { cpuid_hypervisor_vendor() } {
+        Some(vendor) => {
+            let vendor = core::str::from_utf8(&vendor).unwrap_or("<invalid>");
+            info!("Hypervisor: {}", vendor.trim_end_matches('\0'));
+        }
+        None => info!("Hypervisor: none"),
+    }
+
+    let mut memory_map = memory_map(bt);
+    normalize_memory_map(&mut memory_map);
+    let conventional_bytes: usize = memory_map.iter().map(|(_, size)| size).sum();
+    info!(
+        "Conventional Memory {{ regions: {}, bytes: {conventional_bytes:#x} }}",
+        memory_map.len()
+    );
+
+    let framebuffer = get_framebuffer(bt, image_handle);
+    info!("{framebuffer:?}");
 
     // Output program info
     {
@@ -91,7 +282,7 @@ fn boot_uefi_entry(image_handle: Handle, mut system_table: SystemTable<Boot>) ->
         debug!("PROGRAM_SIZE : {:#x}", image_size);
     }
 
-    let _root_directory = {
+    let root_directory = {
         let bt = system_table.boot_services();
         // Get the file system that the bootloader image was loaded from
         // NOTE: This type of `expect`-based error logging is quick to write, but
@@ -109,5 +300,24 @@ fn boot_uefi_entry(image_handle: Handle, mut system_table: SystemTable<Boot>) ->
             .expect("Could not get root directory of boot image's file system!")
     };
 
-    panic!("End of bootloader reached");
+    // Everything that uses boot services needs to be dropped before they are exited
+    drop(memory_map);
+    drop(root_directory);
+    let boot_info = exit_boot_services(system_table, image_handle, framebuffer);
+
+    // Logging through UEFI no longer works, so use the first serial port instead
+    let mut serial = unsafe { Uart16550::new(COM1) };
+    serial.init(MAX_BAUD_RATE);
+    let _ = writeln!(serial, "Exited UEFI boot services");
+
+    // The firmware's console can no longer be used, so draw to the framebuffer instead
+    if let Some(framebuffer) = boot_info.framebuffer {
+        if let Ok(mut console) = unsafe { FramebufferConsole::new(framebuffer) } {
+            console.clear();
+            let _ = writeln!(console, "Exited UEFI boot services");
+        }
+    }
+
+    // TODO: Load a kernel and pass it `boot_info`
+    halt()
 }
@@ -0,0 +1,40 @@
+//! A timer based on the ARM generic timer's physical counter.
+
+use super::system_registers::{read_cntfrq, read_cntpct};
+use crate::developing_modules::timer::Timer;
+
+/// A [`Timer`] that reads the physical count of the ARM generic timer (`CNTPCT_EL0`).
+#[derive(Clone, Copy, Debug)]
+pub struct GenericTimer {
+    ticks_per_second: u64,
+}
+
+impl GenericTimer {
+    /// Returns a [`GenericTimer`] using the counter frequency in `CNTFRQ_EL0`.
+    ///
+    /// # Safety
+    ///
+    /// `CNTFRQ_EL0` must have been set to the counter's frequency by the firmware, and the
+    /// physical counter must be accessible at the current exception level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `CNTFRQ_EL0` is zero.
+    pub unsafe fn new() -> Self {
+        let frequency = read_cntfrq();
+        assert_ne!(frequency, 0, "CNTFRQ_EL0 has not been set");
+        Self {
+            ticks_per_second: frequency as u64,
+        }
+    }
+}
+
+impl Timer for GenericTimer {
+    fn now_ticks(&self) -> u64 {
+        unsafe { read_cntpct() }
+    }
+
+    fn ticks_per_second(&self) -> u64 {
+        self.ticks_per_second
+    }
+}
@@ -1 +1,2 @@
+pub mod generic_timer;
 pub mod system_registers;
\ No newline at end of file
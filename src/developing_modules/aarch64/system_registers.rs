@@ -24,6 +24,38 @@ pub unsafe fn physical_address_width() -> u8 {
     }
 }
 
+/// Returns the virtual addressing width in bits.
+///
+/// If TCR_EL1 has been configured (T0SZ is not zero), this is the width of the region addressed
+/// by TTBR0_EL1, `64 - T0SZ`. Otherwise, this is the largest virtual addressing width supported
+/// by the CPU.
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher.
+///
+/// # Panic
+///
+/// Panics if the retrieved supported virtual addressing width is invalid.
+pub unsafe fn virtual_address_width() -> u8 {
+    let t0sz = read_tcr_el1().t0sz();
+    if t0sz != 0 {
+        return 64 - t0sz;
+    }
+
+    let result: u64;
+    asm!("mrs {result}, ID_AA64MMFR2_EL1",
+         result = out(reg) result);
+
+    let [_, _, virtual_range, ..] = result.to_le_bytes();
+    let virtual_range = virtual_range & 0xf;
+    match virtual_range {
+        0b0000 => 48,
+        0b0001 => 52,
+        _ => panic!("Invalid virtual address width for Aarch64: {}", virtual_range)
+    }
+}
+
 #[derive(Debug)]
 pub enum ExceptionLevel {
     EL0 = 0b00,
@@ -46,4 +78,293 @@ pub unsafe fn current_exception_level() -> ExceptionLevel {
         0b11 => ExceptionLevel::EL3,
         _ => panic!("Invalid exception level for Aarch64: {}", exception_level)
     }
-}
\ No newline at end of file
+}
+
+/// The granule size used for translation tables walked from TTBR0_EL1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GranuleSize {
+    Size4KiB = 0b00,
+    Size64KiB = 0b01,
+    Size16KiB = 0b10
+}
+
+/// The cacheability of memory accesses made during translation table walks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Cacheability {
+    NonCacheable = 0b00,
+    WriteBackReadAllocateWriteAllocate = 0b01,
+    WriteThroughReadAllocate = 0b10,
+    WriteBackReadAllocate = 0b11
+}
+
+/// The error type returned when building a [`TcrEl1`] value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TcrEl1Error {
+    /// T0SZ is outside of the supported range of 16 to 39.
+    InvalidT0sz,
+    /// The virtual address range selected by T0SZ cannot cover every physical address.
+    T0szTooLarge
+}
+
+/// A builder for the raw value of the Translation Control Register (TCR_EL1).
+///
+/// Only the fields for translation tables walked from TTBR0_EL1 are supported.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TcrEl1(u64);
+
+impl TcrEl1 {
+    const T0SZ_SHIFT: u64 = 0;
+    const T0SZ_MASK: u64 = 0b11_1111;
+    const IRGN0_SHIFT: u64 = 8;
+    const ORGN0_SHIFT: u64 = 10;
+    const CACHEABILITY_MASK: u64 = 0b11;
+    const TG0_SHIFT: u64 = 14;
+    const TG0_MASK: u64 = 0b11;
+
+    const MIN_T0SZ: u8 = 16;
+    const MAX_T0SZ: u8 = 39;
+
+    /// Returns a value with every field set to zero.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Returns the raw value of this register.
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Returns a register value from a raw `bits` value.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the size offset of the region addressed by TTBR0_EL1.
+    ///
+    /// The region is `2^(64 - T0SZ)` bytes in size.
+    pub const fn t0sz(self) -> u8 {
+        ((self.0 >> Self::T0SZ_SHIFT) & Self::T0SZ_MASK) as u8
+    }
+
+    /// Sets the inner and outer cacheability of translation table walks.
+    pub const fn with_cacheability(self, inner: Cacheability, outer: Cacheability) -> Self {
+        let mask = (Self::CACHEABILITY_MASK << Self::IRGN0_SHIFT)
+            | (Self::CACHEABILITY_MASK << Self::ORGN0_SHIFT);
+        let bits = ((inner as u64) << Self::IRGN0_SHIFT) | ((outer as u64) << Self::ORGN0_SHIFT);
+        Self((self.0 & !mask) | bits)
+    }
+
+    /// Sets the granule size of translation tables.
+    pub const fn with_granule_size(self, granule_size: GranuleSize) -> Self {
+        let mask = Self::TG0_MASK << Self::TG0_SHIFT;
+        Self((self.0 & !mask) | ((granule_size as u64) << Self::TG0_SHIFT))
+    }
+
+    /// Sets the size offset of the region addressed by TTBR0_EL1.
+    ///
+    /// `physical_width` is the physical addressing width in bits, as returned by
+    /// [`physical_address_width`].
+    ///
+    /// # Errors
+    ///
+    /// * [`TcrEl1Error::InvalidT0sz`]: `t0sz` is not in between 16 and 39
+    /// * [`TcrEl1Error::T0szTooLarge`]: the virtual addressing width, `64 - t0sz`, is less than
+    /// `physical_width`
+    pub const fn with_t0sz(self, t0sz: u8, physical_width: u8) -> Result<Self, TcrEl1Error> {
+        if t0sz < Self::MIN_T0SZ || t0sz > Self::MAX_T0SZ {
+            return Err(TcrEl1Error::InvalidT0sz);
+        }
+        if 64 - t0sz < physical_width {
+            return Err(TcrEl1Error::T0szTooLarge);
+        }
+
+        let mask = Self::T0SZ_MASK << Self::T0SZ_SHIFT;
+        Ok(Self((self.0 & !mask) | ((t0sz as u64) << Self::T0SZ_SHIFT)))
+    }
+}
+
+/// Returns the value of the Translation Control Register (TCR_EL1).
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher.
+pub unsafe fn read_tcr_el1() -> TcrEl1 {
+    let result: u64;
+    asm!("mrs {result}, TCR_EL1",
+         result = out(reg) result);
+
+    TcrEl1::from_bits(result)
+}
+
+/// Sets the Translation Control Register (TCR_EL1) to `tcr`.
+///
+/// A `tcr` value can be built using [`TcrEl1`].
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher. Changing the translation controls while the MMU is enabled
+/// can change how every address is translated.
+pub unsafe fn write_tcr_el1(tcr: u64) {
+    asm!("msr TCR_EL1, {tcr}",
+         "isb",
+         tcr = in(reg) tcr);
+}
+
+/// Returns the value of Translation Table Base Register 0 (TTBR0_EL1).
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher.
+pub unsafe fn read_ttbr0_el1() -> u64 {
+    let result: u64;
+    asm!("mrs {result}, TTBR0_EL1",
+         result = out(reg) result);
+
+    result
+}
+
+/// Sets Translation Table Base Register 0 (TTBR0_EL1) to `base`.
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher. `base` must be the physical address of a valid translation
+/// table if the MMU is enabled.
+pub unsafe fn write_ttbr0_el1(base: u64) {
+    asm!("msr TTBR0_EL1, {base}",
+         "isb",
+         base = in(reg) base);
+}
+
+/// The MMU enable bit of SCTLR_EL1.
+const SCTLR_EL1_M: u64 = 1 << 0;
+/// The data cache enable bit of SCTLR_EL1.
+const SCTLR_EL1_C: u64 = 1 << 2;
+/// The instruction cache enable bit of SCTLR_EL1.
+const SCTLR_EL1_I: u64 = 1 << 12;
+
+/// Returns the value of the System Control Register (SCTLR_EL1).
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher.
+pub unsafe fn read_sctlr_el1() -> u64 {
+    let result: u64;
+    asm!("mrs {result}, SCTLR_EL1",
+         result = out(reg) result);
+
+    result
+}
+
+/// Sets the System Control Register (SCTLR_EL1) to `sctlr`.
+///
+/// An instruction barrier is issued after the write, so that every following instruction uses
+/// the new system controls.
+unsafe fn write_sctlr_el1(sctlr: u64) {
+    asm!("msr SCTLR_EL1, {sctlr}",
+         "isb",
+         sctlr = in(reg) sctlr);
+}
+
+/// Enables the MMU for EL1 and EL0.
+///
+/// # Safety
+///
+/// Must be running at EL1. TCR_EL1 and TTBR0_EL1 must already point to valid translation tables
+/// that map the currently running code.
+pub unsafe fn enable_mmu() {
+    write_sctlr_el1(read_sctlr_el1() | SCTLR_EL1_M);
+}
+
+/// Disables the MMU for EL1 and EL0.
+///
+/// # Safety
+///
+/// Must be running at EL1. The currently running code must be identity mapped, as every address
+/// becomes a physical address.
+pub unsafe fn disable_mmu() {
+    write_sctlr_el1(read_sctlr_el1() & !SCTLR_EL1_M);
+}
+
+/// Enables the instruction and data caches for EL1 and EL0.
+///
+/// # Safety
+///
+/// Must be running at EL1.
+pub unsafe fn enable_caches() {
+    write_sctlr_el1(read_sctlr_el1() | SCTLR_EL1_C | SCTLR_EL1_I);
+}
+
+/// Returns a flat ID for the current core, packed from its affinity levels in MPIDR_EL1.
+///
+/// The ID is packed as `(Aff2 << 16) | (Aff1 << 8) | Aff0`. Aff3 (bits 32 to 39 of MPIDR_EL1)
+/// is not included.
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher.
+pub unsafe fn current_core_id() -> u64 {
+    let result: u64;
+    asm!("mrs {result}, MPIDR_EL1",
+         result = out(reg) result);
+
+    // Aff0, Aff1, and Aff2 are already packed in the lowest 3 bytes
+    result & 0xff_ffff
+}
+
+/// Returns true if the current core is the primary core, which has a packed core ID of zero.
+///
+/// # Safety
+///
+/// Must be running at EL1 or higher.
+pub unsafe fn is_primary_core() -> bool {
+    current_core_id() == 0
+}
+
+/// Returns the value of the generic timer's physical counter (CNTPCT_EL0).
+///
+/// An instruction barrier is issued before the read, so that the counter is not read early, out of
+/// order with earlier instructions.
+///
+/// # Safety
+///
+/// The physical counter must be accessible at the current exception level.
+pub unsafe fn read_cntpct() -> u64 {
+    let result: u64;
+    asm!("isb",
+         "mrs {result}, CNTPCT_EL0",
+         result = out(reg) result);
+
+    result
+}
+
+/// Returns the frequency of the generic timer's counter in Hz (CNTFRQ_EL0).
+///
+/// This register is set by the firmware, and is zero if the firmware did not set it.
+///
+/// # Safety
+///
+/// The counter frequency must be accessible at the current exception level.
+pub unsafe fn read_cntfrq() -> u32 {
+    let result: u64;
+    asm!("mrs {result}, CNTFRQ_EL0",
+         result = out(reg) result);
+
+    // Only the lowest 32 bits are used for the frequency
+    result as u32
+}
+
+/// Busy-waits for at least `us` microseconds, using the generic timer's physical counter.
+///
+/// # Safety
+///
+/// The physical counter and its frequency must be accessible at the current exception level, and
+/// the firmware must have set CNTFRQ_EL0.
+pub unsafe fn delay_us(us: u64) {
+    let ticks = (us as u128 * read_cntfrq() as u128 / 1_000_000)
+        .try_into()
+        .unwrap_or(u64::MAX);
+    let start = read_cntpct();
+    while read_cntpct().wrapping_sub(start) < ticks {
+        core::hint::spin_loop();
+    }
+}
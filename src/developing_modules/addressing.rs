@@ -1,3 +1,6 @@
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct PhysicalAddress(usize);
 
 pub struct VirtualAddress(usize);
@@ -6,11 +9,189 @@ pub struct InvalidPhysicalAddress(usize);
 
 pub struct InvalidVirtualAddress(usize);
 
-// impl PhysicalAddress {
-//     pub unsafe fn new(addr: usize) -> Self {
-//     }
+impl PhysicalAddress {
+    /// Returns a physical address without checking if it is valid.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be a valid physical address on the current CPU.
+    pub const unsafe fn new(addr: usize) -> Self {
+        Self(addr)
+    }
+
+    // pub fn try_new(addr: usize) -> Result<Self, InvalidPhysicalAddress> {
+
+    // }
+
+    /// Returns the address as a `usize`.
+    pub const fn as_usize(&self) -> usize {
+        self.0
+    }
+}
+
+/// A half-open range of addresses, from `start` up to but not including `start + len`.
+///
+/// The range must not extend past the end of the address space, but it may end exactly at it.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct AddressRange {
+    /// The first address in the range.
+    pub start: usize,
+    /// The number of addresses in the range.
+    pub len: usize,
+}
+
+impl AddressRange {
+    /// Returns the range of `len` addresses starting at `start`.
+    pub const fn new(start: usize, len: usize) -> Self {
+        Self { start, len }
+    }
+
+    /// Returns true if `addr` is inside this range.
+    pub const fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr - self.start < self.len
+    }
+
+    /// Returns true if the range contains no addresses.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns true if `other` starts directly after this range ends, or ends directly before this
+    /// range starts.
+    ///
+    /// Adjacent ranges do not overlap.
+    pub fn is_adjacent(&self, other: &AddressRange) -> bool {
+        self.start.checked_add(self.len) == Some(other.start)
+            || other.start.checked_add(other.len) == Some(self.start)
+    }
+
+    /// Returns the range that covers both this range and `other`, if they overlap or are adjacent.
+    pub fn merge(&self, other: &AddressRange) -> Option<AddressRange> {
+        if !self.overlaps(other) && !self.is_adjacent(other) {
+            return None;
+        }
+
+        let start = self.start.min(other.start);
+        // Lengths are measured from `start` so that a range ending at the top of the address space
+        // does not overflow
+        let len = (self.start - start + self.len).max(other.start - start + other.len);
+        Some(Self { start, len })
+    }
+
+    /// Returns true if any address is inside both this range and `other`.
+    ///
+    /// Empty ranges never overlap.
+    pub const fn overlaps(&self, other: &AddressRange) -> bool {
+        (self.contains(other.start) && !other.is_empty())
+            || (other.contains(self.start) && !self.is_empty())
+    }
+}
+
+/// Sorts the `(address, size)` entries of a memory map by address, merges entries that overlap or
+/// are adjacent, and removes entries with a size of zero.
+///
+/// The result can be passed to allocators that require sorted regions that do not overlap, such
+/// as [`PhysicalAllocator::new`](super::physical_allocator::PhysicalAllocator::new).
+pub fn normalize_memory_map(entries: &mut Vec<(usize, usize)>) {
+    entries.retain(|&(_, size)| size != 0);
+    entries.sort_unstable_by_key(|&(addr, _)| addr);
+    // Each entry is merged into the one before it if possible, so runs of entries become one
+    entries.dedup_by(|&mut (addr, size), previous| {
+        let range = AddressRange::new(addr, size);
+        match AddressRange::new(previous.0, previous.1).merge(&range) {
+            Some(merged) => {
+                *previous = (merged.start, merged.len);
+                true
+            }
+            None => false,
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    /// Ensures that:
+    ///
+    /// * The end of a range is not contained in it
+    /// * Touching ranges are adjacent, but do not overlap
+    /// * Empty ranges never overlap
+    /// * Only overlapping and adjacent ranges can be merged
+    /// * A range that ends at the top of the address space can be merged
+    #[test]
+    fn address_ranges() {
+        let range = AddressRange::new(0x1000, 0x1000);
+        assert!(range.contains(0x1000));
+        assert!(range.contains(0x1fff));
+        assert!(!range.contains(0x2000));
+        assert!(!range.contains(0xfff));
+
+        let after = AddressRange::new(0x2000, 0x1000);
+        let before = AddressRange::new(0, 0x1000);
+        assert!(range.is_adjacent(&after) && after.is_adjacent(&range));
+        assert!(range.is_adjacent(&before) && before.is_adjacent(&range));
+        assert!(!range.overlaps(&after) && !after.overlaps(&range));
+        assert!(!range.overlaps(&before) && !before.overlaps(&range));
+        assert!(!before.is_adjacent(&after));
+
+        let inside = AddressRange::new(0x1800, 0x100);
+        let crossing = AddressRange::new(0x1fff, 0x10);
+        assert!(range.overlaps(&inside) && inside.overlaps(&range));
+        assert!(range.overlaps(&crossing) && crossing.overlaps(&range));
+        assert!(range.overlaps(&range));
+
+        let empty = AddressRange::new(0x1800, 0);
+        assert!(empty.is_empty());
+        assert!(!range.overlaps(&empty) && !empty.overlaps(&range));
+
+        assert_eq!(range.merge(&after), Some(AddressRange::new(0x1000, 0x2000)));
+        assert_eq!(after.merge(&range), Some(AddressRange::new(0x1000, 0x2000)));
+        assert_eq!(range.merge(&inside), Some(range));
+        assert_eq!(
+            range.merge(&crossing),
+            Some(AddressRange::new(0x1000, 0x100f))
+        );
+        assert_eq!(before.merge(&after), None);
+
+        let top = AddressRange::new(usize::MAX - 0xfff, 0x1000);
+        let below_top = AddressRange::new(usize::MAX - 0x1fff, 0x1000);
+        assert!(!top.contains(0));
+        assert!(top.is_adjacent(&below_top));
+        assert_eq!(
+            top.merge(&below_top),
+            Some(AddressRange::new(usize::MAX - 0x1fff, 0x2000))
+        );
+    }
 
-//     pub fn try_new(addr: usize) -> Result<Self, InvalidPhysicalAddress> {
+    /// Ensures that:
+    ///
+    /// * Memory map entries are sorted by address
+    /// * Adjacent and overlapping entries are merged, including runs of more than two entries
+    /// * Empty entries are removed
+    #[test]
+    fn normalize_memory_maps() {
+        let mut memory_map = vec![
+            (0x9000, 0x1000),
+            (0x1000, 0x1000),
+            (0x5000, 0),
+            (0x20000, 0x4000),
+            // Adjacent to the entry at 0x1000
+            (0x2000, 0x2000),
+            // Overlaps with the end of the entry at 0x20000
+            (0x23000, 0x2000),
+            // Adjacent to the merged entry at 0x1000
+            (0x4000, 0x1000),
+        ];
+        normalize_memory_map(&mut memory_map);
+        assert_eq!(
+            memory_map,
+            [(0x1000, 0x4000), (0x9000, 0x1000), (0x20000, 0x5000)]
+        );
 
-//     }
-// }
+        let mut empty = vec![(0x1000, 0)];
+        normalize_memory_map(&mut empty);
+        assert!(empty.is_empty());
+    }
+}
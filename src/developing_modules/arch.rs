@@ -0,0 +1,26 @@
+//! Helpers that have a different implementation on each architecture.
+
+#[cfg(any(target_arch = "aarch64", target_arch = "x86_64"))]
+use core::arch::asm;
+
+/// Stops the CPU forever.
+///
+/// The CPU sleeps until the next interrupt (with `wfi` on aarch64 and `hlt` on x86_64) and then
+/// goes back to sleep, so that it does not spin at full speed. Other architectures fall back to a
+/// spin loop.
+pub fn halt() -> ! {
+    loop {
+        #[cfg(target_arch = "aarch64")]
+        unsafe {
+            asm!("wfi", options(nomem, nostack, preserves_flags))
+        };
+
+        #[cfg(target_arch = "x86_64")]
+        unsafe {
+            asm!("hlt", options(nomem, nostack, preserves_flags))
+        };
+
+        #[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+        core::hint::spin_loop();
+    }
+}
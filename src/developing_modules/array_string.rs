@@ -0,0 +1,154 @@
+//! A string with a fixed capacity, for use before a heap is available.
+
+use core::{fmt, ops::Deref, str};
+
+/// The error type returned when a string does not fit in an [`ArrayString`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CapacityError;
+
+/// A UTF-8 string that is stored inline in a buffer of `N` bytes.
+///
+/// Unlike `String`, this never allocates, and fails instead of growing when it is full.
+#[derive(Clone, Copy)]
+pub struct ArrayString<const N: usize> {
+    buffer: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ArrayString<N> {
+    /// Returns an empty string.
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the string as a `&str`.
+    pub fn as_str(&self) -> &str {
+        // The buffer only ever has whole `&str`s copied into it
+        unsafe { str::from_utf8_unchecked(&self.buffer[..self.len]) }
+    }
+
+    /// Returns the number of bytes that the string can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Empties the string.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Appends `string` to the end of this string.
+    ///
+    /// # Errors
+    ///
+    /// * [`CapacityError`]: there are fewer than `string.len()` bytes left, in which case this
+    /// string is unchanged
+    pub fn push_str(&mut self, string: &str) -> Result<(), CapacityError> {
+        let end = self
+            .len
+            .checked_add(string.len())
+            .filter(|&end| end <= N)
+            .ok_or(CapacityError)?;
+        self.buffer[self.len..end].copy_from_slice(string.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+impl<const N: usize> Default for ArrayString<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Deref for ArrayString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<const N: usize> fmt::Debug for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<const N: usize> fmt::Display for ArrayString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Allows using `write!` to build a string. Writes that do not fit return [`fmt::Error`].
+impl<const N: usize> fmt::Write for ArrayString<N> {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.push_str(string).map_err(|_| fmt::Error)
+    }
+}
+
+impl<const N: usize> Eq for ArrayString<N> {}
+
+impl<const N: usize, const M: usize> PartialEq<ArrayString<M>> for ArrayString<N> {
+    fn eq(&self, other: &ArrayString<M>) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<const N: usize> PartialEq<str> for ArrayString<N> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for ArrayString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for ArrayString<N> {
+    type Error = CapacityError;
+
+    fn try_from(string: &str) -> Result<Self, CapacityError> {
+        let mut array_string = Self::new();
+        array_string.push_str(string)?;
+        Ok(array_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    /// Ensures that:
+    ///
+    /// * Strings can be built up to their capacity, but not past it
+    /// * A failed push leaves the string unchanged
+    /// * Strings compare equal to `&str`s and to strings of other capacities
+    #[test]
+    fn array_string() {
+        let mut path = ArrayString::<12>::try_from("/boot").unwrap();
+        path.push_str("/kernel").unwrap();
+        assert_eq!(path, "/boot/kernel");
+        assert_eq!(path.len(), path.capacity());
+
+        assert_eq!(path.push_str("!"), Err(CapacityError));
+        assert_eq!(path.as_str(), "/boot/kernel");
+        assert_eq!(ArrayString::<4>::try_from("/boot"), Err(CapacityError));
+
+        assert_eq!(path, ArrayString::<32>::try_from("/boot/kernel").unwrap());
+        assert!(path.starts_with("/boot"));
+
+        path.clear();
+        assert!(path.is_empty());
+        write!(path, "/{}", 42).unwrap();
+        assert_eq!(path, "/42");
+        assert!(write!(path, "{:>10}", 0).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! A vector with a fixed capacity, for use before a heap is available.
+
+use core::{
+    fmt,
+    mem::MaybeUninit,
+    ops::{Deref, DerefMut},
+    ptr, slice,
+};
+
+/// A vector that stores up to `N` items inline.
+///
+/// Unlike `Vec`, this never allocates, and fails instead of growing when it is full. It can be
+/// indexed and iterated through its slice of items.
+pub struct ArrayVec<T, const N: usize> {
+    /// Only the first `len` items are initialized.
+    items: MaybeUninit<[T; N]>,
+    len: usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    /// Returns an empty vector.
+    pub const fn new() -> Self {
+        Self {
+            items: MaybeUninit::uninit(),
+            len: 0,
+        }
+    }
+
+    /// Returns the initialized items as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.items.as_ptr().cast::<T>(), self.len) }
+    }
+
+    /// Returns the initialized items as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.items.as_mut_ptr().cast::<T>(), self.len) }
+    }
+
+    /// Returns the number of items that the vector can hold.
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Drops every item, leaving the vector empty.
+    pub fn clear(&mut self) {
+        let items: *mut [T] = self.as_mut_slice();
+        // The length is reset first, so that a panic while dropping an item cannot cause the items
+        // to be dropped again
+        self.len = 0;
+        unsafe { ptr::drop_in_place(items) };
+    }
+
+    /// Returns true if the vector has no room for another item.
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Removes the last item and returns it, or returns `None` if the vector is empty.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        // The item at the old last index is initialized, and is no longer counted in `len`
+        Some(unsafe { self.items.as_ptr().cast::<T>().add(self.len).read() })
+    }
+
+    /// Adds `item` to the end of the vector.
+    ///
+    /// # Errors
+    ///
+    /// Returns `item` back if the vector is full.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(item);
+        }
+
+        unsafe {
+            self.items
+                .as_mut_ptr()
+                .cast::<T>()
+                .add(self.len)
+                .write(item)
+        };
+        self.len += 1;
+        Ok(())
+    }
+}
+
+impl<T, const N: usize> Default for ArrayVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for ArrayVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a ArrayVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+
+    fn into_iter(self) -> slice::Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut ArrayVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> slice::IterMut<'a, T> {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    /// Ensures that:
+    ///
+    /// * Items can be pushed until the vector is full, and a rejected item is returned
+    /// * Items can be indexed, iterated and popped in order
+    #[test]
+    fn push_and_pop() {
+        let mut memory_map: ArrayVec<(usize, usize), 3> = ArrayVec::new();
+        assert!(memory_map.is_empty());
+        for region in [(0x1000, 0x1000), (0x4000, 0x2000), (0x9000, 0x100)] {
+            memory_map.push(region).unwrap();
+        }
+        assert!(memory_map.is_full());
+        assert_eq!(memory_map.push((0, 0)), Err((0, 0)));
+
+        assert_eq!(memory_map[1], (0x4000, 0x2000));
+        memory_map[2].1 = 0x200;
+        let total: usize = memory_map.iter().map(|(_, size)| size).sum();
+        assert_eq!(total, 0x3200);
+        for region in &mut memory_map {
+            region.0 += 1;
+        }
+        assert_eq!(memory_map.first(), Some(&(0x1001, 0x1000)));
+
+        assert_eq!(memory_map.pop(), Some((0x9001, 0x200)));
+        assert_eq!(memory_map.len(), 2);
+        assert_eq!(memory_map.pop(), Some((0x4001, 0x2000)));
+        assert_eq!(memory_map.pop(), Some((0x1001, 0x1000)));
+        assert_eq!(memory_map.pop(), None);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Items are dropped when the vector is cleared or dropped
+    /// * Popped and rejected items are not dropped by the vector
+    #[test]
+    fn drop_items() {
+        let item = Rc::new(0);
+        let mut items: ArrayVec<Rc<i32>, 4> = ArrayVec::new();
+        for _ in 0..4 {
+            items.push(item.clone()).unwrap();
+        }
+        let rejected = items.push(item.clone()).unwrap_err();
+        assert_eq!(Rc::strong_count(&item), 6);
+        drop(rejected);
+
+        let popped = items.pop().unwrap();
+        assert_eq!(Rc::strong_count(&item), 5);
+        drop(popped);
+
+        items.clear();
+        assert_eq!(Rc::strong_count(&item), 1);
+
+        items.push(item.clone()).unwrap();
+        drop(items);
+        assert_eq!(Rc::strong_count(&item), 1);
+    }
+}
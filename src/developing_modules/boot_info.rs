@@ -0,0 +1,103 @@
+//! Information that is handed off from the bootloader to a kernel.
+
+/// The layout of a single 32-bit pixel in a [`Framebuffer`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PixelFormat {
+    /// Red is stored in the lowest byte, followed by green and blue. The highest byte is
+    /// reserved.
+    Rgb,
+    /// Blue is stored in the lowest byte, followed by green and red. The highest byte is
+    /// reserved.
+    Bgr,
+    /// The bits used by each color are given by a mask.
+    Bitmask { red: u32, green: u32, blue: u32 },
+}
+
+/// A linear framebuffer that can be drawn to directly.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Framebuffer {
+    /// The physical address of the first pixel.
+    pub base: usize,
+    /// The number of visible pixels in each row.
+    pub width: u32,
+    /// The number of rows.
+    pub height: u32,
+    /// The number of pixels between the start of each row, including any padding.
+    pub stride: u32,
+    pub pixel_format: PixelFormat,
+}
+
+/// The maximum number of memory regions that can be stored in a [`BootInfo`].
+pub const MAX_MEMORY_REGIONS: usize = 128;
+
+/// Everything a kernel needs to know about the system after the bootloader is finished.
+///
+/// This does not point to any memory that is owned by the bootloader or firmware boot services,
+/// so it remains valid after the firmware's boot services are exited.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootInfo {
+    /// The `(address, size)` of every region of free memory.
+    memory_map: [(usize, usize); MAX_MEMORY_REGIONS],
+    memory_map_len: usize,
+    pub framebuffer: Option<Framebuffer>,
+    /// The physical address of the ACPI Root System Description Pointer (RSDP).
+    pub rsdp: Option<usize>,
+}
+
+impl BootInfo {
+    /// Returns boot information with an empty memory map.
+    pub const fn new(framebuffer: Option<Framebuffer>, rsdp: Option<usize>) -> Self {
+        Self {
+            memory_map: [(0, 0); MAX_MEMORY_REGIONS],
+            memory_map_len: 0,
+            framebuffer,
+            rsdp,
+        }
+    }
+
+    /// Returns the `(address, size)` of every region of free memory.
+    pub fn memory_map(&self) -> &[(usize, usize)] {
+        &self.memory_map[..self.memory_map_len]
+    }
+
+    /// Adds a region of free memory to the memory map.
+    ///
+    /// Returns false if the memory map already contains [`MAX_MEMORY_REGIONS`] regions.
+    pub fn push_memory_region(&mut self, addr: usize, size: usize) -> bool {
+        if self.memory_map_len == MAX_MEMORY_REGIONS {
+            return false;
+        }
+
+        self.memory_map[self.memory_map_len] = (addr, size);
+        self.memory_map_len += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures that:
+    ///
+    /// * Memory regions are returned in the order they were added
+    /// * Memory regions are not added after the memory map is full
+    #[test]
+    fn memory_map() {
+        let mut boot_info = BootInfo::new(None, None);
+        assert!(boot_info.memory_map().is_empty());
+
+        for i in 0..MAX_MEMORY_REGIONS {
+            assert!(boot_info.push_memory_region(i * 0x1000, 0x1000));
+        }
+        assert!(!boot_info.push_memory_region(0, 0x1000));
+
+        let memory_map = boot_info.memory_map();
+        assert_eq!(memory_map.len(), MAX_MEMORY_REGIONS);
+        assert_eq!(memory_map[0], (0, 0x1000));
+        assert_eq!(
+            memory_map[MAX_MEMORY_REGIONS - 1],
+            ((MAX_MEMORY_REGIONS - 1) * 0x1000, 0x1000)
+        );
+    }
+}
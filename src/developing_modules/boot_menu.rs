@@ -0,0 +1,176 @@
+//! A menu for choosing which boot entry to boot.
+
+use super::config::BootEntry;
+#[cfg(not(test))]
+use log::info;
+#[cfg(test)]
+use std::println as info;
+
+/// A key that was pressed while the boot menu is shown.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Key {
+    Up,
+    Down,
+    Enter,
+    Char(char),
+    /// Any other key, such as a function key.
+    Other,
+}
+
+/// A source of key presses for the boot menu, such as a firmware's text input protocol.
+pub trait KeyInput {
+    /// Waits for a key to be pressed and returns it.
+    ///
+    /// Returns `None` if no key was pressed within `timeout` seconds, or if no more keys can be
+    /// read. If `timeout` is `None`, this waits until a key is pressed.
+    fn wait_for_key(&mut self, timeout: Option<u32>) -> Option<Key>;
+}
+
+/// Shows a menu of `entries` and returns the index of the entry to boot.
+///
+/// The first entry is booted after `timeout` seconds, unless a key is pressed to cancel the
+/// countdown. Once the countdown is cancelled, an entry is chosen with the arrow keys and booted
+/// with enter, or booted directly by pressing its number.
+///
+/// # Panics
+///
+/// Panics if `entries` is empty.
+pub fn boot_menu(entries: &[BootEntry], timeout: u32, input: &mut dyn KeyInput) -> usize {
+    assert!(
+        !entries.is_empty(),
+        "The boot menu needs at least one entry"
+    );
+
+    let mut selected = 0;
+    print_entries(entries, selected);
+
+    let mut cancelled = false;
+    for remaining in (1..=timeout).rev() {
+        info!("Booting entry 0 in {remaining}s, press any key to choose another entry");
+        if input.wait_for_key(Some(1)).is_some() {
+            cancelled = true;
+            break;
+        }
+    }
+    if !cancelled {
+        return selected;
+    }
+
+    while let Some(key) = input.wait_for_key(None) {
+        match key {
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected = (selected + 1).min(entries.len() - 1),
+            Key::Enter => break,
+            Key::Char(c) => match c.to_digit(10) {
+                Some(index) if (index as usize) < entries.len() => return index as usize,
+                _ => continue,
+            },
+            Key::Other => continue,
+        }
+        print_entries(entries, selected);
+    }
+    selected
+}
+
+fn print_entries(entries: &[BootEntry], selected: usize) {
+    for (index, entry) in entries.iter().enumerate() {
+        let marker = if index == selected { '>' } else { ' ' };
+        let name = if entry.name.is_empty() {
+            &entry.kernel_path
+        } else {
+            &entry.name
+        };
+        info!("{marker} {index}: {name}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{string::ToString, vec::Vec};
+
+    /// Replays a list of key presses, where `None` is a second passing without a key press.
+    struct ScriptedInput {
+        keys: Vec<Option<Key>>,
+        waits: usize,
+    }
+
+    impl ScriptedInput {
+        fn new(keys: &[Option<Key>]) -> Self {
+            Self {
+                keys: keys.iter().rev().copied().collect(),
+                waits: 0,
+            }
+        }
+    }
+
+    impl KeyInput for ScriptedInput {
+        fn wait_for_key(&mut self, timeout: Option<u32>) -> Option<Key> {
+            self.waits += 1;
+            match self.keys.pop() {
+                Some(key) => key,
+                None if timeout.is_some() => None,
+                None => panic!("Waited forever for a key"),
+            }
+        }
+    }
+
+    fn entries() -> Vec<BootEntry> {
+        ["Caliga", "Recovery", ""]
+            .iter()
+            .map(|name| BootEntry {
+                name: name.to_string(),
+                kernel_path: "/kernel.elf".to_string(),
+                initramfs_path: None,
+                cmdline: "".to_string(),
+                kernel_crc32: None,
+                kernel_sha256: None,
+            })
+            .collect()
+    }
+
+    /// Ensures that:
+    ///
+    /// * The first entry is booted when the timeout runs out, after waiting once per second
+    /// * A timeout of zero boots the first entry without waiting
+    #[test]
+    fn timeout() {
+        let mut input = ScriptedInput::new(&[]);
+        assert_eq!(boot_menu(&entries(), 3, &mut input), 0);
+        assert_eq!(input.waits, 3);
+
+        let mut input = ScriptedInput::new(&[]);
+        assert_eq!(boot_menu(&entries(), 0, &mut input), 0);
+        assert_eq!(input.waits, 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Any key cancels the countdown
+    /// * Entries can be chosen with the arrow keys, which stop at the first and last entries
+    /// * Entries can be chosen by their number, and invalid numbers are ignored
+    #[test]
+    fn choose_entry() {
+        let mut input = ScriptedInput::new(&[
+            None,
+            Some(Key::Other),
+            Some(Key::Down),
+            Some(Key::Down),
+            Some(Key::Down),
+            Some(Key::Up),
+            Some(Key::Enter),
+        ]);
+        assert_eq!(boot_menu(&entries(), 5, &mut input), 1);
+
+        let mut input = ScriptedInput::new(&[Some(Key::Up), Some(Key::Up), Some(Key::Enter)]);
+        assert_eq!(boot_menu(&entries(), 5, &mut input), 0);
+
+        let mut input = ScriptedInput::new(&[
+            Some(Key::Enter),
+            Some(Key::Char('7')),
+            Some(Key::Char('a')),
+            Some(Key::Char('2')),
+        ]);
+        assert_eq!(boot_menu(&entries(), 5, &mut input), 2);
+    }
+}
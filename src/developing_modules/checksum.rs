@@ -0,0 +1,65 @@
+//! Checksums for verifying the integrity of loaded files.
+
+/// The reflected form of the IEEE 802.3 CRC-32 polynomial.
+const CRC32_POLYNOMIAL: u32 = 0xedb8_8320;
+
+/// A lookup table of the CRC of every byte, built at compile time.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < table.len() {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+/// Returns the CRC-32 (IEEE) checksum of `data`.
+///
+/// This is the same checksum used by zlib, gzip and `cksum -a crc32b`.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Returns whether the CRC-32 (IEEE) checksum of `data` is `expected`.
+pub fn verify_crc32(data: &[u8], expected: u32) -> bool {
+    crc32(data) == expected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures that:
+    ///
+    /// * Checksums match known values
+    /// * Verification fails if any byte changes
+    #[test]
+    fn crc32_checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+
+        let mut data = *b"123456789";
+        assert!(verify_crc32(&data, 0xcbf4_3926));
+        data[4] ^= 1;
+        assert!(!verify_crc32(&data, 0xcbf4_3926));
+    }
+}
@@ -0,0 +1,439 @@
+//! A parser for the bootloader's configuration file.
+//!
+//! The configuration file is made up of `key = value` lines. Blank lines and lines starting with
+//! `#` are ignored. Only the first `=` of a line separates the key from the value, so values
+//! (such as a kernel command line) can contain `=` and `#` themselves.
+//!
+//! Each `[entry "name"]` line starts a new boot entry, which is made up of the `kernel`,
+//! `initramfs`, `cmdline`, `kernel_crc32` and `kernel_sha256` keys that follow it. These keys can
+//! also be set before the first entry, in which case they make up an unnamed entry that is listed
+//! first. The `timeout` key can only be set before the first entry.
+//!
+//! ```
+//! # use caliga_bootloader::developing_modules::config::parse_config;
+//! let config = parse_config(
+//!     b"# Boot the default kernel\n\
+//!       timeout = 3\n\
+//!       \n\
+//!       [entry \"Caliga\"]\n\
+//!       kernel = /boot/kernel.elf\n\
+//!       cmdline = console=ttyS0 root=/dev/sda1\n",
+//! )
+//! .unwrap();
+//! assert_eq!(config.timeout, 3);
+//! assert_eq!(config.entries[0].name, "Caliga");
+//! assert_eq!(config.entries[0].kernel_path, "/boot/kernel.elf");
+//! assert_eq!(config.entries[0].initramfs_path, None);
+//! assert_eq!(config.entries[0].cmdline, "console=ttyS0 root=/dev/sda1");
+//! ```
+
+use super::crypto::sha256::DIGEST_SIZE;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// The error type returned by [`parse_config`].
+///
+/// Line numbers start at 1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigError {
+    /// The file is not valid UTF-8.
+    InvalidUtf8,
+    /// A line is not blank, a comment, an entry header or a `key = value` pair.
+    InvalidLine { line: usize },
+    /// A line sets a key that is not recognized, or that cannot be set in an entry.
+    UnknownKey { line: usize },
+    /// A line sets a key that was already set by an earlier line.
+    DuplicateKey { line: usize },
+    /// A line's value could not be parsed for its key.
+    InvalidValue { line: usize },
+    /// A required key is not set in an entry.
+    ///
+    /// `line` is the entry's header, or the first line of an unnamed entry.
+    MissingKey { key: &'static str, line: usize },
+    /// The file does not contain any boot entries.
+    NoEntries,
+}
+
+/// The boot options read from a configuration file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootConfig {
+    /// The entries that can be booted, in the order they appear in the file.
+    ///
+    /// This always contains at least one entry.
+    pub entries: Vec<BootEntry>,
+    /// The number of seconds to wait before booting, set with the `timeout` key. Zero by default.
+    pub timeout: u32,
+}
+
+/// A kernel that can be booted, along with the options to boot it with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BootEntry {
+    /// The name of the entry, set by its `[entry "name"]` header. Empty for an unnamed entry.
+    pub name: String,
+    /// The path of the kernel to boot, set with the required `kernel` key.
+    pub kernel_path: String,
+    /// The path of an initial ramdisk to load, set with the `initramfs` key.
+    pub initramfs_path: Option<String>,
+    /// The command line passed to the kernel, set with the `cmdline` key. Empty by default.
+    pub cmdline: String,
+    /// The expected CRC-32 of the kernel, set as a hexadecimal number with the `kernel_crc32` key.
+    ///
+    /// The kernel should not be booted if its checksum does not match. See
+    /// [`verify_crc32`](super::checksum::verify_crc32).
+    pub kernel_crc32: Option<u32>,
+    /// The expected SHA-256 digest of the kernel, set as 64 hexadecimal digits with the
+    /// `kernel_sha256` key.
+    ///
+    /// The kernel should not be booted if its digest does not match. See
+    /// [`Sha256`](super::crypto::sha256::Sha256).
+    pub kernel_sha256: Option<[u8; DIGEST_SIZE]>,
+}
+
+/// The keys of an entry that have been set so far.
+#[derive(Default)]
+struct PartialEntry {
+    name: String,
+    /// The entry's header, or the first line of an unnamed entry.
+    line: usize,
+    kernel_path: Option<String>,
+    initramfs_path: Option<String>,
+    cmdline: Option<String>,
+    kernel_crc32: Option<u32>,
+    kernel_sha256: Option<[u8; DIGEST_SIZE]>,
+}
+
+impl PartialEntry {
+    fn finish(self) -> Result<BootEntry, ConfigError> {
+        Ok(BootEntry {
+            name: self.name,
+            kernel_path: self.kernel_path.ok_or(ConfigError::MissingKey {
+                key: "kernel",
+                line: self.line,
+            })?,
+            initramfs_path: self.initramfs_path,
+            cmdline: self.cmdline.unwrap_or_default(),
+            kernel_crc32: self.kernel_crc32,
+            kernel_sha256: self.kernel_sha256,
+        })
+    }
+}
+
+/// Parses the bytes of a configuration file into a [`BootConfig`].
+pub fn parse_config(bytes: &[u8]) -> Result<BootConfig, ConfigError> {
+    let text = core::str::from_utf8(bytes).map_err(|_| ConfigError::InvalidUtf8)?;
+
+    let mut entries = Vec::new();
+    let mut timeout = None;
+    // The entry that keys are currently being added to, which is `None` only before any keys of
+    // an unnamed entry are set
+    let mut current: Option<PartialEntry> = None;
+    let mut in_section = false;
+
+    for (index, raw_line) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw_line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = trimmed.strip_prefix('[') {
+            let name = parse_entry_header(header).ok_or(ConfigError::InvalidLine { line })?;
+            if let Some(entry) = current.take() {
+                entries.push(entry.finish()?);
+            }
+            current = Some(PartialEntry {
+                name: name.to_string(),
+                line,
+                ..Default::default()
+            });
+            in_section = true;
+            continue;
+        }
+
+        let (key, value) = trimmed
+            .split_once('=')
+            .ok_or(ConfigError::InvalidLine { line })?;
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() {
+            return Err(ConfigError::InvalidLine { line });
+        }
+
+        if key == "timeout" && !in_section {
+            let seconds = value
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue { line })?;
+            set_once(&mut timeout, seconds, line)?;
+            continue;
+        }
+
+        let entry = current.get_or_insert_with(|| PartialEntry {
+            line,
+            ..Default::default()
+        });
+        match key {
+            "kernel" => set_once(&mut entry.kernel_path, value.to_string(), line)?,
+            "initramfs" => set_once(&mut entry.initramfs_path, value.to_string(), line)?,
+            "cmdline" => set_once(&mut entry.cmdline, value.to_string(), line)?,
+            "kernel_crc32" => {
+                let crc = parse_hex_u32(value).ok_or(ConfigError::InvalidValue { line })?;
+                set_once(&mut entry.kernel_crc32, crc, line)?
+            }
+            "kernel_sha256" => {
+                let digest = parse_digest(value).ok_or(ConfigError::InvalidValue { line })?;
+                set_once(&mut entry.kernel_sha256, digest, line)?
+            }
+            _ => return Err(ConfigError::UnknownKey { line }),
+        }
+    }
+
+    if let Some(entry) = current {
+        entries.push(entry.finish()?);
+    }
+    if entries.is_empty() {
+        return Err(ConfigError::NoEntries);
+    }
+
+    Ok(BootConfig {
+        entries,
+        timeout: timeout.unwrap_or(0),
+    })
+}
+
+/// Returns the name of an `[entry "name"]` header, given the header without its opening bracket.
+fn parse_entry_header(header: &str) -> Option<&str> {
+    let quoted = header
+        .strip_suffix(']')?
+        .trim()
+        .strip_prefix("entry")?
+        .trim_start();
+    quoted.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Parses a hexadecimal number with an optional `0x` prefix.
+fn parse_hex_u32(value: &str) -> Option<u32> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    // `from_str_radix` accepts a leading sign, which is not valid here
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    u32::from_str_radix(digits, 16).ok()
+}
+
+/// Parses a SHA-256 digest written as 64 hexadecimal digits.
+fn parse_digest(value: &str) -> Option<[u8; DIGEST_SIZE]> {
+    if value.len() != DIGEST_SIZE * 2 || !value.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; DIGEST_SIZE];
+    for (byte, hex) in digest.iter_mut().zip(value.as_bytes().chunks_exact(2)) {
+        // Both characters are ASCII, so this is always valid UTF-8
+        let hex = core::str::from_utf8(hex).ok()?;
+        if hex.starts_with(['+', '-']) {
+            return None;
+        }
+        *byte = u8::from_str_radix(hex, 16).ok()?;
+    }
+    Some(digest)
+}
+
+/// Stores `value` in `slot`, or returns an error if the key was already set on an earlier line.
+fn set_once<T>(slot: &mut Option<T>, value: T, line: usize) -> Result<(), ConfigError> {
+    if slot.is_some() {
+        return Err(ConfigError::DuplicateKey { line });
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::developing_modules::crypto::sha256::Sha256;
+    use std::vec;
+
+    /// Ensures that:
+    ///
+    /// * Every key is read into the config
+    /// * Comments, blank lines and surrounding whitespace are ignored
+    /// * Values can contain `=` and `#`
+    /// * Optional keys fall back to their defaults
+    #[test]
+    fn parse_configs() {
+        let config = parse_config(
+            b"# Caliga config\r\n\
+              \n\
+              \tkernel=/kernel.elf  \r\n\
+              initramfs = /initrd.img\n\
+              cmdline = root=/dev/sda1 init=/bin/sh#1\n\
+              timeout = 10\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            BootConfig {
+                entries: vec![BootEntry {
+                    name: String::new(),
+                    kernel_path: "/kernel.elf".to_string(),
+                    initramfs_path: Some("/initrd.img".to_string()),
+                    cmdline: "root=/dev/sda1 init=/bin/sh#1".to_string(),
+                    kernel_crc32: None,
+                    kernel_sha256: None,
+                }],
+                timeout: 10,
+            }
+        );
+
+        let config = parse_config(b"kernel = /kernel.elf").unwrap();
+        assert_eq!(config.entries[0].initramfs_path, None);
+        assert_eq!(config.entries[0].cmdline, "");
+        assert_eq!(config.timeout, 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Entry headers start new entries, in order
+    /// * Keys before the first header make up an unnamed entry
+    /// * The same key can be set once in each entry
+    #[test]
+    fn parse_entries() {
+        let config = parse_config(
+            b"timeout = 5\n\
+              kernel = /default.elf\n\
+              \n\
+              [entry \"Caliga\"]\n\
+              kernel = /caliga.elf\n\
+              cmdline = quiet\n\
+              kernel_crc32 = 0xCBF43926\n\
+              \n\
+              [ entry   \"Recovery shell\" ]\n\
+              kernel = /caliga.elf\n\
+              initramfs = /recovery.img\n\
+              kernel_sha256 = BA7816BF8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n",
+        )
+        .unwrap();
+        assert_eq!(config.timeout, 5);
+        assert_eq!(
+            config.entries,
+            [
+                BootEntry {
+                    name: String::new(),
+                    kernel_path: "/default.elf".to_string(),
+                    initramfs_path: None,
+                    cmdline: String::new(),
+                    kernel_crc32: None,
+                    kernel_sha256: None,
+                },
+                BootEntry {
+                    name: "Caliga".to_string(),
+                    kernel_path: "/caliga.elf".to_string(),
+                    initramfs_path: None,
+                    cmdline: "quiet".to_string(),
+                    kernel_crc32: Some(0xcbf4_3926),
+                    kernel_sha256: None,
+                },
+                BootEntry {
+                    name: "Recovery shell".to_string(),
+                    kernel_path: "/caliga.elf".to_string(),
+                    initramfs_path: Some("/recovery.img".to_string()),
+                    cmdline: String::new(),
+                    kernel_crc32: None,
+                    kernel_sha256: Some(Sha256::digest(b"abc")),
+                },
+            ]
+        );
+
+        // A timeout on its own does not make an unnamed entry
+        let config = parse_config(b"timeout = 1\n[entry \"A\"]\nkernel = /a.elf\n").unwrap();
+        assert_eq!(config.entries.len(), 1);
+        assert_eq!(config.entries[0].name, "A");
+    }
+
+    /// Ensures that:
+    ///
+    /// * Errors report the line that caused them
+    /// * Unknown keys, duplicate keys and invalid values are rejected
+    /// * Every entry needs a kernel path, and there must be at least one entry
+    #[test]
+    fn invalid_configs() {
+        assert_eq!(
+            parse_config(b"kernel = \xff"),
+            Err(ConfigError::InvalidUtf8)
+        );
+        assert_eq!(
+            parse_config(b"kernel = /kernel.elf\n\njust some text\n"),
+            Err(ConfigError::InvalidLine { line: 3 })
+        );
+        assert_eq!(
+            parse_config(b"= /kernel.elf"),
+            Err(ConfigError::InvalidLine { line: 1 })
+        );
+        for header in ["[entry]", "[entry \"A\"", "[entry A]", "[section \"A\"]"] {
+            assert_eq!(
+                parse_config(header.as_bytes()),
+                Err(ConfigError::InvalidLine { line: 1 })
+            );
+        }
+        assert_eq!(
+            parse_config(b"# comment\nkernal = /kernel.elf\n"),
+            Err(ConfigError::UnknownKey { line: 2 })
+        );
+        assert_eq!(
+            parse_config(b"[entry \"A\"]\nkernel = /a.elf\ntimeout = 1\n"),
+            Err(ConfigError::UnknownKey { line: 3 })
+        );
+        assert_eq!(
+            parse_config(b"kernel = /a.elf\nkernel = /b.elf\n"),
+            Err(ConfigError::DuplicateKey { line: 2 })
+        );
+        assert_eq!(
+            parse_config(b"timeout = 1\ntimeout = 2\nkernel = /a.elf\n"),
+            Err(ConfigError::DuplicateKey { line: 2 })
+        );
+        assert_eq!(
+            parse_config(b"kernel = /kernel.elf\ntimeout = -1\n"),
+            Err(ConfigError::InvalidValue { line: 2 })
+        );
+        for crc in ["", "0x", "-1", "0x+1", "0x1_0000", "0x100000000", "crc"] {
+            let config = std::format!("kernel = /a.elf\nkernel_crc32 = {crc}\n");
+            assert_eq!(
+                parse_config(config.as_bytes()),
+                Err(ConfigError::InvalidValue { line: 2 })
+            );
+        }
+        let valid_digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        for digest in [
+            &valid_digest[2..],
+            &std::format!("{valid_digest}00"),
+            &valid_digest.replace('b', "g"),
+            &valid_digest.replacen("ba", "+a", 1),
+            &valid_digest.replacen("ba", "é", 1),
+        ] {
+            let config = std::format!("kernel = /a.elf\nkernel_sha256 = {digest}\n");
+            assert_eq!(
+                parse_config(config.as_bytes()),
+                Err(ConfigError::InvalidValue { line: 2 })
+            );
+        }
+        assert_eq!(
+            parse_config(b"timeout = 5\ncmdline = quiet\n[entry \"A\"]\n"),
+            Err(ConfigError::MissingKey {
+                key: "kernel",
+                line: 2
+            })
+        );
+        assert_eq!(
+            parse_config(b"[entry \"A\"]\nkernel = /a.elf\n[entry \"B\"]\n"),
+            Err(ConfigError::MissingKey {
+                key: "kernel",
+                line: 3
+            })
+        );
+        assert_eq!(parse_config(b"timeout = 5\n"), Err(ConfigError::NoEntries));
+        assert_eq!(parse_config(b""), Err(ConfigError::NoEntries));
+    }
+}
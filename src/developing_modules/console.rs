@@ -0,0 +1,407 @@
+//! A text console that draws to a linear framebuffer.
+
+use core::{fmt, ptr};
+
+use super::boot_info::{Framebuffer, PixelFormat};
+
+/// The width of each glyph in pixels.
+pub const GLYPH_WIDTH: u32 = 8;
+/// The height of each glyph in pixels.
+pub const GLYPH_HEIGHT: u32 = 16;
+
+/// The error type returned by [`FramebufferConsole::new`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConsoleError {
+    /// The framebuffer uses a [`PixelFormat::Bitmask`] format.
+    UnsupportedPixelFormat,
+    /// The framebuffer cannot fit a single glyph.
+    FramebufferTooSmall,
+}
+
+/// A text console that draws glyphs from a built-in 8x16 font to a [`Framebuffer`].
+///
+/// Only ASCII is supported; any other character is drawn as `'?'`. The console scrolls up a line
+/// when text is written past the last row.
+pub struct FramebufferConsole {
+    framebuffer: Framebuffer,
+    column: u32,
+    row: u32,
+    foreground: u32,
+    background: u32,
+}
+
+impl FramebufferConsole {
+    /// Returns a console that draws white text on a black background to `framebuffer`.
+    ///
+    /// # Safety
+    ///
+    /// `framebuffer.base` must point to writable memory that is at least `stride * height`
+    /// pixels in size. Nothing else should draw to the framebuffer while the console is in use.
+    pub unsafe fn new(framebuffer: Framebuffer) -> Result<Self, ConsoleError> {
+        if let PixelFormat::Bitmask { .. } = framebuffer.pixel_format {
+            return Err(ConsoleError::UnsupportedPixelFormat);
+        }
+        if framebuffer.width < GLYPH_WIDTH || framebuffer.height < GLYPH_HEIGHT {
+            return Err(ConsoleError::FramebufferTooSmall);
+        }
+
+        let mut console = Self {
+            framebuffer,
+            column: 0,
+            row: 0,
+            foreground: 0,
+            background: 0,
+        };
+        console.set_colors([0xff, 0xff, 0xff], [0, 0, 0]);
+        Ok(console)
+    }
+
+    /// Sets the `[red, green, blue]` colors used for text drawn after this call.
+    pub fn set_colors(&mut self, foreground: [u8; 3], background: [u8; 3]) {
+        self.foreground = self.encode_color(foreground);
+        self.background = self.encode_color(background);
+    }
+
+    /// Returns the number of glyphs that fit in each row.
+    pub fn columns(&self) -> u32 {
+        self.framebuffer.width / GLYPH_WIDTH
+    }
+
+    /// Returns the number of rows of glyphs that fit on the screen.
+    pub fn rows(&self) -> u32 {
+        self.framebuffer.height / GLYPH_HEIGHT
+    }
+
+    /// Fills the screen with the background color and moves the cursor to the top left.
+    pub fn clear(&mut self) {
+        for y in 0..self.framebuffer.height {
+            self.fill_line(y);
+        }
+        self.column = 0;
+        self.row = 0;
+    }
+
+    /// Draws a single character at the cursor and advances it.
+    pub fn write_byte(&mut self, byte: u8) {
+        match byte {
+            b'\n' => self.new_line(),
+            b'\r' => self.column = 0,
+            _ => {
+                if self.column == self.columns() {
+                    self.new_line();
+                }
+
+                let glyph = FONT.get(byte as usize).unwrap_or(&FONT[b'?' as usize]);
+                self.draw_glyph(glyph);
+                self.column += 1;
+            }
+        }
+    }
+
+    /// Draws `glyph` at the cursor.
+    fn draw_glyph(&mut self, glyph: &[u8; 16]) {
+        let x = self.column * GLYPH_WIDTH;
+        let y = self.row * GLYPH_HEIGHT;
+        for (row, bits) in glyph.iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                let color = if bits & (0x80 >> column) != 0 {
+                    self.foreground
+                } else {
+                    self.background
+                };
+                self.write_pixel(x + column, y + row as u32, color);
+            }
+        }
+    }
+
+    /// Moves the cursor to the start of the next row, scrolling if it is already on the last row.
+    fn new_line(&mut self) {
+        self.column = 0;
+        if self.row + 1 < self.rows() {
+            self.row += 1;
+            return;
+        }
+
+        // Move every row of glyphs up by one and clear the last row
+        let stride = self.framebuffer.stride as usize;
+        let row_pixels = stride * GLYPH_HEIGHT as usize;
+        let base = self.framebuffer.base as *mut u32;
+        unsafe {
+            ptr::copy(
+                base.add(row_pixels),
+                base,
+                row_pixels * (self.rows() as usize - 1),
+            );
+        }
+        for y in self.row * GLYPH_HEIGHT..(self.row + 1) * GLYPH_HEIGHT {
+            self.fill_line(y);
+        }
+    }
+
+    /// Fills the visible pixels of scanline `y` with the background color.
+    fn fill_line(&mut self, y: u32) {
+        for x in 0..self.framebuffer.width {
+            self.write_pixel(x, y, self.background);
+        }
+    }
+
+    /// Writes `color` to the pixel at `x` and `y`.
+    fn write_pixel(&mut self, x: u32, y: u32, color: u32) {
+        let offset = y as usize * self.framebuffer.stride as usize + x as usize;
+        unsafe {
+            ptr::write_volatile((self.framebuffer.base as *mut u32).add(offset), color);
+        }
+    }
+
+    /// Returns the pixel value of an `[red, green, blue]` color in the framebuffer's format.
+    fn encode_color(&self, [red, green, blue]: [u8; 3]) -> u32 {
+        let (low, high) = match self.framebuffer.pixel_format {
+            PixelFormat::Bgr => (blue, red),
+            _ => (red, blue),
+        };
+        u32::from(low) | u32::from(green) << 8 | u32::from(high) << 16
+    }
+}
+
+impl fmt::Write for FramebufferConsole {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.write_byte(if c.is_ascii() { c as u8 } else { b'?' });
+        }
+        Ok(())
+    }
+}
+
+/// An 8x16 bitmap font for the first 128 ASCII characters.
+///
+/// Each glyph is 16 rows from top to bottom, with the most significant bit of each row being the
+/// leftmost pixel. Control characters are blank.
+#[rustfmt::skip]
+const FONT: [[u8; 16]; 128] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x10, 0x10, 0x00, 0x00], // '!'
+    [0x28, 0x28, 0x28, 0x28, 0x28, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x28, 0x28, 0x28, 0x28, 0x7c, 0x7c, 0x28, 0x28, 0x7c, 0x7c, 0x28, 0x28, 0x28, 0x28, 0x00, 0x00], // '#'
+    [0x10, 0x10, 0x3c, 0x3c, 0x50, 0x50, 0x38, 0x38, 0x14, 0x14, 0x78, 0x78, 0x10, 0x10, 0x00, 0x00], // '$'
+    [0x60, 0x60, 0x64, 0x64, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x4c, 0x4c, 0x0c, 0x0c, 0x00, 0x00], // '%'
+    [0x30, 0x30, 0x48, 0x48, 0x50, 0x50, 0x20, 0x20, 0x54, 0x54, 0x48, 0x48, 0x34, 0x34, 0x00, 0x00], // '&'
+    [0x10, 0x10, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '\''
+    [0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00], // '('
+    [0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00], // ')'
+    [0x00, 0x00, 0x10, 0x10, 0x54, 0x54, 0x38, 0x38, 0x54, 0x54, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00], // '*'
+    [0x00, 0x00, 0x10, 0x10, 0x10, 0x10, 0x7c, 0x7c, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00, 0x00, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00], // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x7c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00], // '.'
+    [0x00, 0x00, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x00, 0x00, 0x00, 0x00], // '/'
+    [0x38, 0x38, 0x44, 0x44, 0x4c, 0x4c, 0x54, 0x54, 0x64, 0x64, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // '0'
+    [0x10, 0x10, 0x30, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00], // '1'
+    [0x38, 0x38, 0x44, 0x44, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x7c, 0x7c, 0x00, 0x00], // '2'
+    [0x7c, 0x7c, 0x08, 0x08, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // '3'
+    [0x08, 0x08, 0x18, 0x18, 0x28, 0x28, 0x48, 0x48, 0x7c, 0x7c, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00], // '4'
+    [0x7c, 0x7c, 0x40, 0x40, 0x78, 0x78, 0x04, 0x04, 0x04, 0x04, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // '5'
+    [0x18, 0x18, 0x20, 0x20, 0x40, 0x40, 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // '6'
+    [0x7c, 0x7c, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00], // '7'
+    [0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // '8'
+    [0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x3c, 0x3c, 0x04, 0x04, 0x08, 0x08, 0x30, 0x30, 0x00, 0x00], // '9'
+    [0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00], // ':'
+    [0x00, 0x00, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x30, 0x30, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00], // ';'
+    [0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00], // '<'
+    [0x00, 0x00, 0x00, 0x00, 0x7c, 0x7c, 0x00, 0x00, 0x7c, 0x7c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '='
+    [0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00], // '>'
+    [0x38, 0x38, 0x44, 0x44, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x00, 0x00, 0x10, 0x10, 0x00, 0x00], // '?'
+    [0x38, 0x38, 0x44, 0x44, 0x04, 0x04, 0x34, 0x34, 0x54, 0x54, 0x54, 0x54, 0x38, 0x38, 0x00, 0x00], // '@'
+    [0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x7c, 0x7c, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'A'
+    [0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x00, 0x00], // 'B'
+    [0x38, 0x38, 0x44, 0x44, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // 'C'
+    [0x70, 0x70, 0x48, 0x48, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x48, 0x48, 0x70, 0x70, 0x00, 0x00], // 'D'
+    [0x7c, 0x7c, 0x40, 0x40, 0x40, 0x40, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x7c, 0x7c, 0x00, 0x00], // 'E'
+    [0x7c, 0x7c, 0x40, 0x40, 0x40, 0x40, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00], // 'F'
+    [0x38, 0x38, 0x44, 0x44, 0x40, 0x40, 0x5c, 0x5c, 0x44, 0x44, 0x44, 0x44, 0x3c, 0x3c, 0x00, 0x00], // 'G'
+    [0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x7c, 0x7c, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'H'
+    [0x38, 0x38, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00], // 'I'
+    [0x1c, 0x1c, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x48, 0x48, 0x30, 0x30, 0x00, 0x00], // 'J'
+    [0x44, 0x44, 0x48, 0x48, 0x50, 0x50, 0x60, 0x60, 0x50, 0x50, 0x48, 0x48, 0x44, 0x44, 0x00, 0x00], // 'K'
+    [0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x7c, 0x7c, 0x00, 0x00], // 'L'
+    [0x44, 0x44, 0x6c, 0x6c, 0x54, 0x54, 0x54, 0x54, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'M'
+    [0x44, 0x44, 0x44, 0x44, 0x64, 0x64, 0x54, 0x54, 0x4c, 0x4c, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'N'
+    [0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // 'O'
+    [0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00], // 'P'
+    [0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x54, 0x54, 0x48, 0x48, 0x34, 0x34, 0x00, 0x00], // 'Q'
+    [0x78, 0x78, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x50, 0x50, 0x48, 0x48, 0x44, 0x44, 0x00, 0x00], // 'R'
+    [0x3c, 0x3c, 0x40, 0x40, 0x40, 0x40, 0x38, 0x38, 0x04, 0x04, 0x04, 0x04, 0x78, 0x78, 0x00, 0x00], // 'S'
+    [0x7c, 0x7c, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00], // 'T'
+    [0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // 'U'
+    [0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x00, 0x00], // 'V'
+    [0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x54, 0x54, 0x54, 0x54, 0x54, 0x54, 0x28, 0x28, 0x00, 0x00], // 'W'
+    [0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'X'
+    [0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00], // 'Y'
+    [0x7c, 0x7c, 0x04, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x40, 0x40, 0x7c, 0x7c, 0x00, 0x00], // 'Z'
+    [0x38, 0x38, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x38, 0x38, 0x00, 0x00], // '['
+    [0x00, 0x00, 0x40, 0x40, 0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x04, 0x04, 0x00, 0x00, 0x00, 0x00], // '\\'
+    [0x38, 0x38, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x38, 0x38, 0x00, 0x00], // ']'
+    [0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0x7c, 0x00, 0x00], // '_'
+    [0x20, 0x20, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '`'
+    [0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x04, 0x04, 0x3c, 0x3c, 0x44, 0x44, 0x3c, 0x3c, 0x00, 0x00], // 'a'
+    [0x40, 0x40, 0x40, 0x40, 0x58, 0x58, 0x64, 0x64, 0x44, 0x44, 0x44, 0x44, 0x78, 0x78, 0x00, 0x00], // 'b'
+    [0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x40, 0x40, 0x40, 0x40, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // 'c'
+    [0x04, 0x04, 0x04, 0x04, 0x34, 0x34, 0x4c, 0x4c, 0x44, 0x44, 0x44, 0x44, 0x3c, 0x3c, 0x00, 0x00], // 'd'
+    [0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x44, 0x44, 0x7c, 0x7c, 0x40, 0x40, 0x38, 0x38, 0x00, 0x00], // 'e'
+    [0x18, 0x18, 0x24, 0x24, 0x20, 0x20, 0x70, 0x70, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00], // 'f'
+    [0x00, 0x00, 0x3c, 0x3c, 0x44, 0x44, 0x44, 0x44, 0x3c, 0x3c, 0x04, 0x04, 0x38, 0x38, 0x00, 0x00], // 'g'
+    [0x40, 0x40, 0x40, 0x40, 0x58, 0x58, 0x64, 0x64, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'h'
+    [0x10, 0x10, 0x00, 0x00, 0x30, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00], // 'i'
+    [0x08, 0x08, 0x00, 0x00, 0x18, 0x18, 0x08, 0x08, 0x08, 0x08, 0x48, 0x48, 0x30, 0x30, 0x00, 0x00], // 'j'
+    [0x40, 0x40, 0x40, 0x40, 0x48, 0x48, 0x50, 0x50, 0x60, 0x60, 0x50, 0x50, 0x48, 0x48, 0x00, 0x00], // 'k'
+    [0x30, 0x30, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x38, 0x38, 0x00, 0x00], // 'l'
+    [0x00, 0x00, 0x00, 0x00, 0x68, 0x68, 0x54, 0x54, 0x54, 0x54, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'm'
+    [0x00, 0x00, 0x00, 0x00, 0x58, 0x58, 0x64, 0x64, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x00, 0x00], // 'n'
+    [0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x38, 0x38, 0x00, 0x00], // 'o'
+    [0x00, 0x00, 0x00, 0x00, 0x78, 0x78, 0x44, 0x44, 0x78, 0x78, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00], // 'p'
+    [0x00, 0x00, 0x00, 0x00, 0x34, 0x34, 0x4c, 0x4c, 0x3c, 0x3c, 0x04, 0x04, 0x04, 0x04, 0x00, 0x00], // 'q'
+    [0x00, 0x00, 0x00, 0x00, 0x58, 0x58, 0x64, 0x64, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00], // 'r'
+    [0x00, 0x00, 0x00, 0x00, 0x38, 0x38, 0x40, 0x40, 0x38, 0x38, 0x04, 0x04, 0x78, 0x78, 0x00, 0x00], // 's'
+    [0x20, 0x20, 0x20, 0x20, 0x70, 0x70, 0x20, 0x20, 0x20, 0x20, 0x24, 0x24, 0x18, 0x18, 0x00, 0x00], // 't'
+    [0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x4c, 0x4c, 0x34, 0x34, 0x00, 0x00], // 'u'
+    [0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x00, 0x00], // 'v'
+    [0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x54, 0x54, 0x54, 0x54, 0x28, 0x28, 0x00, 0x00], // 'w'
+    [0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x28, 0x28, 0x10, 0x10, 0x28, 0x28, 0x44, 0x44, 0x00, 0x00], // 'x'
+    [0x00, 0x00, 0x00, 0x00, 0x44, 0x44, 0x44, 0x44, 0x3c, 0x3c, 0x04, 0x04, 0x38, 0x38, 0x00, 0x00], // 'y'
+    [0x00, 0x00, 0x00, 0x00, 0x7c, 0x7c, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x7c, 0x7c, 0x00, 0x00], // 'z'
+    [0x08, 0x08, 0x10, 0x10, 0x10, 0x10, 0x20, 0x20, 0x10, 0x10, 0x10, 0x10, 0x08, 0x08, 0x00, 0x00], // '{'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x00, 0x00], // '|'
+    [0x20, 0x20, 0x10, 0x10, 0x10, 0x10, 0x08, 0x08, 0x10, 0x10, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00], // '}'
+    [0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0x54, 0x54, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // '~'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+    use std::{vec, vec::Vec};
+
+    const WIDTH: u32 = GLYPH_WIDTH * 4;
+    const HEIGHT: u32 = GLYPH_HEIGHT * 2;
+    const STRIDE: u32 = WIDTH + 3;
+
+    /// Returns a console that draws to `pixels`.
+    fn console(pixels: &mut Vec<u32>, pixel_format: PixelFormat) -> FramebufferConsole {
+        *pixels = vec![0xdead_beef; (STRIDE * HEIGHT) as usize];
+        let framebuffer = Framebuffer {
+            base: pixels.as_mut_ptr() as usize,
+            width: WIDTH,
+            height: HEIGHT,
+            stride: STRIDE,
+            pixel_format,
+        };
+        let mut console = unsafe { FramebufferConsole::new(framebuffer) }.unwrap();
+        console.clear();
+        console
+    }
+
+    /// Returns true if the glyph at `column` and `row` in `pixels` matches `c`.
+    fn glyph_matches(pixels: &[u32], column: u32, row: u32, c: u8, foreground: u32) -> bool {
+        FONT[c as usize].iter().enumerate().all(|(y, bits)| {
+            (0..GLYPH_WIDTH).all(|x| {
+                let offset = (row * GLYPH_HEIGHT + y as u32) * STRIDE + column * GLYPH_WIDTH + x;
+                let expected = if bits & (0x80 >> x) != 0 {
+                    foreground
+                } else {
+                    0
+                };
+                pixels[offset as usize] == expected
+            })
+        })
+    }
+
+    /// Ensures that:
+    ///
+    /// * Clearing only writes the visible pixels of each line
+    /// * Glyphs are drawn at the cursor
+    /// * Lines wrap when they reach the last column
+    /// * Colors are encoded in the framebuffer's pixel format
+    #[test]
+    fn write_glyphs() {
+        let mut pixels = Vec::new();
+        let mut console = console(&mut pixels, PixelFormat::Bgr);
+        assert_eq!(console.columns(), 4);
+        assert_eq!(console.rows(), 2);
+        assert_eq!(pixels[WIDTH as usize], 0xdead_beef);
+
+        console.set_colors([0x12, 0x34, 0x56], [0, 0, 0]);
+        write!(console, "Hi!\r\nx").unwrap();
+        assert!(glyph_matches(&pixels, 0, 0, b'H', 0x12_3456));
+        assert!(glyph_matches(&pixels, 1, 0, b'i', 0x12_3456));
+        assert!(glyph_matches(&pixels, 2, 0, b'!', 0x12_3456));
+        assert!(glyph_matches(&pixels, 3, 0, b' ', 0x12_3456));
+        assert!(glyph_matches(&pixels, 0, 1, b'x', 0x12_3456));
+
+        // "d" wraps onto a new line, which scrolls the screen up
+        write!(console, "abcd").unwrap();
+        assert!(glyph_matches(&pixels, 0, 0, b'x', 0x12_3456));
+        assert!(glyph_matches(&pixels, 3, 0, b'c', 0x12_3456));
+        assert!(glyph_matches(&pixels, 0, 1, b'd', 0x12_3456));
+        assert!(glyph_matches(&pixels, 1, 1, b' ', 0x12_3456));
+    }
+
+    /// Ensures that unsupported framebuffers are rejected.
+    #[test]
+    fn unsupported_framebuffers() {
+        let mut framebuffer = Framebuffer {
+            base: 0x1000,
+            width: WIDTH,
+            height: HEIGHT,
+            stride: STRIDE,
+            pixel_format: PixelFormat::Bitmask {
+                red: 0xff,
+                green: 0xff00,
+                blue: 0xff_0000,
+            },
+        };
+        assert_eq!(
+            unsafe { FramebufferConsole::new(framebuffer) }.err(),
+            Some(ConsoleError::UnsupportedPixelFormat)
+        );
+
+        framebuffer.pixel_format = PixelFormat::Rgb;
+        framebuffer.height = GLYPH_HEIGHT - 1;
+        assert_eq!(
+            unsafe { FramebufferConsole::new(framebuffer) }.err(),
+            Some(ConsoleError::FramebufferTooSmall)
+        );
+    }
+}
@@ -0,0 +1,340 @@
+//! A reader for CPIO archives in the "newc" format, such as an initramfs loaded into memory.
+//!
+//! The archive is never copied; files are returned as slices of the archive's bytes.
+
+/// The error type returned when reading a [`CpioArchive`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CpioError {
+    /// An entry's header is not a valid newc header, or its name or data is not contained in the
+    /// archive.
+    InvalidHeader,
+    /// The archive does not contain the requested path.
+    FileNotFound,
+    /// The requested path is a directory.
+    IsDirectory,
+    /// The requested path is neither a regular file nor a directory, such as a symbolic link or
+    /// a device node.
+    NotRegularFile,
+}
+
+const HEADER_SIZE: usize = 110;
+/// The number of 8 digit hexadecimal fields that follow the magic of a header.
+const FIELD_COUNT: usize = 13;
+const MAGIC: &[u8] = b"070701";
+/// The magic of newc archives that include a checksum of each file, which is ignored here.
+const MAGIC_CRC: &[u8] = b"070702";
+const TRAILER_NAME: &str = "TRAILER!!!";
+
+/// The mask of the file type bits of an entry's mode.
+const MODE_TYPE_MASK: u32 = 0o170000;
+const MODE_DIRECTORY: u32 = 0o040000;
+const MODE_REGULAR_FILE: u32 = 0o100000;
+
+/// A single file, directory or other entry in a [`CpioArchive`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CpioEntry<'a> {
+    /// The entry's path, as it is stored in the archive.
+    pub name: &'a str,
+    /// The entry's file type and permission bits.
+    pub mode: u32,
+    /// The entry's contents. This is empty for directories.
+    pub data: &'a [u8],
+}
+
+impl<'a> CpioEntry<'a> {
+    /// Returns true if the entry is a directory.
+    pub fn is_directory(&self) -> bool {
+        self.mode & MODE_TYPE_MASK == MODE_DIRECTORY
+    }
+
+    /// Returns true if the entry is a regular file.
+    ///
+    /// Other types of entries, such as symbolic links and device nodes, also have data, but it
+    /// is not the contents of a file.
+    pub fn is_regular_file(&self) -> bool {
+        self.mode & MODE_TYPE_MASK == MODE_REGULAR_FILE
+    }
+}
+
+/// A CPIO archive in the newc format, stored in memory.
+#[derive(Clone, Copy, Debug)]
+pub struct CpioArchive<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> CpioArchive<'a> {
+    /// Wraps the bytes of an archive.
+    ///
+    /// The archive's headers are not parsed until its entries are read, so errors are returned
+    /// by [`CpioArchive::entries`] and [`CpioArchive::open`].
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Returns an iterator over the entries of the archive, in the order they are stored.
+    ///
+    /// The iterator stops after the archive's trailer, or after returning an error.
+    pub fn entries(&self) -> CpioEntries<'a> {
+        CpioEntries {
+            data: self.data,
+            offset: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the contents of the regular file at `path`.
+    ///
+    /// Leading `/` and `./` are ignored in both `path` and the archive's entry names, so
+    /// `/init` matches an entry named `./init`.
+    pub fn open(&self, path: &str) -> Result<&'a [u8], CpioError> {
+        let path = normalize_path(path);
+        for entry in self.entries() {
+            let entry = entry?;
+            if normalize_path(entry.name) != path {
+                continue;
+            }
+            if entry.is_directory() {
+                return Err(CpioError::IsDirectory);
+            }
+            if !entry.is_regular_file() {
+                return Err(CpioError::NotRegularFile);
+            }
+            return Ok(entry.data);
+        }
+        Err(CpioError::FileNotFound)
+    }
+}
+
+/// An iterator over the entries of a [`CpioArchive`].
+///
+/// See [`CpioArchive::entries`].
+#[derive(Clone, Debug)]
+pub struct CpioEntries<'a> {
+    data: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl<'a> CpioEntries<'a> {
+    /// Parses the entry at the current offset, and moves the offset to the next entry.
+    fn parse_entry(&mut self) -> Result<CpioEntry<'a>, CpioError> {
+        let header = self
+            .data
+            .get(self.offset..)
+            .and_then(|rest| rest.get(..HEADER_SIZE))
+            .ok_or(CpioError::InvalidHeader)?;
+        if &header[..6] != MAGIC && &header[..6] != MAGIC_CRC {
+            return Err(CpioError::InvalidHeader);
+        }
+        let mut fields = [0; FIELD_COUNT];
+        for (index, field) in fields.iter_mut().enumerate() {
+            *field = read_hex_field(header, index)?;
+        }
+        let mode = fields[1];
+        let file_size = fields[6] as usize;
+        let name_size = fields[11] as usize;
+
+        // The name includes a trailing null byte, and is padded so that the data is aligned
+        let name_start = self.offset + HEADER_SIZE;
+        let name_end = name_start
+            .checked_add(name_size)
+            .ok_or(CpioError::InvalidHeader)?;
+        let name = self
+            .data
+            .get(name_start..name_end)
+            .and_then(|name| name.strip_suffix(&[0]))
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .ok_or(CpioError::InvalidHeader)?;
+
+        let data_start = align_up(name_end);
+        let data_end = data_start
+            .checked_add(file_size)
+            .ok_or(CpioError::InvalidHeader)?;
+        let data = self
+            .data
+            .get(data_start..data_end)
+            .ok_or(CpioError::InvalidHeader)?;
+
+        self.offset = align_up(data_end);
+        Ok(CpioEntry { name, mode, data })
+    }
+}
+
+impl<'a> Iterator for CpioEntries<'a> {
+    type Item = Result<CpioEntry<'a>, CpioError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.parse_entry() {
+            Ok(entry) if entry.name == TRAILER_NAME => {
+                self.done = true;
+                None
+            }
+            Ok(entry) => Some(Ok(entry)),
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Reads one of the fields that follow the magic of a header.
+fn read_hex_field(header: &[u8], index: usize) -> Result<u32, CpioError> {
+    let start = MAGIC.len() + index * 8;
+    core::str::from_utf8(&header[start..start + 8])
+        .ok()
+        .filter(|field| field.bytes().all(|byte| byte.is_ascii_hexdigit()))
+        .and_then(|field| u32::from_str_radix(field, 16).ok())
+        .ok_or(CpioError::InvalidHeader)
+}
+
+/// Rounds `offset` up to the 4 byte alignment used for names and data.
+const fn align_up(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+fn normalize_path(mut path: &str) -> &str {
+    loop {
+        if let Some(rest) = path.strip_prefix("./") {
+            path = rest;
+        } else if let Some(rest) = path.strip_prefix('/') {
+            path = rest;
+        } else {
+            return path;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{format, vec::Vec};
+
+    const MODE_SYMLINK: u32 = 0o120000;
+    const MODE_CHAR_DEVICE: u32 = 0o020000;
+
+    /// Appends a newc entry to `archive`.
+    fn push_entry(archive: &mut Vec<u8>, name: &str, mode: u32, data: &[u8]) {
+        archive.extend_from_slice(MAGIC);
+        let fields = [0, mode, 0, 0, 1, 0, data.len() as u32, 0, 0, 0, 0];
+        for field in fields.into_iter().chain([name.len() as u32 + 1, 0]) {
+            archive.extend_from_slice(format!("{field:08X}").as_bytes());
+        }
+        archive.extend_from_slice(name.as_bytes());
+        archive.push(0);
+        archive.resize(align_up(archive.len()), 0);
+        archive.extend_from_slice(data);
+        archive.resize(align_up(archive.len()), 0);
+    }
+
+    fn build_archive() -> Vec<u8> {
+        let mut archive = Vec::new();
+        push_entry(&mut archive, ".", MODE_DIRECTORY | 0o755, &[]);
+        push_entry(&mut archive, "boot", MODE_DIRECTORY | 0o755, &[]);
+        push_entry(
+            &mut archive,
+            "boot/kernel",
+            MODE_REGULAR_FILE | 0o644,
+            b"kernel",
+        );
+        push_entry(
+            &mut archive,
+            "./init",
+            MODE_REGULAR_FILE | 0o755,
+            b"#!/bin/sh\n",
+        );
+        push_entry(&mut archive, "empty", MODE_REGULAR_FILE | 0o644, &[]);
+        // A symbolic link's data is the path that it points to
+        push_entry(&mut archive, "linuxrc", MODE_SYMLINK | 0o777, b"init");
+        push_entry(&mut archive, "dev/console", MODE_CHAR_DEVICE | 0o600, &[]);
+        push_entry(&mut archive, TRAILER_NAME, 0, &[]);
+        // Archives are usually padded to a block size after the trailer
+        archive.resize((archive.len() + 511) / 512 * 512, 0);
+        archive
+    }
+
+    /// Ensures that:
+    ///
+    /// * Every entry before the trailer is returned, with its name, type and data
+    /// * Files are found regardless of leading `/` and `./`
+    /// * Directories, symbolic links, device nodes and missing files cannot be opened
+    #[test]
+    fn read_archive() {
+        let data = build_archive();
+        let archive = CpioArchive::new(&data);
+
+        let entries: Vec<CpioEntry> = archive.entries().map(Result::unwrap).collect();
+        let names: Vec<&str> = entries.iter().map(|entry| entry.name).collect();
+        assert_eq!(
+            names,
+            [
+                ".",
+                "boot",
+                "boot/kernel",
+                "./init",
+                "empty",
+                "linuxrc",
+                "dev/console"
+            ]
+        );
+        assert!(entries[1].is_directory());
+        assert!(!entries[1].is_regular_file());
+        assert!(entries[2].is_regular_file());
+        assert!(!entries[5].is_directory());
+        assert!(!entries[5].is_regular_file());
+        assert_eq!(entries[2].mode & 0o777, 0o644);
+
+        assert_eq!(archive.open("/boot/kernel"), Ok(&b"kernel"[..]));
+        assert_eq!(archive.open("boot/kernel"), Ok(&b"kernel"[..]));
+        assert_eq!(archive.open("/init"), Ok(&b"#!/bin/sh\n"[..]));
+        assert_eq!(archive.open("empty"), Ok(&b""[..]));
+        assert_eq!(archive.open("/boot"), Err(CpioError::IsDirectory));
+        assert_eq!(archive.open("/boot/missing"), Err(CpioError::FileNotFound));
+        assert_eq!(archive.open("/linuxrc"), Err(CpioError::NotRegularFile));
+        assert_eq!(archive.open("/dev/console"), Err(CpioError::NotRegularFile));
+    }
+
+    /// Ensures that:
+    ///
+    /// * Bad magic, bad fields and truncated entries are rejected
+    /// * The iterator stops after an error
+    #[test]
+    fn invalid_archives() {
+        let data = build_archive();
+
+        let mut bad_magic = data.clone();
+        bad_magic[5] = b'7';
+        let mut entries = CpioArchive::new(&bad_magic).entries();
+        assert_eq!(entries.next(), Some(Err(CpioError::InvalidHeader)));
+        assert_eq!(entries.next(), None);
+
+        let mut bad_field = data.clone();
+        bad_field[MAGIC.len()] = b'x';
+        assert_eq!(
+            CpioArchive::new(&bad_field).open("/init"),
+            Err(CpioError::InvalidHeader)
+        );
+
+        // Cut the archive off in the middle of the kernel's data
+        let kernel_offset = data
+            .windows(6)
+            .rposition(|window| window == b"kernel")
+            .unwrap();
+        let truncated = &data[..kernel_offset + 3];
+        assert_eq!(
+            CpioArchive::new(truncated).open("/init"),
+            Err(CpioError::InvalidHeader)
+        );
+        // Entries before the cut can still be read
+        assert_eq!(CpioArchive::new(truncated).entries().count(), 3);
+
+        // An archive without a trailer is truncated
+        assert_eq!(
+            CpioArchive::new(&[]).open("/init"),
+            Err(CpioError::InvalidHeader)
+        );
+    }
+}
@@ -0,0 +1,3 @@
+//! Cryptographic primitives for verifying loaded files.
+
+pub mod sha256;
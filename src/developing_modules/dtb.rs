@@ -0,0 +1,437 @@
+//! A minimal parser for flattened devicetree (DTB) blobs.
+//!
+//! Only the parts of the specification needed by the bootloader are implemented. See the
+//! [Devicetree Specification](https://www.devicetree.org/specifications/) for the full format.
+
+use alloc::vec::Vec;
+
+use super::raw::checked_slice;
+
+/// The error type returned when parsing a devicetree blob.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DtbError {
+    /// The blob does not start with the FDT magic number.
+    BadMagic,
+    /// The blob is smaller than its header says, or a block is out of bounds.
+    Truncated,
+    /// The structure block contains an unknown token or unbalanced nodes.
+    InvalidStructure,
+    /// The blob's address is null, or the blob would extend past the end of the address space.
+    InvalidAddress,
+}
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_HEADER_SIZE: usize = 40;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// The default `#address-cells` of a node's children if the property is missing.
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+/// The default `#size-cells` of a node's children if the property is missing.
+const DEFAULT_SIZE_CELLS: u32 = 1;
+
+/// A validated flattened devicetree blob.
+#[derive(Clone, Copy, Debug)]
+pub struct Dtb<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Dtb<'a> {
+    /// Validates the header and structure block of the blob in `data`.
+    ///
+    /// # Errors
+    ///
+    /// * [`DtbError::BadMagic`]: `data` does not start with the FDT magic number
+    /// * [`DtbError::Truncated`]: `data` is smaller than the size in its header
+    /// * [`DtbError::InvalidStructure`]: the structure block is malformed
+    pub fn new(data: &'a [u8]) -> Result<Self, DtbError> {
+        if data.len() < FDT_HEADER_SIZE {
+            return Err(DtbError::Truncated);
+        }
+        if read_u32(data, 0) != Some(FDT_MAGIC) {
+            return Err(DtbError::BadMagic);
+        }
+
+        let total_size = read_u32(data, 4).unwrap() as usize;
+        if total_size > data.len() {
+            return Err(DtbError::Truncated);
+        }
+
+        let dtb = Self {
+            data: &data[..total_size],
+        };
+        dtb.structure_block()?;
+        dtb.strings_block()?;
+        dtb.for_each_property(|_, _, _, _| {})?;
+
+        Ok(dtb)
+    }
+
+    /// Returns the devicetree blob at `addr`.
+    ///
+    /// # Errors
+    ///
+    /// * [`DtbError::InvalidAddress`]: `addr` is zero, or the blob would wrap around the end of
+    /// the address space
+    /// * Any error returned by [`Dtb::new`]
+    ///
+    /// # Safety
+    ///
+    /// `addr` must point to a readable blob that is at least as large as the size in its header
+    /// and is valid for the lifetime `'a`.
+    pub unsafe fn from_addr(addr: usize) -> Result<Self, DtbError> {
+        let header = checked_slice(addr as *const u8, FDT_HEADER_SIZE)
+            .map_err(|_| DtbError::InvalidAddress)?;
+        if read_u32(header, 0) != Some(FDT_MAGIC) {
+            return Err(DtbError::BadMagic);
+        }
+
+        let total_size = read_u32(header, 4).unwrap() as usize;
+        Self::new(
+            checked_slice(addr as *const u8, total_size).map_err(|_| DtbError::InvalidAddress)?,
+        )
+    }
+
+    /// Returns the size of the blob in bytes.
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns the `(base, size)` pairs in the `reg` property of every `/memory` node.
+    ///
+    /// # Errors
+    ///
+    /// * [`DtbError::InvalidStructure`]: the root node's `#address-cells` and `#size-cells` are so
+    /// large that the size of a `reg` entry overflows
+    pub fn memory_regions(&self) -> Result<Vec<(u64, u64)>, DtbError> {
+        let mut regions = Vec::new();
+        let mut address_cells = DEFAULT_ADDRESS_CELLS;
+        let mut size_cells = DEFAULT_SIZE_CELLS;
+        let mut error = None;
+
+        self.for_each_property(|depth, node_name, property_name, value| {
+            // The root node's properties come before its children, so the cell counts are known
+            // before any `/memory` node is reached
+            if depth == 1 {
+                match property_name {
+                    b"#address-cells" => address_cells = read_u32(value, 0).unwrap_or(0),
+                    b"#size-cells" => size_cells = read_u32(value, 0).unwrap_or(0),
+                    _ => {}
+                }
+                return;
+            }
+
+            let is_memory_node = node_name == b"memory" || node_name.starts_with(b"memory@");
+            if depth != 2 || !is_memory_node || property_name != b"reg" {
+                return;
+            }
+
+            // The cell counts come from the blob, so they can be any value
+            let entry_size = address_cells
+                .checked_add(size_cells)
+                .and_then(|cells| usize::try_from(cells).ok()?.checked_mul(4));
+            let entry_size = match entry_size {
+                Some(0) => return,
+                Some(entry_size) => entry_size,
+                None => {
+                    error = Some(DtbError::InvalidStructure);
+                    return;
+                }
+            };
+            for entry in value.chunks_exact(entry_size) {
+                let (base, size) = entry.split_at(address_cells as usize * 4);
+                if let (Some(base), Some(size)) = (read_cells(base), read_cells(size)) {
+                    regions.push((base, size));
+                }
+            }
+        })?;
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(regions),
+        }
+    }
+
+    /// Calls `f` with the node depth, node name, property name, and property value of every
+    /// property in the structure block.
+    ///
+    /// The root node has a depth of 1 and an empty name. Unit addresses are included in node
+    /// names.
+    fn for_each_property<F: FnMut(usize, &[u8], &[u8], &[u8])>(
+        &self,
+        mut f: F,
+    ) -> Result<(), DtbError> {
+        let structure = self.structure_block()?;
+        let strings = self.strings_block()?;
+
+        // Only the name of the innermost node is needed, as only the root and its direct
+        // children are ever matched
+        let mut node_name: &[u8] = &[];
+        let mut depth: usize = 0;
+        let mut offset = 0;
+        loop {
+            let token = read_u32(structure, offset).ok_or(DtbError::Truncated)?;
+            offset += 4;
+
+            match token {
+                FDT_BEGIN_NODE => {
+                    node_name = read_str(structure, offset)?;
+                    offset = align_up_4(offset + node_name.len() + 1);
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    depth = depth.checked_sub(1).ok_or(DtbError::InvalidStructure)?;
+                    node_name = &[];
+                }
+                FDT_PROP => {
+                    let len = read_u32(structure, offset).ok_or(DtbError::Truncated)? as usize;
+                    let name_offset =
+                        read_u32(structure, offset + 4).ok_or(DtbError::Truncated)? as usize;
+                    offset += 8;
+
+                    let value = structure
+                        .get(offset..offset + len)
+                        .ok_or(DtbError::Truncated)?;
+                    let property_name = read_str(strings, name_offset)?;
+                    if depth == 0 {
+                        return Err(DtbError::InvalidStructure);
+                    }
+                    f(depth, node_name, property_name, value);
+
+                    offset = align_up_4(offset + len);
+                }
+                FDT_NOP => {}
+                FDT_END if depth == 0 => return Ok(()),
+                _ => return Err(DtbError::InvalidStructure),
+            }
+        }
+    }
+
+    /// Returns the structure block.
+    fn structure_block(&self) -> Result<&'a [u8], DtbError> {
+        self.block(8, 36)
+    }
+
+    /// Returns the strings block.
+    fn strings_block(&self) -> Result<&'a [u8], DtbError> {
+        self.block(12, 32)
+    }
+
+    /// Returns the block whose offset and size are stored in the header at `offset_field` and
+    /// `size_field`.
+    fn block(&self, offset_field: usize, size_field: usize) -> Result<&'a [u8], DtbError> {
+        let data = self.data;
+        let start = read_u32(data, offset_field).unwrap() as usize;
+        let size = read_u32(data, size_field).unwrap() as usize;
+        data.get(start..start + size).ok_or(DtbError::Truncated)
+    }
+}
+
+/// Reads a big-endian `u32` at `offset` in `data`.
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Reads a value made of one or two big-endian cells.
+fn read_cells(cells: &[u8]) -> Option<u64> {
+    match cells.len() {
+        4 => read_u32(cells, 0).map(u64::from),
+        8 => Some((u64::from(read_u32(cells, 0)?) << 32) | u64::from(read_u32(cells, 4)?)),
+        _ => None,
+    }
+}
+
+/// Reads a null-terminated string at `offset` in `data`, without the null terminator.
+fn read_str(data: &[u8], offset: usize) -> Result<&[u8], DtbError> {
+    let bytes = data.get(offset..).ok_or(DtbError::Truncated)?;
+    let len = bytes
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or(DtbError::Truncated)?;
+    Ok(&bytes[..len])
+}
+
+/// Aligns `offset` up to the next multiple of 4.
+fn align_up_4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    /// Builds devicetree blobs for testing.
+    struct DtbBuilder {
+        structure: Vec<u8>,
+        strings: Vec<u8>,
+    }
+
+    impl DtbBuilder {
+        fn new() -> Self {
+            Self {
+                structure: Vec::new(),
+                strings: Vec::new(),
+            }
+        }
+
+        fn token(&mut self, token: u32) -> &mut Self {
+            self.structure.extend_from_slice(&token.to_be_bytes());
+            self
+        }
+
+        fn begin_node(&mut self, name: &str) -> &mut Self {
+            self.token(FDT_BEGIN_NODE);
+            self.structure.extend_from_slice(name.as_bytes());
+            self.structure.push(0);
+            self.pad()
+        }
+
+        fn property(&mut self, name: &str, value: &[u32]) -> &mut Self {
+            let name_offset = self.strings.len() as u32;
+            self.strings.extend_from_slice(name.as_bytes());
+            self.strings.push(0);
+
+            self.token(FDT_PROP)
+                .token(value.len() as u32 * 4)
+                .token(name_offset);
+            for &cell in value {
+                self.token(cell);
+            }
+            self
+        }
+
+        fn pad(&mut self) -> &mut Self {
+            while self.structure.len() % 4 != 0 {
+                self.structure.push(0);
+            }
+            self
+        }
+
+        fn build(&mut self) -> Vec<u8> {
+            self.token(FDT_END);
+
+            let structure_offset = FDT_HEADER_SIZE + 16;
+            let strings_offset = structure_offset + self.structure.len();
+            let total_size = strings_offset + self.strings.len();
+
+            let mut header = vec![0; FDT_HEADER_SIZE];
+            for (field, value) in [
+                FDT_MAGIC,
+                total_size as u32,
+                structure_offset as u32,
+                strings_offset as u32,
+                FDT_HEADER_SIZE as u32,
+                17,
+                16,
+                0,
+                self.strings.len() as u32,
+                self.structure.len() as u32,
+            ]
+            .iter()
+            .enumerate()
+            {
+                header[field * 4..field * 4 + 4].copy_from_slice(&value.to_be_bytes());
+            }
+
+            // An empty memory reservation block
+            header.extend_from_slice(&[0; 16]);
+            header.extend_from_slice(&self.structure);
+            header.extend_from_slice(&self.strings);
+            header
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * Memory regions with 2-cell addresses and sizes are read
+    /// * Multiple entries in a single `reg` property are read
+    /// * `reg` properties of non-memory nodes are ignored
+    #[test]
+    fn memory_regions() {
+        let data = DtbBuilder::new()
+            .begin_node("")
+            .property("#address-cells", &[2])
+            .property("#size-cells", &[2])
+            .begin_node("memory@40000000")
+            .property("device_type", &[u32::from_be_bytes(*b"mem\0")])
+            .property("reg", &[0, 0x4000_0000, 0, 0x800_0000, 0x1, 0, 0, 0x1000])
+            .token(FDT_END_NODE)
+            .token(FDT_NOP)
+            .begin_node("pl011@9000000")
+            .property("reg", &[0, 0x900_0000, 0, 0x1000])
+            .token(FDT_END_NODE)
+            .token(FDT_END_NODE)
+            .build();
+
+        let dtb = Dtb::new(&data).expect("Failed to parse DTB");
+        assert_eq!(dtb.total_size(), data.len());
+        assert_eq!(
+            dtb.memory_regions(),
+            Ok(vec![(0x4000_0000, 0x800_0000), (0x1_0000_0000, 0x1000)])
+        );
+    }
+
+    /// Ensures that the default cell counts are used when the root node does not specify them.
+    #[test]
+    fn default_cells() {
+        let data = DtbBuilder::new()
+            .begin_node("")
+            .begin_node("memory")
+            .property("reg", &[0, 0x4000_0000, 0x800_0000])
+            .token(FDT_END_NODE)
+            .token(FDT_END_NODE)
+            .build();
+
+        let dtb = Dtb::new(&data).expect("Failed to parse DTB");
+        assert_eq!(dtb.memory_regions(), Ok(vec![(0x4000_0000, 0x800_0000)]));
+    }
+
+    /// Ensures that cell counts whose `reg` entry size overflows return an error.
+    #[test]
+    fn overflowing_cells() {
+        let data = DtbBuilder::new()
+            .begin_node("")
+            .property("#address-cells", &[u32::MAX])
+            .property("#size-cells", &[1])
+            .begin_node("memory")
+            .property("reg", &[0, 0x4000_0000, 0x800_0000])
+            .token(FDT_END_NODE)
+            .token(FDT_END_NODE)
+            .build();
+
+        let dtb = Dtb::new(&data).expect("Failed to parse DTB");
+        assert_eq!(dtb.memory_regions(), Err(DtbError::InvalidStructure));
+    }
+
+    /// Ensures that proper errors are returned for invalid blobs.
+    #[test]
+    fn invalid_blobs() {
+        let data = DtbBuilder::new().begin_node("").token(FDT_END_NODE).build();
+        assert!(Dtb::new(&data).is_ok());
+        assert_eq!(
+            Dtb::new(&data[..data.len() - 1]).err(),
+            Some(DtbError::Truncated)
+        );
+
+        let mut bad_magic = data;
+        bad_magic[0] = 0;
+        assert_eq!(Dtb::new(&bad_magic).err(), Some(DtbError::BadMagic));
+
+        let unbalanced = DtbBuilder::new().begin_node("").build();
+        assert_eq!(
+            Dtb::new(&unbalanced).err(),
+            Some(DtbError::InvalidStructure)
+        );
+
+        assert_eq!(
+            unsafe { Dtb::from_addr(0) }.err(),
+            Some(DtbError::InvalidAddress)
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! A minimal ELF64 parser for loading a kernel image.
+
+/// The error type returned when parsing an ELF file with [`Elf64::new`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ElfError {
+    /// The file does not start with the ELF magic bytes.
+    BadMagic,
+    /// The file is not a 64-bit ELF file.
+    WrongClass,
+    /// The file is not little-endian.
+    WrongEndianness,
+    /// The file was built for a different architecture.
+    WrongMachine,
+    /// The file is too small to contain its ELF header or program headers.
+    TruncatedHeader,
+    /// A loadable segment's file bytes are not contained in the file, or its file size is
+    /// larger than its memory size.
+    InvalidSegment,
+}
+
+/// The type of a [`ProgramHeader`] that describes a loadable segment.
+pub const PT_LOAD: u32 = 1;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LITTLE_ENDIAN: u8 = 1;
+#[cfg(target_arch = "x86_64")]
+const ELF_MACHINE: u16 = 62;
+#[cfg(target_arch = "aarch64")]
+const ELF_MACHINE: u16 = 183;
+
+const ELF_HEADER_SIZE: usize = 64;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// A single entry of an ELF file's program header table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProgramHeader {
+    pub segment_type: u32,
+    pub flags: u32,
+    /// The offset of the segment's bytes in the file.
+    pub offset: u64,
+    pub virtual_address: u64,
+    pub physical_address: u64,
+    /// The number of bytes of the segment that are stored in the file.
+    pub file_size: u64,
+    /// The number of bytes of the segment in memory. Any bytes after `file_size` are zeroed.
+    pub memory_size: u64,
+    pub align: u64,
+}
+
+impl ProgramHeader {
+    /// Parses a program header from the start of `raw`, which must contain at least
+    /// `PROGRAM_HEADER_SIZE` bytes.
+    fn parse(raw: &[u8]) -> Self {
+        Self {
+            segment_type: read_u32(raw, 0x00),
+            flags: read_u32(raw, 0x04),
+            offset: read_u64(raw, 0x08),
+            virtual_address: read_u64(raw, 0x10),
+            physical_address: read_u64(raw, 0x18),
+            file_size: read_u64(raw, 0x20),
+            memory_size: read_u64(raw, 0x28),
+            align: read_u64(raw, 0x30),
+        }
+    }
+
+    /// Returns the range of bytes in the file that contain this segment.
+    fn file_range(&self) -> Option<core::ops::Range<usize>> {
+        let start = usize::try_from(self.offset).ok()?;
+        let end = start.checked_add(usize::try_from(self.file_size).ok()?)?;
+        Some(start..end)
+    }
+}
+
+/// A validated 64-bit, little-endian ELF file for the current architecture.
+#[derive(Clone, Copy, Debug)]
+pub struct Elf64<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Elf64<'a> {
+    /// Validates the ELF header and program headers in `data`.
+    ///
+    /// # Errors
+    ///
+    /// * [`ElfError::TruncatedHeader`]: `data` is too small to contain the headers
+    /// * [`ElfError::BadMagic`]: `data` does not start with the ELF magic bytes
+    /// * [`ElfError::WrongClass`]: `data` is not a 64-bit ELF file
+    /// * [`ElfError::WrongEndianness`]: `data` is not little-endian
+    /// * [`ElfError::WrongMachine`]: `data` was built for a different architecture
+    /// * [`ElfError::InvalidSegment`]: a loadable segment is not contained in `data`
+    pub fn new(data: &'a [u8]) -> Result<Self, ElfError> {
+        if data.len() < ELF_HEADER_SIZE {
+            return Err(ElfError::TruncatedHeader);
+        }
+        if data[..4] != ELF_MAGIC {
+            return Err(ElfError::BadMagic);
+        }
+        if data[4] != ELF_CLASS_64 {
+            return Err(ElfError::WrongClass);
+        }
+        if data[5] != ELF_DATA_LITTLE_ENDIAN {
+            return Err(ElfError::WrongEndianness);
+        }
+        if read_u16(data, 0x12) != ELF_MACHINE {
+            return Err(ElfError::WrongMachine);
+        }
+
+        let elf = Self { data };
+
+        // Every program header needs to be contained in the file
+        let entry_size = read_u16(data, 0x36) as usize;
+        let table_size = elf.program_header_count().checked_mul(entry_size);
+        let table_end = table_size.and_then(|size| size.checked_add(elf.program_header_offset()?));
+        if entry_size < PROGRAM_HEADER_SIZE || table_end.map_or(true, |end| end > data.len()) {
+            return Err(ElfError::TruncatedHeader);
+        }
+
+        for header in elf.program_headers() {
+            if header.segment_type != PT_LOAD {
+                continue;
+            }
+
+            let in_file = header
+                .file_range()
+                .map_or(false, |range| range.end <= data.len());
+            if !in_file || header.file_size > header.memory_size {
+                return Err(ElfError::InvalidSegment);
+            }
+        }
+
+        Ok(elf)
+    }
+
+    /// Returns the address of the entry point.
+    pub fn entry(&self) -> u64 {
+        read_u64(self.data, 0x18)
+    }
+
+    /// Calls `place` for every loadable (`PT_LOAD`) segment.
+    ///
+    /// `place` is given the segment's physical address, the segment's bytes from the file, and
+    /// the segment's size in memory. It should copy the bytes to the address and zero the
+    /// remaining `memory_size - bytes.len()` bytes after them.
+    pub fn load_segments<F: FnMut(u64, &[u8], usize)>(&self, mut place: F) {
+        for header in self.program_headers() {
+            if header.segment_type != PT_LOAD {
+                continue;
+            }
+
+            // Loadable segments were checked to be in the file in `new`
+            let bytes = &self.data[header.file_range().unwrap()];
+            place(header.physical_address, bytes, header.memory_size as usize);
+        }
+    }
+
+    /// Returns an iterator over every program header.
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + 'a {
+        let data = self.data;
+        let offset = self.program_header_offset().unwrap_or(0);
+        let entry_size = read_u16(data, 0x36) as usize;
+
+        (0..self.program_header_count()).map(move |index| {
+            let start = offset + index * entry_size;
+            ProgramHeader::parse(&data[start..start + PROGRAM_HEADER_SIZE])
+        })
+    }
+
+    /// Returns the number of program headers.
+    fn program_header_count(&self) -> usize {
+        read_u16(self.data, 0x38) as usize
+    }
+
+    /// Returns the offset of the program header table in the file.
+    fn program_header_offset(&self) -> Option<usize> {
+        usize::try_from(read_u64(self.data, 0x20)).ok()
+    }
+}
+
+/// Reads a little-endian `u16` at `offset` in `data`.
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+/// Reads a little-endian `u32` at `offset` in `data`.
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+/// Reads a little-endian `u64` at `offset` in `data`.
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    const ENTRY: u64 = 0x20_0010;
+    const SEGMENT_ADDR: u64 = 0x20_0000;
+    const SEGMENT_OFFSET: usize = ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE * 2;
+    const SEGMENT_BYTES: &[u8] = b"kernel code";
+    const SEGMENT_MEMORY_SIZE: u64 = 0x100;
+
+    /// Returns a minimal ELF file with a loadable segment followed by a non-loadable segment.
+    fn build_elf() -> Vec<u8> {
+        let mut elf = vec![0; SEGMENT_OFFSET];
+        elf[..4].copy_from_slice(&ELF_MAGIC);
+        elf[4] = ELF_CLASS_64;
+        elf[5] = ELF_DATA_LITTLE_ENDIAN;
+        elf[0x12..0x14].copy_from_slice(&ELF_MACHINE.to_le_bytes());
+        elf[0x18..0x20].copy_from_slice(&ENTRY.to_le_bytes());
+        elf[0x20..0x28].copy_from_slice(&(ELF_HEADER_SIZE as u64).to_le_bytes());
+        elf[0x36..0x38].copy_from_slice(&(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        elf[0x38..0x3a].copy_from_slice(&2u16.to_le_bytes());
+
+        let load = &mut elf[ELF_HEADER_SIZE..ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE];
+        load[0x00..0x04].copy_from_slice(&PT_LOAD.to_le_bytes());
+        load[0x08..0x10].copy_from_slice(&(SEGMENT_OFFSET as u64).to_le_bytes());
+        load[0x10..0x18].copy_from_slice(&SEGMENT_ADDR.to_le_bytes());
+        load[0x18..0x20].copy_from_slice(&SEGMENT_ADDR.to_le_bytes());
+        load[0x20..0x28].copy_from_slice(&(SEGMENT_BYTES.len() as u64).to_le_bytes());
+        load[0x28..0x30].copy_from_slice(&SEGMENT_MEMORY_SIZE.to_le_bytes());
+
+        // A `PT_NOTE` segment, which should not be loaded
+        let note = &mut elf[ELF_HEADER_SIZE + PROGRAM_HEADER_SIZE..SEGMENT_OFFSET];
+        note[0x00..0x04].copy_from_slice(&4u32.to_le_bytes());
+
+        elf.extend_from_slice(SEGMENT_BYTES);
+        elf
+    }
+
+    /// Ensures that:
+    ///
+    /// * A valid ELF file is parsed
+    /// * The entry point is read correctly
+    /// * Only loadable segments are passed to `load_segments`
+    #[test]
+    fn load_segments() {
+        let data = build_elf();
+        let elf = Elf64::new(&data).expect("Failed to parse ELF file");
+        assert_eq!(elf.entry(), ENTRY);
+        assert_eq!(elf.program_headers().count(), 2);
+
+        let mut segments = Vec::new();
+        elf.load_segments(|addr, bytes, memory_size| {
+            segments.push((addr, bytes.to_vec(), memory_size));
+        });
+        assert_eq!(
+            segments,
+            [(
+                SEGMENT_ADDR,
+                SEGMENT_BYTES.to_vec(),
+                SEGMENT_MEMORY_SIZE as usize
+            )]
+        );
+    }
+
+    /// Ensures that proper errors are returned for invalid ELF files.
+    #[test]
+    fn invalid_files() {
+        let data = build_elf();
+        assert_eq!(
+            Elf64::new(&data[..ELF_HEADER_SIZE - 1]).err(),
+            Some(ElfError::TruncatedHeader)
+        );
+        assert_eq!(
+            Elf64::new(&data[..SEGMENT_OFFSET - 1]).err(),
+            Some(ElfError::TruncatedHeader)
+        );
+        assert_eq!(
+            Elf64::new(&data[..data.len() - 1]).err(),
+            Some(ElfError::InvalidSegment)
+        );
+
+        let mut bad_magic = data.clone();
+        bad_magic[1] = b'e';
+        assert_eq!(Elf64::new(&bad_magic).err(), Some(ElfError::BadMagic));
+
+        let mut wrong_class = data.clone();
+        wrong_class[4] = 1;
+        assert_eq!(Elf64::new(&wrong_class).err(), Some(ElfError::WrongClass));
+
+        let mut wrong_machine = data;
+        wrong_machine[0x12..0x14].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            Elf64::new(&wrong_machine).err(),
+            Some(ElfError::WrongMachine)
+        );
+    }
+}
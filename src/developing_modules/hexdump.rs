@@ -0,0 +1,108 @@
+//! Hex dumps of memory for debugging.
+//!
+//! Each line shows the address of its first byte, up to 16 bytes in hexadecimal, and the same
+//! bytes as ASCII, with `.` in place of any unprintable byte:
+//!
+//! ```text
+//! 0000000000001000: 48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 ff |Hello, world!...|
+//! 0000000000001010: 2a 2a                                           |**|
+//! ```
+
+use core::{fmt, ptr};
+
+/// The number of bytes shown on each line.
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes a hex dump of `data` to `out`, labelling the first byte with the address `addr`.
+///
+/// `addr` is only used for labels, so it does not need to be the address of `data`.
+pub fn hexdump(mut out: impl fmt::Write, addr: usize, data: &[u8]) -> fmt::Result {
+    for (index, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        write_line(&mut out, addr + index * BYTES_PER_LINE, line)?;
+    }
+    Ok(())
+}
+
+/// Writes a hex dump of the `len` bytes starting at `addr` to `out`, reading each byte with a
+/// volatile read.
+///
+/// This can be used to dump device memory, where each read must actually take place.
+///
+/// # Safety
+///
+/// `addr` must be valid for `len` byte reads. Reading device memory can have side effects on the
+/// device.
+pub unsafe fn hexdump_volatile(mut out: impl fmt::Write, addr: usize, len: usize) -> fmt::Result {
+    let mut line = [0; BYTES_PER_LINE];
+    for line_start in (0..len).step_by(BYTES_PER_LINE) {
+        let line_len = (len - line_start).min(BYTES_PER_LINE);
+        let line_addr = addr + line_start;
+        for (offset, byte) in line[..line_len].iter_mut().enumerate() {
+            *byte = ptr::read_volatile((line_addr + offset) as *const u8);
+        }
+        write_line(&mut out, line_addr, &line[..line_len])?;
+    }
+    Ok(())
+}
+
+/// Writes a single line of a hex dump, padding the hex bytes of a partial line so that its ASCII
+/// is aligned with the lines before it.
+fn write_line(out: &mut impl fmt::Write, addr: usize, line: &[u8]) -> fmt::Result {
+    write!(out, "{addr:016x}:")?;
+    for byte in line {
+        write!(out, " {byte:02x}")?;
+    }
+    for _ in line.len()..BYTES_PER_LINE {
+        out.write_str("   ")?;
+    }
+
+    out.write_str(" |")?;
+    for &byte in line {
+        let ascii = if byte.is_ascii_graphic() || byte == b' ' {
+            byte as char
+        } else {
+            '.'
+        };
+        out.write_char(ascii)?;
+    }
+    out.write_str("|\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::String;
+
+    const EXPECTED: &str = "\
+0000000000001000: 48 65 6c 6c 6f 2c 20 77 6f 72 6c 64 21 0a 00 ff |Hello, world!...|
+0000000000001010: 2a 2a                                           |**|
+";
+
+    /// Ensures that:
+    ///
+    /// * Full lines show 16 bytes, and unprintable bytes are shown as `.`
+    /// * Partial lines are padded so that their ASCII is aligned
+    /// * Volatile dumps match regular dumps
+    /// * Empty data writes nothing
+    #[test]
+    fn dumps() {
+        let data = b"Hello, world!\n\0\xff**";
+
+        let mut dump = String::new();
+        hexdump(&mut dump, 0x1000, data).unwrap();
+        assert_eq!(dump, EXPECTED);
+
+        let mut volatile_dump = String::new();
+        unsafe { hexdump_volatile(&mut volatile_dump, data.as_ptr() as usize, data.len()) }
+            .unwrap();
+        let addr = data.as_ptr() as usize;
+        let mut expected = String::new();
+        hexdump(&mut expected, addr, data).unwrap();
+        assert_eq!(volatile_dump, expected);
+
+        let mut empty = String::new();
+        hexdump(&mut empty, 0, &[]).unwrap();
+        unsafe { hexdump_volatile(&mut empty, data.as_ptr() as usize, 0) }.unwrap();
+        assert_eq!(empty, "");
+    }
+}
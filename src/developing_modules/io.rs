@@ -1,14 +1,91 @@
 /// This implementation is a shortened version of the RedoxOS implementation found here:
 ///
 /// <https://gitlab.redox-os.org/redox-os/syscall/-/blob/master/src/io/io.rs>
+use core::{
+    fmt,
+    ops::{BitAnd, BitOr, Not, Shl, Shr},
+};
 
 pub trait Io {
     type Value: Copy + PartialEq;
 
     fn read(&self) -> Self::Value;
     fn write(&mut self, value: Self::Value);
+
+    /// Reads the `width` bits starting at bit `offset`, shifted down to the lowest bits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bit range does not fit in `Self::Value`.
+    #[inline(always)]
+    fn read_bits(&self, offset: u32, width: u32) -> Self::Value
+    where
+        Self::Value: RegisterInt,
+    {
+        let mask = Self::Value::mask(offset, width);
+        (self.read() & mask) >> offset
+    }
+
+    /// Writes the lowest `width` bits of `value` to the bits starting at bit `offset`, leaving
+    /// every other bit unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bit range does not fit in `Self::Value`.
+    #[inline(always)]
+    fn write_bits(&mut self, offset: u32, width: u32, value: Self::Value)
+    where
+        Self::Value: RegisterInt,
+    {
+        let mask = Self::Value::mask(offset, width);
+        let value = (self.read() & !mask) | ((value << offset) & mask);
+        self.write(value);
+    }
+}
+
+/// An unsigned integer that can be used with [`Io::read_bits`] and [`Io::write_bits`].
+pub trait RegisterInt:
+    Copy
+    + PartialEq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+{
+    const BITS: u32;
+    const ZERO: Self;
+
+    /// Returns a value with the `width` bits starting at bit `offset` set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bit range does not fit in `Self`.
+    #[inline(always)]
+    fn mask(offset: u32, width: u32) -> Self {
+        assert!(offset < Self::BITS && width <= Self::BITS - offset);
+
+        if width == 0 {
+            Self::ZERO
+        } else {
+            (!Self::ZERO >> (Self::BITS - width)) << offset
+        }
+    }
+}
+
+macro_rules! impl_register_int {
+    ($($int:ty),*) => {
+        $(
+            impl RegisterInt for $int {
+                const BITS: u32 = <$int>::BITS;
+                const ZERO: Self = 0;
+            }
+        )*
+    };
 }
 
+impl_register_int!(u8, u16, u32, u64);
+
 pub struct ReadOnly<I> {
     inner: I,
 }
@@ -26,13 +103,23 @@ impl<I: Io> ReadOnly<I> {
     }
 }
 
+/// Reads the register to show its value.
+impl<I: Io> fmt::Debug for ReadOnly<I>
+where
+    I::Value: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReadOnly").field(&self.read()).finish()
+    }
+}
+
 pub struct WriteOnly<I> {
     inner: I,
 }
 
 impl<I> WriteOnly<I> {
-    pub const fn new(inner: I) -> ReadOnly<I> {
-        ReadOnly { inner }
+    pub const fn new(inner: I) -> WriteOnly<I> {
+        WriteOnly { inner }
     }
 }
 
@@ -42,3 +129,154 @@ impl<I: Io> WriteOnly<I> {
         self.inner.write(value);
     }
 }
+
+/// Never reads the register, as reading a write-only register can have side effects or return
+/// garbage.
+impl<I> fmt::Debug for WriteOnly<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WriteOnly(<write-only>)")
+    }
+}
+
+pub struct ReadWrite<I> {
+    inner: I,
+}
+
+impl<I> ReadWrite<I> {
+    pub const fn new(inner: I) -> ReadWrite<I> {
+        ReadWrite { inner }
+    }
+}
+
+impl<I: Io> ReadWrite<I> {
+    /// Reads the value, applies `f` to it, and writes the result back.
+    #[inline(always)]
+    pub fn modify<F: FnOnce(I::Value) -> I::Value>(&mut self, f: F) {
+        let value = self.inner.read();
+        self.inner.write(f(value));
+    }
+
+    #[inline(always)]
+    pub fn read(&self) -> I::Value {
+        self.inner.read()
+    }
+
+    #[inline(always)]
+    pub fn write(&mut self, value: I::Value) {
+        self.inner.write(value);
+    }
+}
+
+/// Reads the register to show its value.
+impl<I: Io> fmt::Debug for ReadWrite<I>
+where
+    I::Value: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReadWrite").field(&self.read()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::developing_modules::mmio::Mmio;
+    use core::mem;
+    use std::format;
+
+    /// A register that panics if it is ever read.
+    struct NoRead;
+
+    impl Io for NoRead {
+        type Value = u32;
+
+        fn read(&self) -> u32 {
+            panic!("Register should not be read");
+        }
+
+        fn write(&mut self, _value: u32) {}
+    }
+
+    /// Ensures that:
+    ///
+    /// * `WriteOnly::new` returns a `WriteOnly` wrapper
+    /// * Writing through the wrapper changes the inner register
+    #[test]
+    fn write_only() {
+        // `Mmio` is usually placed over device memory, so it has no constructor
+        let inner: Mmio<u32> = unsafe { mem::transmute(u32::MAX) };
+        let mut register: WriteOnly<Mmio<u32>> = WriteOnly::new(inner);
+
+        register.write(0);
+        assert_eq!(register.inner.read(), 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Values written through a `ReadWrite` wrapper can be read back
+    /// * `modify` only changes the bits that are changed by its closure
+    #[test]
+    fn read_write() {
+        let inner: Mmio<u32> = unsafe { mem::transmute(0u32) };
+        let mut register = ReadWrite::new(inner);
+
+        register.write(0xF0F0);
+        assert_eq!(register.read(), 0xF0F0);
+
+        register.modify(|value| value | 0x1);
+        assert_eq!(register.read(), 0xF0F1);
+
+        register.modify(|value| value & !0xF000);
+        assert_eq!(register.read(), 0x00F1);
+    }
+
+    /// Ensures that:
+    ///
+    /// * `read_bits` returns only the requested bits, shifted down
+    /// * `write_bits` only changes the requested bits
+    /// * Bit ranges that cover the whole value work
+    #[test]
+    fn bitfields() {
+        let mut register: Mmio<u32> = unsafe { mem::transmute(0xABCD_1234u32) };
+
+        assert_eq!(register.read_bits(0, 4), 0x4);
+        assert_eq!(register.read_bits(3, 1), 0x0);
+        assert_eq!(register.read_bits(4, 1), 0x1);
+        assert_eq!(register.read_bits(16, 16), 0xABCD);
+        assert_eq!(register.read_bits(0, 32), 0xABCD_1234);
+        assert_eq!(register.read_bits(8, 0), 0);
+
+        register.write_bits(8, 8, 0xFF);
+        assert_eq!(register.read(), 0xABCD_FF34);
+
+        // Bits past `width` are ignored
+        register.write_bits(0, 4, 0x15);
+        assert_eq!(register.read(), 0xABCD_FF35);
+
+        register.write_bits(0, 32, 0);
+        assert_eq!(register.read(), 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Formatting a `WriteOnly` wrapper does not read the register
+    /// * Formatting a `ReadOnly` or `ReadWrite` wrapper shows the register's value
+    #[test]
+    fn debug_format() {
+        let register = WriteOnly::new(NoRead);
+        assert_eq!(format!("{register:?}"), "WriteOnly(<write-only>)");
+
+        let inner: Mmio<u32> = unsafe { mem::transmute(0x12u32) };
+        assert_eq!(format!("{:x?}", ReadOnly::new(inner)), "ReadOnly(12)");
+        let inner: Mmio<u32> = unsafe { mem::transmute(0x34u32) };
+        assert_eq!(format!("{:x?}", ReadWrite::new(inner)), "ReadWrite(34)");
+    }
+
+    /// Ensures that a bit range that does not fit in the register panics.
+    #[test]
+    #[should_panic]
+    fn bitfield_out_of_range() {
+        let register: Mmio<u8> = unsafe { mem::transmute(0u8) };
+        register.read_bits(4, 5);
+    }
+}
@@ -3,9 +3,15 @@
 /// https://gitlab.redox-os.org/redox-os/syscall/-/blob/master/src/io/mmio.rs
 use core::{
     mem::MaybeUninit,
+    ops::{Deref, DerefMut},
     ptr::{addr_of, addr_of_mut, read_volatile, write_volatile},
 };
 
+#[cfg(any(target_arch = "aarch64", target_arch = "x86_64"))]
+use core::arch::asm;
+#[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+use core::sync::atomic::{fence, Ordering};
+
 use crate::developing_modules::io::Io;
 
 #[repr(packed)]
@@ -27,3 +33,199 @@ where
         unsafe { write_volatile(addr_of_mut!(self.value).cast::<T>(), value) };
     }
 }
+
+impl<T> Mmio<T>
+where
+    T: Copy + PartialEq,
+{
+    /// Reads the value with a memory barrier before and after the read.
+    ///
+    /// This ensures that the read is ordered against every other memory access, not just other
+    /// accesses to this register.
+    pub fn read_sync(&self) -> T {
+        memory_barrier();
+        let value = self.read();
+        memory_barrier();
+        value
+    }
+
+    /// Writes the value with a memory barrier before and after the write.
+    ///
+    /// This ensures that the write is ordered against every other memory access, not just other
+    /// accesses to this register.
+    pub fn write_sync(&mut self, value: T) {
+        memory_barrier();
+        self.write(value);
+        memory_barrier();
+    }
+}
+
+/// A register that is preceded by `PADDING` reserved bytes.
+///
+/// This is used by [`register_block!`](crate::register_block) to place each register at its
+/// offset. It dereferences to the register, so the padding can be ignored when using a register
+/// block.
+#[repr(C)]
+pub struct Padded<const PADDING: usize, R> {
+    _reserved: [u8; PADDING],
+    register: R,
+}
+
+impl<const PADDING: usize, R> Deref for Padded<PADDING, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.register
+    }
+}
+
+impl<const PADDING: usize, R> DerefMut for Padded<PADDING, R> {
+    fn deref_mut(&mut self) -> &mut R {
+        &mut self.register
+    }
+}
+
+/// Declares a `#[repr(C)]` struct for a block of memory-mapped device registers.
+///
+/// Each register is listed with its offset from the start of the block, its access (`RO`, `WO` or
+/// `RW`) and its type. Reserved bytes are inserted before each register so that it is placed at
+/// its offset. Registers must be listed in order of their offsets, and must not overlap; either
+/// mistake is a compile error.
+///
+/// Register types must have an alignment of 1, like [`Mmio`], so that the compiler does not add
+/// padding of its own. This is also checked at compile time.
+///
+/// ```
+/// use caliga_bootloader::{developing_modules::mmio::Mmio, register_block};
+///
+/// register_block! {
+///     /// Part of the register block of a PL011 UART.
+///     pub struct Pl011Registers {
+///         0x00 => pub data: RW Mmio<u32>,
+///         0x18 => pub flags: RO Mmio<u32>,
+///         0x24 => pub integer_baud_rate: RW Mmio<u32>,
+///     }
+/// }
+///
+/// assert_eq!(core::mem::size_of::<Pl011Registers>(), 0x28);
+/// ```
+#[macro_export]
+macro_rules! register_block {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($registers:tt)*
+        }
+    ) => {
+        $crate::register_block!(
+            @registers [$(#[$attr])* $vis struct $name] [] (0) $($registers)*
+        );
+    };
+
+    // Adds the next register, padded from the end of the previous register
+    (
+        @registers [$($header:tt)*] [$($fields:tt)*] ($end:expr)
+        $(#[$field_attr:meta])*
+        $offset:literal => $field_vis:vis $field:ident : $access:ident $ty:ty
+        $(, $($rest:tt)*)?
+    ) => {
+        const _: () = assert!(
+            core::mem::align_of::<$ty>() == 1,
+            "Registers in a register block must have an alignment of 1",
+        );
+        $crate::register_block!(
+            @registers [$($header)*]
+            [
+                $($fields)*
+                $(#[$field_attr])*
+                $field_vis $field: $crate::developing_modules::mmio::Padded<
+                    { $offset - ($end) },
+                    $crate::register_block!(@access $access $ty),
+                >,
+            ]
+            ($offset + core::mem::size_of::<$ty>())
+            $($($rest)*)?
+        );
+    };
+
+    // Every register has been added
+    (@registers [$(#[$attr:meta])* $vis:vis struct $name:ident] [$($fields:tt)*] ($end:expr)) => {
+        $(#[$attr])*
+        #[repr(C)]
+        $vis struct $name {
+            $($fields)*
+        }
+    };
+
+    (@access RO $ty:ty) => { $crate::developing_modules::io::ReadOnly<$ty> };
+    (@access WO $ty:ty) => { $crate::developing_modules::io::WriteOnly<$ty> };
+    (@access RW $ty:ty) => { $crate::developing_modules::io::ReadWrite<$ty> };
+}
+
+/// Orders every memory access before this barrier against every memory access after it.
+#[inline(always)]
+fn memory_barrier() {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        asm!("dmb sy", options(nostack, preserves_flags))
+    };
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        asm!("mfence", options(nostack, preserves_flags))
+    };
+
+    #[cfg(not(any(target_arch = "aarch64", target_arch = "x86_64")))]
+    fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::developing_modules::io::{ReadOnly, ReadWrite, WriteOnly};
+    use core::mem;
+
+    register_block! {
+        struct TestRegisters {
+            0x00 => control: RW Mmio<u32>,
+            0x04 => status: RO Mmio<u8>,
+            0x05 => interrupts: RW Mmio<u8>,
+            /// A register after a gap of reserved bytes
+            0x10 => data: WO Mmio<u64>,
+        }
+    }
+
+    fn offset_of<T>(register: &T, base: usize) -> usize {
+        register as *const T as usize - base
+    }
+
+    /// Ensures that:
+    ///
+    /// * Registers are placed at their offsets, with no padding after the last register
+    /// * Registers are wrapped in their access type
+    /// * Registers can be read and written through the padding
+    #[test]
+    fn register_block() {
+        assert_eq!(mem::size_of::<TestRegisters>(), 0x18);
+
+        let mut memory = [0u64; 3];
+        let base = memory.as_mut_ptr() as usize;
+        let registers = unsafe { &mut *(base as *mut TestRegisters) };
+        let _: &ReadWrite<Mmio<u32>> = &registers.control;
+        let _: &ReadOnly<Mmio<u8>> = &registers.status;
+        let _: &WriteOnly<Mmio<u64>> = &registers.data;
+        assert_eq!(offset_of(&*registers.control, base), 0x00);
+        assert_eq!(offset_of(&*registers.status, base), 0x04);
+        assert_eq!(offset_of(&*registers.interrupts, base), 0x05);
+        assert_eq!(offset_of(&*registers.data, base), 0x10);
+
+        registers.control.write(0x1234_5678);
+        registers.interrupts.modify(|value| value | 0x80);
+        registers.data.write(u64::MAX);
+        let words = unsafe { *(base as *const [u64; 3]) };
+        assert_eq!(words, [0x0000_8000_1234_5678, 0, u64::MAX]);
+
+        unsafe { *(base as *mut u8).add(4) = 0x42 };
+        assert_eq!(registers.status.read(), 0x42);
+    }
+}
@@ -3,11 +3,27 @@
 //! They will likely go through many changes before being included included in the main module tree.
 
 pub mod addressing;
+pub mod arch;
+pub mod array_string;
+pub mod array_vec;
+pub mod boot_info;
+pub mod boot_menu;
+pub mod checksum;
+pub mod config;
+pub mod console;
+pub mod cpio;
+pub mod crypto;
+pub mod dtb;
+pub mod elf;
+pub mod hexdump;
 pub mod io;
 pub mod mmio;
-//pub mod physical_allocator;
+pub mod physical_allocator;
 pub mod page_frame_allocator;
+pub mod raw;
 pub mod slab_allocator;
+pub mod slab_heap;
+pub mod timer;
 
 #[cfg(target_arch = "aarch64")]
 pub mod aarch64;
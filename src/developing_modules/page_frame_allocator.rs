@@ -1,10 +1,403 @@
+//! A bitmap-based allocator of physical page frames.
+
+use core::slice;
+
 use crate::developing_modules::addressing::PhysicalAddress;
 
+/// The error type returned by a [`PageFrameAllocator`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PageFrameAllocatorError {
+    /// The memory map did not contain any frames.
+    NoRegions,
+    /// None of the regions in the memory map can fit the allocator's bitmap.
+    NoSpaceForBitmap,
+    /// The frame is not aligned to the page size, or it is not managed by the allocator.
+    InvalidFrame,
+    /// The frame is already free.
+    DoubleFree,
+}
+
+/// The size of a page frame, which is always a power of two.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct PageSize {
-    exponent: usize
+    exponent: usize,
+}
+
+impl PageSize {
+    /// Returns a page size of `size` bytes, or `None` if `size` is not a power of two.
+    pub const fn new(size: usize) -> Option<Self> {
+        if !size.is_power_of_two() {
+            return None;
+        }
+
+        Some(Self {
+            exponent: size.trailing_zeros() as usize,
+        })
+    }
+
+    /// Returns the page size in bytes.
+    pub const fn size(&self) -> usize {
+        1 << self.exponent
+    }
+}
+
+impl Default for PageSize {
+    /// Returns a page size of 4KiB.
+    fn default() -> Self {
+        Self { exponent: 12 }
+    }
 }
 
+/// A page-aligned frame of physical memory.
+#[derive(Debug, Eq, PartialEq)]
 pub struct PageFrame {
     start: PhysicalAddress,
+}
+
+impl PageFrame {
+    /// Returns the address of the first byte in this frame.
+    pub fn start(&self) -> usize {
+        self.start.as_usize()
+    }
+}
+
+/// An allocator of physical page frames.
+///
+/// Each frame between the lowest and highest address in the memory map is tracked by a single bit
+/// in a bitmap, which is set if the frame is in use. The bitmap is stored in the first region of
+/// the memory map that can fit it, and the frames it is stored in are marked as used.
+#[derive(Debug)]
+pub struct PageFrameAllocator {
+    bitmap: &'static mut [u8],
+    /// The address of the first frame tracked by the bitmap.
+    base: usize,
+    frame_count: usize,
+    page_size: PageSize,
+    /// The index of the first frame that might be free.
+    next_free: usize,
+}
+
+impl PageFrameAllocator {
+    /// Returns an allocator that manages every frame fully contained in a region of
+    /// `memory_map`, which is a list of `(address, size)` pairs.
+    ///
+    /// Frames that are not in any region are reserved and never allocated.
+    ///
+    /// # Safety
+    ///
+    /// Every region in `memory_map` must be unused, writable memory that is not used for anything
+    /// else for as long as the allocator and the frames it returns are in use.
+    ///
+    /// # Errors
+    ///
+    /// * [`PageFrameAllocatorError::NoRegions`]: `memory_map` does not contain a whole frame
+    /// * [`PageFrameAllocatorError::NoSpaceForBitmap`]: no region can fit the bitmap
+    pub unsafe fn new(
+        memory_map: &[(usize, usize)],
+        page_size: PageSize,
+    ) -> Result<Self, PageFrameAllocatorError> {
+        let frame_size = page_size.size();
+        let frames = |&(addr, size): &(usize, usize)| {
+            let start = align_up(addr, frame_size)?;
+            let end = align_down(addr.saturating_add(size), frame_size);
+            (start < end).then_some((start, end))
+        };
+
+        // Find the range of addresses that needs to be tracked
+        let base = memory_map
+            .iter()
+            .filter_map(frames)
+            .map(|(start, _)| start)
+            .min();
+        let end = memory_map
+            .iter()
+            .filter_map(frames)
+            .map(|(_, end)| end)
+            .max();
+        let (Some(base), Some(end)) = (base, end) else {
+            return Err(PageFrameAllocatorError::NoRegions);
+        };
+        let frame_count = (end - base) / frame_size;
+
+        // Store the bitmap in the first region that can fit it
+        let bitmap_size = (frame_count + 7) / 8;
+        let bitmap_addr = memory_map
+            .iter()
+            .filter_map(frames)
+            .find(|(start, end)| end - start >= bitmap_size)
+            .map(|(start, _)| start)
+            .ok_or(PageFrameAllocatorError::NoSpaceForBitmap)?;
+        let bitmap = slice::from_raw_parts_mut(bitmap_addr as *mut u8, bitmap_size);
+
+        // Reserve every frame, then free the frames in each region
+        bitmap.fill(0xff);
+        let mut allocator = Self {
+            bitmap,
+            base,
+            frame_count,
+            page_size,
+            next_free: 0,
+        };
+        for (start, end) in memory_map.iter().filter_map(frames) {
+            for index in (start - base) / frame_size..(end - base) / frame_size {
+                allocator.set_used(index, false);
+            }
+        }
+
+        // Keep the bitmap from being allocated
+        let bitmap_frames = (bitmap_size + frame_size - 1) / frame_size;
+        let bitmap_index = (bitmap_addr - base) / frame_size;
+        for index in bitmap_index..bitmap_index + bitmap_frames {
+            allocator.set_used(index, true);
+        }
+
+        Ok(allocator)
+    }
+
+    /// Marks every frame that overlaps `start..start + len` as used.
+    ///
+    /// This is meant for keeping memory such as a loaded kernel or the framebuffer out of the
+    /// free pool, so it should be called before any frames are allocated. Any part of the range
+    /// outside of the managed frames is ignored.
+    pub fn reserve_range(&mut self, start: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        let frame_size = self.page_size.size();
+        let end = start.saturating_add(len);
+        let managed_end = self.base + self.frame_count * frame_size;
+        if end <= self.base || start >= managed_end {
+            return;
+        }
+
+        // Partially overlapped frames at either end are reserved, too
+        let first = (start.max(self.base) - self.base) / frame_size;
+        let last = (end.min(managed_end) - self.base + frame_size - 1) / frame_size;
+        for index in first..last {
+            self.set_used(index, true);
+        }
+    }
+
+    /// Allocates a single frame, or returns `None` if every frame is in use.
+    pub fn alloc_frame(&mut self) -> Option<PageFrame> {
+        let index = (self.next_free..self.frame_count).find(|&index| !self.is_used(index))?;
+        self.set_used(index, true);
+        self.next_free = index + 1;
+        Some(self.frame(index))
+    }
+
+    /// Allocates `count` physically contiguous frames and returns the first one.
+    ///
+    /// Returns `None` if `count` is 0 or there is no run of `count` free frames.
+    pub fn alloc_contiguous(&mut self, count: usize) -> Option<PageFrame> {
+        if count == 0 {
+            return None;
+        }
+
+        let mut run_start = self.next_free;
+        for index in self.next_free..self.frame_count {
+            if self.is_used(index) {
+                run_start = index + 1;
+                continue;
+            }
 
-}
\ No newline at end of file
+            if index + 1 - run_start == count {
+                for used in run_start..=index {
+                    self.set_used(used, true);
+                }
+                if run_start == self.next_free {
+                    self.next_free = index + 1;
+                }
+                return Some(self.frame(run_start));
+            }
+        }
+
+        None
+    }
+
+    /// Frees a frame that was returned by this allocator.
+    ///
+    /// To free frames returned by [`alloc_contiguous`](Self::alloc_contiguous), each frame needs
+    /// to be freed individually.
+    ///
+    /// # Errors
+    ///
+    /// * [`PageFrameAllocatorError::InvalidFrame`]: the frame is not managed by this allocator
+    /// * [`PageFrameAllocatorError::DoubleFree`]: the frame is already free
+    pub fn free_frame(&mut self, frame: PageFrame) -> Result<(), PageFrameAllocatorError> {
+        let offset = frame
+            .start()
+            .checked_sub(self.base)
+            .filter(|offset| offset % self.page_size.size() == 0)
+            .ok_or(PageFrameAllocatorError::InvalidFrame)?;
+        let index = offset / self.page_size.size();
+        if index >= self.frame_count {
+            return Err(PageFrameAllocatorError::InvalidFrame);
+        }
+        if !self.is_used(index) {
+            return Err(PageFrameAllocatorError::DoubleFree);
+        }
+
+        self.set_used(index, false);
+        self.next_free = self.next_free.min(index);
+        Ok(())
+    }
+
+    /// Returns the number of frames that are free.
+    pub fn free_frames(&self) -> usize {
+        (0..self.frame_count)
+            .filter(|&index| !self.is_used(index))
+            .count()
+    }
+
+    /// Returns the page size of each frame.
+    pub fn page_size(&self) -> PageSize {
+        self.page_size
+    }
+
+    /// Returns the frame at `index`.
+    fn frame(&self, index: usize) -> PageFrame {
+        let addr = self.base + index * self.page_size.size();
+        // The address is inside one of the regions from the memory map
+        PageFrame {
+            start: unsafe { PhysicalAddress::new(addr) },
+        }
+    }
+
+    /// Returns true if the frame at `index` is in use.
+    fn is_used(&self, index: usize) -> bool {
+        self.bitmap[index / 8] & (1 << (index % 8)) != 0
+    }
+
+    /// Marks the frame at `index` as used or free.
+    fn set_used(&mut self, index: usize, used: bool) {
+        if used {
+            self.bitmap[index / 8] |= 1 << (index % 8);
+        } else {
+            self.bitmap[index / 8] &= !(1 << (index % 8));
+        }
+    }
+}
+
+/// Returns `addr` rounded up to a multiple of `align`, or `None` if it overflows.
+fn align_up(addr: usize, align: usize) -> Option<usize> {
+    Some(addr.checked_add(align - 1)? & !(align - 1))
+}
+
+/// Returns `addr` rounded down to a multiple of `align`.
+fn align_down(addr: usize, align: usize) -> usize {
+    addr & !(align - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec;
+
+    const FRAME_SIZE: usize = 0x1000;
+
+    /// Returns the address of a page-aligned, leaked buffer that can fit `frames` frames.
+    fn frame_buffer(frames: usize) -> usize {
+        let buffer = vec![0u8; (frames + 1) * FRAME_SIZE].leak();
+        let addr = buffer.as_mut_ptr() as usize;
+        align_up(addr, FRAME_SIZE).unwrap()
+    }
+
+    /// Ensures that:
+    ///
+    /// * Only frames fully inside a region are free
+    /// * The bitmap is stored in the first region and its frame is reserved
+    /// * Single and contiguous frames can be allocated and freed
+    #[test]
+    fn alloc_frames() {
+        let base = frame_buffer(8);
+        // Two regions with a reserved frame between them and a partial frame at the end
+        let memory_map = [
+            (base, 3 * FRAME_SIZE),
+            (base + 4 * FRAME_SIZE, 3 * FRAME_SIZE + 0x10),
+        ];
+        let mut allocator =
+            unsafe { PageFrameAllocator::new(&memory_map, PageSize::default()) }.unwrap();
+        assert_eq!(allocator.page_size().size(), FRAME_SIZE);
+        assert_eq!(allocator.free_frames(), 5);
+
+        let frame = allocator.alloc_frame().unwrap();
+        assert_eq!(frame.start(), base + FRAME_SIZE);
+
+        // The run of 3 frames needs to skip the reserved frame
+        let run = allocator.alloc_contiguous(3).unwrap();
+        assert_eq!(run.start(), base + 4 * FRAME_SIZE);
+        assert_eq!(allocator.alloc_contiguous(2), None);
+        assert_eq!(allocator.free_frames(), 1);
+
+        let start = frame.start();
+        allocator.free_frame(frame).unwrap();
+        assert_eq!(allocator.alloc_contiguous(2).unwrap().start(), start);
+        assert_eq!(allocator.alloc_frame(), None);
+        assert_eq!(allocator.alloc_contiguous(0), None);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Frames partially overlapped by a reserved range are reserved
+    /// * Reserving ranges outside of the managed frames does nothing
+    #[test]
+    fn reserve_range() {
+        let base = frame_buffer(6);
+        let mut allocator =
+            unsafe { PageFrameAllocator::new(&[(base, 6 * FRAME_SIZE)], PageSize::default()) }
+                .unwrap();
+        assert_eq!(allocator.free_frames(), 5);
+
+        allocator.reserve_range(base + FRAME_SIZE + 0x10, FRAME_SIZE);
+        allocator.reserve_range(base + 5 * FRAME_SIZE - 1, 0x10_0000);
+        allocator.reserve_range(base - FRAME_SIZE, FRAME_SIZE);
+        allocator.reserve_range(base + 6 * FRAME_SIZE, FRAME_SIZE);
+        allocator.reserve_range(base + 3 * FRAME_SIZE, 0);
+        assert_eq!(allocator.free_frames(), 1);
+        assert_eq!(
+            allocator.alloc_frame().unwrap().start(),
+            base + 3 * FRAME_SIZE
+        );
+    }
+
+    /// Ensures that proper errors are returned.
+    #[test]
+    fn errors() {
+        let base = frame_buffer(2);
+        assert_eq!(
+            unsafe { PageFrameAllocator::new(&[(base + 1, FRAME_SIZE)], PageSize::default()) }
+                .err(),
+            Some(PageFrameAllocatorError::NoRegions)
+        );
+
+        let mut allocator =
+            unsafe { PageFrameAllocator::new(&[(base, 2 * FRAME_SIZE)], PageSize::default()) }
+                .unwrap();
+        let frame = allocator.alloc_frame().unwrap();
+        let start = frame.start();
+        allocator.free_frame(frame).unwrap();
+
+        let invalid = |addr| PageFrame {
+            start: unsafe { PhysicalAddress::new(addr) },
+        };
+        assert_eq!(
+            allocator.free_frame(invalid(start)),
+            Err(PageFrameAllocatorError::DoubleFree)
+        );
+        assert_eq!(
+            allocator.free_frame(invalid(start + 1)),
+            Err(PageFrameAllocatorError::InvalidFrame)
+        );
+        assert_eq!(
+            allocator.free_frame(invalid(start + FRAME_SIZE)),
+            Err(PageFrameAllocatorError::InvalidFrame)
+        );
+        assert_eq!(
+            allocator.free_frame(invalid(base - FRAME_SIZE)),
+            Err(PageFrameAllocatorError::InvalidFrame)
+        );
+        assert_eq!(PageSize::new(0x1001), None);
+    }
+}
@@ -1,10 +1,15 @@
 /// TODO:
 /// 
-/// * Create `PhysicalAllocator::new()`
 /// * Implement `allocate` and `free` for physical allocator
 ///     * Implement block splitting and merging
 
-use core::{mem, ptr::NonNull, slice};
+use core::{
+    alloc::{AllocError, Allocator, Layout},
+    cell::UnsafeCell,
+    mem,
+    ptr::{self, NonNull},
+    slice,
+};
 
 #[cfg(not(test))]
 use log::debug;
@@ -16,8 +21,40 @@ const REGION_HEADER_SIZE: usize = mem::size_of::<MemoryRegion>();
 const CELL_SIZE: usize = mem::size_of::<MemoryBlock>();
 const MINIMUM_REGION_SIZE: usize = REGION_HEADER_SIZE + CELL_SIZE * 4;
 
+// Region and block headers each take the place of a single cell, so every header must be exactly
+// one cell in size and alignment
+//
+// These are compile-time checks, so it is intended that they are evaluated away when they pass
+#[allow(clippy::assertions_on_constants)]
+const _: () = {
+    assert!(CELL_SIZE == REGION_HEADER_SIZE);
+    assert!(mem::size_of::<MemoryCell>() == CELL_SIZE);
+    assert!(mem::align_of::<MemoryCell>() == CELL_SIZE);
+    assert!(mem::align_of::<MemoryRegion>() == CELL_SIZE);
+    assert!(mem::align_of::<MemoryBlock>() == CELL_SIZE);
+};
+
 const BLOCK_STATUS_FREE: u32 = 0x1;
 
+/// The most regions that [`PhysicalAllocator::regions`] returns, so that a cycle in the list of regions
+/// (which would be a bug) cannot loop forever.
+const MAX_REGIONS: usize = 4096;
+
+/// The error type returned when adding a memory region to a [`PhysicalAllocator`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PhysicalAllocatorError {
+    /// The region is too small to contain a region header, block header, and a few cells.
+    RegionTooSmall,
+    /// The region overlaps with a region that is already part of the allocator.
+    OverlappingRegion,
+    /// The region starts at a null pointer.
+    NullRegion,
+    /// The memory map did not contain any regions.
+    NoRegions,
+    /// The region extends past the end of the address space.
+    RegionOutOfBounds,
+}
+
 #[derive(Clone, Copy, Debug)]
 #[repr(align(32))]
 struct MemoryCell([u8; CELL_SIZE]);
@@ -50,7 +87,112 @@ pub struct PhysicalAllocator {
     regions: Option<NonNull<MemoryRegion>>,
 }
 
+/// A [`PhysicalAllocator`] that can be shared by reference, so that it can back standard
+/// collections such as `Vec<T, &SharedPhysicalAllocator>`.
+///
+/// [`PhysicalAllocator`] needs `&mut self` to allocate, while [`Allocator`] only gives `&self`,
+/// so the allocator is wrapped in an `UnsafeCell`. Like [`GenericSlabAllocator`], this does not
+/// lock anything and cannot be shared between threads.
+///
+/// Freeing memory is not implemented by [`PhysicalAllocator`] yet, so deallocated memory is
+/// never reused.
+///
+/// [`GenericSlabAllocator`]: super::slab_allocator::GenericSlabAllocator
+#[derive(Debug)]
+pub struct SharedPhysicalAllocator {
+    allocator: UnsafeCell<PhysicalAllocator>,
+}
+
+impl MemoryBlock {
+    /// Returns a block header with the given `status`.
+    const fn new(next: Option<NonNull<MemoryBlock>>, cell_count: usize, status: u32) -> Self {
+        Self {
+            next,
+            cell_count,
+            status,
+            _padding0: 0,
+            _padding1: 0,
+        }
+    }
+
+    /// Returns the number of cells after this block's header that must be skipped so that the cells of a
+    /// block placed there are aligned to `align`.
+    ///
+    /// The skipped cells are kept as their own free block, so the result is either 0 or large enough to fit a
+    /// block header and at least one cell.
+    fn aligned_offset(&self, align: usize) -> usize {
+        let cells_start = (self as *const MemoryBlock).wrapping_add(1) as *const u8;
+        let offset = cells_start.align_offset(align) / CELL_SIZE;
+
+        // A single skipped cell would only have room for a block header
+        if offset == 1 {
+            offset + align / CELL_SIZE
+        } else {
+            offset
+        }
+    }
+}
+
 impl MemoryRegion {
+    /// Allocates `cell_count` cells aligned to `align` from the first free block that is large enough.
+    ///
+    /// Cells that are skipped before the aligned cells, or left over after them, are kept on the free list as
+    /// their own blocks. Left over cells that cannot fit a block header and at least one cell are added to the
+    /// allocated block instead.
+    ///
+    /// # Constraints
+    ///
+    /// * `align` must be a power of two and at least `CELL_SIZE`
+    /// * `cell_count` must not be zero
+    unsafe fn allocate_aligned(&mut self, cell_count: usize, align: usize) -> Option<NonNull<u8>> {
+        // Points to the link of the free list that points to the current block
+        let mut link = &mut self.free_blocks as *mut Option<NonNull<MemoryBlock>>;
+
+        while let Some(block_ptr) = *link {
+            let block = &mut *block_ptr.as_ptr();
+            let offset = block.aligned_offset(align);
+            let available = block.cell_count;
+            if offset + cell_count > available {
+                link = &mut block.next;
+                continue;
+            }
+
+            // Cells are indexed from the block header, so the allocated block's header is at `offset`
+            let cells = block_ptr.as_ptr() as *mut MemoryCell;
+            let remaining = available - offset - cell_count;
+            let mut free_blocks = block.next.take();
+
+            // Split off any left over cells into a new free block
+            let allocated_count = if remaining >= 2 {
+                let suffix = cells.add(offset + cell_count + 1) as *mut MemoryBlock;
+                suffix.write(MemoryBlock::new(
+                    free_blocks,
+                    remaining - 1,
+                    BLOCK_STATUS_FREE,
+                ));
+                free_blocks = Some(NonNull::new_unchecked(suffix));
+                cell_count
+            } else {
+                cell_count + remaining
+            };
+
+            // Skipped cells stay in the original block, which shrinks to fit them
+            if offset > 0 {
+                block.cell_count = offset - 1;
+                block.next = free_blocks;
+                free_blocks = Some(block_ptr);
+            }
+            *link = free_blocks;
+
+            let allocated = cells.add(offset) as *mut MemoryBlock;
+            allocated.write(MemoryBlock::new(None, allocated_count, 0));
+
+            return Some(NonNull::new_unchecked(allocated.add(1) as *mut u8));
+        }
+
+        None
+    }
+
     /// Returns the first block in this region.
     unsafe fn first_block(&mut self) -> &mut MemoryBlock {
         &mut *((self as *mut MemoryRegion).add(1) as *mut MemoryBlock)
@@ -115,6 +257,20 @@ impl MemoryRegion {
         true
     }
 
+    /// Returns the number of bytes in this region's free blocks (not including the block headers).
+    fn free_bytes(&self) -> usize {
+        let mut free_bytes = 0;
+        let mut current_block = self.free_blocks;
+        while let Some(block) = current_block {
+            // This is safe as the free list only points to block headers inside of this region
+            let block = unsafe { block.as_ref() };
+            free_bytes += block.cell_count * CELL_SIZE;
+            current_block = block.next;
+        }
+
+        free_bytes
+    }
+
     /// Returns true if `other` is contiguous with this region.
     ///
     /// This means `other` is directly after/before this region.
@@ -135,10 +291,11 @@ impl MemoryRegion {
 
     /// Returns true if `other` is overlapping with this region.
     unsafe fn is_overlapping(&self, other: &MemoryRegion) -> bool {
-        let overlapping_before = (other >= self)
-            && ((self as *const MemoryRegion).add(self.size) > (other as *const MemoryRegion));
-        let overlapping_after = (self > other)
-            && ((other as *const MemoryRegion).add(other.size) > (self as *const MemoryRegion));
+        // Region sizes are in bytes, so the end of each region needs to be calculated in bytes
+        let self_ptr = self as *const MemoryRegion as *const u8;
+        let other_ptr = other as *const MemoryRegion as *const u8;
+        let overlapping_before = (other >= self) && (self_ptr.add(self.size) > other_ptr);
+        let overlapping_after = (self > other) && (other_ptr.add(other.size) > self_ptr);
 
         overlapping_before || overlapping_after
     }
@@ -200,22 +357,27 @@ impl MemoryRegion {
         true
     }
 
-    fn new(region: &mut [u8]) -> Result<&mut MemoryRegion, ()> {
-        // This method (and others) assume that a region header is the same size as a block header
-        assert!(CELL_SIZE == REGION_HEADER_SIZE);
-
+    /// Initializes a new region in the memory of `region`.
+    ///
+    /// # Errors
+    ///
+    /// * [`PhysicalAllocatorError::RegionTooSmall`]: `region` is smaller than `MINIMUM_REGION_SIZE`, or
+    /// does not fit a region header, block header, and a single cell once aligned
+    fn new(region: &mut [u8]) -> Result<&mut MemoryRegion, PhysicalAllocatorError> {
         // There needs to be enough room for a region header, block header, and a single cell,
         // even if the region is unaligned
         if region.len() < MINIMUM_REGION_SIZE {
-            return Err(());
+            return Err(PhysicalAllocatorError::RegionTooSmall);
         }
 
         // Split region in case the start/end are unaligned
         let (pre_region, region, post_region) = unsafe { region.align_to_mut::<MemoryCell>() };
-        assert!(region.len() >= 3);
+        if region.len() < 3 {
+            return Err(PhysicalAllocatorError::RegionTooSmall);
+        }
         assert!(pre_region.len() < CELL_SIZE);
         assert!(post_region.len() < CELL_SIZE);
-        debug!("{:p} {:p}", pre_region, region);
+        debug!("{pre_region:p} {region:p}");
         debug!(
             "Pre: {:?} Region: {:?} Post: {:?}",
             pre_region.len(),
@@ -241,11 +403,7 @@ impl MemoryRegion {
         cells.fill(MemoryCell([0; CELL_SIZE]));
         post_region.fill(0);
 
-        block_header.next = None;
-        block_header.cell_count = cells.len();
-        block_header.status = BLOCK_STATUS_FREE;
-        block_header._padding0 = 0;
-        block_header._padding1 = 0;
+        *block_header = MemoryBlock::new(None, cells.len(), BLOCK_STATUS_FREE);
 
         region_header.next = None;
         // Add 2 here so that the region and block headers are counted in the region size
@@ -254,7 +412,7 @@ impl MemoryRegion {
         region_header.post_size = post_region.len() as u32;
         region_header.free_blocks = unsafe { Some(NonNull::new_unchecked(block_header)) };
 
-        debug!("{:?}", region_header);
+        debug!("{region_header:?}");
 
         Ok(region_header)
     }
@@ -278,14 +436,53 @@ impl PartialOrd for MemoryRegion {
 }
 
 impl PhysicalAllocator {
+    /// Allocates at least `size` bytes of physical memory, aligned to `align`.
+    ///
+    /// Returns `None` if `size` is zero, `align` is not a power of two, `align` is less than `CELL_SIZE`, or no
+    /// free block is large enough.
+    ///
+    /// # Safety
+    ///
+    /// Every region in this allocator must still point to valid memory that is only used by this allocator.
+    pub unsafe fn allocate_aligned(&mut self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        if size == 0 || !align.is_power_of_two() || align < CELL_SIZE {
+            return None;
+        }
+
+        // Round up to a whole number of cells
+        let cell_count = size.checked_add(CELL_SIZE - 1)? / CELL_SIZE;
+
+        let mut current_region = self.regions;
+        while let Some(mut region) = current_region {
+            let region = region.as_mut();
+            if let Some(allocation) = region.allocate_aligned(cell_count, align) {
+                return Some(allocation);
+            }
+
+            current_region = region.next;
+        }
+
+        None
+    }
+
+    /// Returns the number of bytes that are free to be allocated, across every region.
+    ///
+    /// Block headers are not counted, so an allocation may not be able to use every free byte.
+    pub fn free_bytes(&self) -> usize {
+        self.iter_regions().map(MemoryRegion::free_bytes).sum()
+    }
+
     /// Insert a new region into this allocator's linked list of regions.
     ///
     /// Regions are inserted in order of address.
     ///
     /// # Errors
     ///
-    /// * `new_region` overlaps with an existing region
-    fn insert_region<'a>(&'a mut self, new_region: &'a mut MemoryRegion) -> Result<(), ()> {
+    /// * [`PhysicalAllocatorError::OverlappingRegion`]: `new_region` overlaps with an existing region
+    fn insert_region<'a>(
+        &'a mut self,
+        new_region: &'a mut MemoryRegion,
+    ) -> Result<(), PhysicalAllocatorError> {
         if self.regions.is_none() {
             self.regions = unsafe { Some(NonNull::new_unchecked(new_region)) };
             return Ok(());
@@ -294,13 +491,13 @@ impl PhysicalAllocator {
         let first_region = unsafe { self.regions.unwrap().as_mut() };
 
         if unsafe { first_region.is_overlapping(new_region) } {
-            return Err(());
+            return Err(PhysicalAllocatorError::OverlappingRegion);
         }
 
-        if unsafe {
-            MemoryRegion::insert_before(&mut (first_region as *mut MemoryRegion), new_region)
-        } {
-            self.regions = unsafe { Some(NonNull::new_unchecked(first_region)) };
+        // `insert_before` sets this to `new_region` if it becomes the first region
+        let mut first_region_ptr = first_region as *mut MemoryRegion;
+        if unsafe { MemoryRegion::insert_before(&mut first_region_ptr, new_region) } {
+            self.regions = NonNull::new(first_region_ptr);
             return Ok(());
         }
 
@@ -308,7 +505,7 @@ impl PhysicalAllocator {
         while let Some(mut region) = current_region {
             let region = unsafe { region.as_mut() };
             if unsafe { region.is_overlapping(new_region) } {
-                return Err(());
+                return Err(PhysicalAllocatorError::OverlappingRegion);
             }
 
             if unsafe { region.merge(new_region) } {
@@ -322,14 +519,129 @@ impl PhysicalAllocator {
             current_region = region.next;
         }
 
-        unimplemented!()
+        // `new_region` is known to be after the first region, and `insert_after` always succeeds on
+        // the last region, so the loop returns before reaching the end of the list
+        unreachable!("Region was not inserted after the last region")
+    }
+
+    /// Returns an iterator over every region in this allocator, in order of address.
+    fn iter_regions(&self) -> impl Iterator<Item = &MemoryRegion> + '_ {
+        let mut current_region = self.regions;
+        core::iter::from_fn(move || {
+            // This is safe as regions are only ever linked to other regions owned by this allocator
+            let region = unsafe { current_region?.as_ref() };
+            current_region = region.next;
+            Some(region)
+        })
+    }
+
+    /// Returns an iterator over the `(address, size)` of every region in this allocator, in order of address.
+    ///
+    /// The address is the start of the region's header and the size includes every header, so contiguous
+    /// regions that were merged are returned as a single region. At most `MAX_REGIONS` regions are returned.
+    pub fn regions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.iter_regions()
+            .take(MAX_REGIONS)
+            .map(|region| (region as *const MemoryRegion as usize, region.size))
+    }
+
+    /// Initializes a new physical allocator that manages every region in `memory_map`.
+    ///
+    /// Each entry of `memory_map` is the `(address, size)` of a region of free memory. Entries with a size of
+    /// zero are skipped.
+    ///
+    /// # Errors
+    ///
+    /// * [`PhysicalAllocatorError::NoRegions`]: `memory_map` does not contain any non-empty regions
+    /// * [`PhysicalAllocatorError::NullRegion`]: a region starts at address zero
+    /// * [`PhysicalAllocatorError::RegionOutOfBounds`]: a region wraps around the end of the
+    /// address space
+    /// * [`PhysicalAllocatorError::RegionTooSmall`]: a region is too small to be managed
+    /// * [`PhysicalAllocatorError::OverlappingRegion`]: two regions overlap
+    ///
+    /// # Safety
+    ///
+    /// Every region in `memory_map` must be valid memory that is not used by anything other than this
+    /// allocator, for as long as the allocator is used.
+    pub unsafe fn new(memory_map: &[(usize, usize)]) -> Result<Self, PhysicalAllocatorError> {
+        let mut allocator = Self { regions: None };
+
+        for &(addr, size) in memory_map.iter().filter(|(_, size)| *size != 0) {
+            if addr == 0 {
+                return Err(PhysicalAllocatorError::NullRegion);
+            }
+            if addr.checked_add(size).is_none() {
+                return Err(PhysicalAllocatorError::RegionOutOfBounds);
+            }
+
+            let region = slice::from_raw_parts_mut(addr as *mut u8, size);
+            let region = MemoryRegion::new(region)?;
+            allocator.insert_region(region)?;
+        }
+
+        if allocator.regions.is_none() {
+            return Err(PhysicalAllocatorError::NoRegions);
+        }
+
+        Ok(allocator)
+    }
+
+    /// Returns the total size of every region in bytes, including headers.
+    pub fn total_bytes(&self) -> usize {
+        self.iter_regions().map(|region| region.size).sum()
     }
 }
 
+impl SharedPhysicalAllocator {
+    /// Wraps `allocator` so that it can be used through a shared reference.
+    pub fn new(allocator: PhysicalAllocator) -> Self {
+        Self {
+            allocator: UnsafeCell::new(allocator),
+        }
+    }
+
+    /// Returns the number of bytes that are free to be allocated, across every region.
+    ///
+    /// See [`PhysicalAllocator::free_bytes`].
+    pub fn free_bytes(&self) -> usize {
+        // This is safe as no reference to the allocator is held outside of these methods
+        unsafe { &*self.allocator.get() }.free_bytes()
+    }
+
+    /// Returns the wrapped allocator.
+    pub fn into_inner(self) -> PhysicalAllocator {
+        self.allocator.into_inner()
+    }
+}
+
+unsafe impl Allocator for SharedPhysicalAllocator {
+    // Allocates at least `layout.size()` bytes, rounded up to a whole number of cells. The returned
+    // slice is exactly `layout.size()` bytes long. Zero-sized layouts do not use any memory.
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling = ptr::slice_from_raw_parts_mut(layout.align() as *mut u8, 0);
+            return NonNull::new(dangling).ok_or(AllocError);
+        }
+
+        // This is safe as no reference to the allocator is held outside of these methods, and
+        // `PhysicalAllocator::new` requires every region to stay valid
+        let allocator = unsafe { &mut *self.allocator.get() };
+        // Every allocation is aligned to at least a single cell
+        let align = layout.align().max(CELL_SIZE);
+        let alloc_ptr =
+            unsafe { allocator.allocate_aligned(layout.size(), align) }.ok_or(AllocError)?;
+        let allocation = ptr::slice_from_raw_parts_mut(alloc_ptr.as_ptr(), layout.size());
+        Ok(unsafe { NonNull::new_unchecked(allocation) })
+    }
+
+    // Freeing is not implemented by `PhysicalAllocator` yet, so the memory is leaked.
+    unsafe fn deallocate(&self, _alloc_ptr: NonNull<u8>, _layout: Layout) {}
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{mem, vec};
+    use std::{boxed::Box, mem, vec};
 
     #[test]
     fn insert_region() {
@@ -365,10 +677,265 @@ mod tests {
             .insert_region(region)
             .expect("Failed to insert new region");
 
+        assert_eq!(
+            allocator.insert_region(region),
+            Err(PhysicalAllocatorError::OverlappingRegion)
+        );
+
+        debug!("{allocator:?}");
+    }
+
+    #[test]
+    fn insert_ascending_regions() {
+        const REGION_SIZE: usize = 0x200;
+
+        // Leave a gap between each region so that they are not merged
+        let mut backed_region: Vec<u8> = vec![0; REGION_SIZE * 6];
+        let mut allocator = PhysicalAllocator { regions: None };
+        for chunk in backed_region.chunks_mut(REGION_SIZE).step_by(2) {
+            let region = MemoryRegion::new(chunk).expect("Failed to initialize memory region");
+            allocator
+                .insert_region(region)
+                .expect("Failed to insert new region");
+        }
+
+        // Each region is appended after the last one
+        let region_addrs: Vec<usize> = allocator
+            .regions()
+            .map(|(region_addr, _)| region_addr)
+            .collect();
+        assert_eq!(region_addrs.len(), 3);
+        assert!(region_addrs.windows(2).all(|addrs| addrs[0] < addrs[1]));
+    }
+
+    #[test]
+    fn new_from_memory_map() {
+        const REGION_SIZE: usize = 0x200;
+
+        let mut backed_regions: Vec<Vec<u8>> = (0..3).map(|_| vec![0; REGION_SIZE]).collect();
+        // Regions are listed from highest to lowest address, so that each region is inserted at the start
+        backed_regions.sort_by_key(|backed_region| backed_region.as_ptr());
+        backed_regions.reverse();
+        let mut memory_map: Vec<(usize, usize)> = backed_regions
+            .iter_mut()
+            .map(|backed_region| (backed_region.as_mut_ptr() as usize, backed_region.len()))
+            .collect();
+        // Empty regions are skipped
+        memory_map.push((0, 0));
+
+        let allocator =
+            unsafe { PhysicalAllocator::new(&memory_map).expect("Failed to create allocator") };
+
+        // Regions should be sorted by address
+        let region_addrs: Vec<usize> = allocator
+            .iter_regions()
+            .map(|region| region as *const MemoryRegion as usize)
+            .collect();
+        assert_eq!(region_addrs.len(), 3);
+        assert!(region_addrs.windows(2).all(|addrs| addrs[0] < addrs[1]));
+
+        let total_bytes: usize = allocator.iter_regions().map(|region| region.size).sum();
+        assert_eq!(allocator.total_bytes(), total_bytes);
+
+        assert_eq!(
+            unsafe { PhysicalAllocator::new(&[]).err() },
+            Some(PhysicalAllocatorError::NoRegions)
+        );
+        assert_eq!(
+            unsafe { PhysicalAllocator::new(&[(0, 0)]).err() },
+            Some(PhysicalAllocatorError::NoRegions)
+        );
+        assert_eq!(
+            unsafe { PhysicalAllocator::new(&[(0, REGION_SIZE)]).err() },
+            Some(PhysicalAllocatorError::NullRegion)
+        );
+        assert_eq!(
+            unsafe { PhysicalAllocator::new(&[(usize::MAX - 16, 64)]).err() },
+            Some(PhysicalAllocatorError::RegionOutOfBounds)
+        );
+    }
+
+    #[test]
+    fn regions() {
+        const REGION_SIZE: usize = 0x200;
+
+        let mut backed_regions: Vec<Vec<u8>> = (0..2).map(|_| vec![0; REGION_SIZE]).collect();
+        backed_regions.sort_by_key(|backed_region| backed_region.as_ptr());
+        let memory_map: Vec<(usize, usize)> = backed_regions
+            .iter_mut()
+            .map(|backed_region| (backed_region.as_mut_ptr() as usize, backed_region.len()))
+            .collect();
+
+        let allocator =
+            unsafe { PhysicalAllocator::new(&memory_map).expect("Failed to create allocator") };
+
+        // Each region starts at its first aligned byte, and has no unaligned bytes in its size
+        let regions: Vec<(usize, usize)> = allocator.regions().collect();
+        assert_eq!(regions.len(), 2);
+        for (&(addr, size), &(backed_addr, backed_size)) in regions.iter().zip(memory_map.iter()) {
+            let pre_size = (backed_addr as *const u8).align_offset(CELL_SIZE);
+            assert_eq!(addr, backed_addr + pre_size);
+            assert_eq!(addr % CELL_SIZE, 0);
+            assert!(size <= backed_size - pre_size && size > backed_size - pre_size - CELL_SIZE);
+        }
+        assert_eq!(
+            regions.iter().map(|(_, size)| size).sum::<usize>(),
+            allocator.total_bytes()
+        );
+
+        // A cycle in the list of regions stops after `MAX_REGIONS`
+        let mut last_region = allocator.regions.unwrap();
+        while let Some(next) = unsafe { last_region.as_ref().next } {
+            last_region = next;
+        }
+        unsafe { last_region.as_mut().next = allocator.regions };
+        assert_eq!(allocator.regions().count(), MAX_REGIONS);
+        unsafe { last_region.as_mut().next = None };
+    }
+
+    #[test]
+    fn shared_allocator() {
+        const REGION_SIZE: usize = 0x1000;
+
+        let mut backed_region: Vec<u8> = vec![0; REGION_SIZE];
+        let region_start = backed_region.as_mut_ptr() as usize;
+        let memory_map = [(region_start, REGION_SIZE)];
+        let allocator = SharedPhysicalAllocator::new(unsafe {
+            PhysicalAllocator::new(&memory_map).expect("Failed to create allocator")
+        });
+        let free_bytes = allocator.free_bytes();
+
+        let mut values: Vec<u64, &SharedPhysicalAllocator> = Vec::new_in(&allocator);
+        for value in 0..32 {
+            values.push(value);
+        }
+        assert!(values.iter().copied().eq(0..32));
+        let values_addr = values.as_ptr() as usize;
+        assert!(values_addr > region_start && values_addr < region_start + REGION_SIZE);
+        assert!(allocator.free_bytes() < free_bytes);
+
+        let boxed = Box::try_new_in(0xdau8, &allocator).expect("Failed to allocate");
+        assert_eq!(*boxed, 0xda);
+        assert_eq!(&*boxed as *const u8 as usize % CELL_SIZE, 0);
+
+        // Zero-sized allocations do not use any memory
+        let free_bytes = allocator.free_bytes();
+        let _unit = Box::try_new_in((), &allocator).expect("Failed to allocate");
+        assert_eq!(allocator.free_bytes(), free_bytes);
+
+        // There is not enough memory left for this
+        Box::<[u8; REGION_SIZE], _>::try_new_uninit_in(&allocator)
+            .expect_err("Should have failed to allocate");
+    }
+
+    #[test]
+    fn region_too_small() {
+        let mut backed_region: Vec<u8> = vec![0; MINIMUM_REGION_SIZE];
+
+        assert_eq!(
+            MemoryRegion::new(&mut backed_region[..MINIMUM_REGION_SIZE - 1]).err(),
+            Some(PhysicalAllocatorError::RegionTooSmall)
+        );
+        assert_eq!(
+            MemoryRegion::new(&mut backed_region[..0]).err(),
+            Some(PhysicalAllocatorError::RegionTooSmall)
+        );
+    }
+
+    #[test]
+    fn byte_counts() {
+        const REGION_SIZE: usize = 0x400;
+        const ALLOCATION_SIZE: usize = 100;
+
+        let mut backed_regions: Vec<Vec<u8>> = vec![vec![0; REGION_SIZE], vec![0; REGION_SIZE]];
+        // Regions are inserted in order of address
+        backed_regions.sort_by_key(|backed_region| backed_region.as_ptr());
+
+        let mut allocator = PhysicalAllocator { regions: None };
+        assert_eq!(allocator.total_bytes(), 0);
+        assert_eq!(allocator.free_bytes(), 0);
+
+        let mut total_bytes = 0;
+        let mut free_bytes = 0;
+        for backed_region in backed_regions.iter_mut() {
+            let region =
+                MemoryRegion::new(backed_region).expect("Failed to initialize memory region");
+            total_bytes += region.size;
+            // Only the region header and first block header are not free
+            free_bytes += region.size - CELL_SIZE * 2;
+            allocator
+                .insert_region(region)
+                .expect("Failed to insert new region");
+        }
+        assert_eq!(allocator.total_bytes(), total_bytes);
+        assert_eq!(allocator.free_bytes(), free_bytes);
+
+        unsafe {
+            allocator
+                .allocate_aligned(ALLOCATION_SIZE, CELL_SIZE)
+                .expect("Failed to allocate");
+        }
+
+        // The allocation is rounded up to whole cells, and the left over cells need a new block header
+        let rounded_size = (ALLOCATION_SIZE + CELL_SIZE - 1) / CELL_SIZE * CELL_SIZE;
+        assert_eq!(allocator.total_bytes(), total_bytes);
+        assert_eq!(
+            allocator.free_bytes(),
+            free_bytes - rounded_size - CELL_SIZE
+        );
+    }
+
+    #[test]
+    fn allocate_aligned() {
+        const PAGE_SIZE: usize = 0x1000;
+        const REGION_SIZE: usize = PAGE_SIZE * 4;
+
+        // Start the region a bit before a page boundary, so that aligned allocations need to skip some cells
+        let mut backed_region: Vec<u8> = vec![0; REGION_SIZE + PAGE_SIZE];
+        let start = backed_region.as_ptr().align_offset(PAGE_SIZE) + PAGE_SIZE - 0x200;
+        let region_start = &backed_region[start] as *const u8 as usize;
+        let region_end = region_start + REGION_SIZE - PAGE_SIZE;
+        let region = MemoryRegion::new(&mut backed_region[start..start + REGION_SIZE - PAGE_SIZE])
+            .expect("Failed to initialize memory region");
+
+        let mut allocator = PhysicalAllocator { regions: None };
         allocator
             .insert_region(region)
-            .expect_err("Should have failed to insert overlapping region");
+            .expect("Failed to insert new region");
 
-        debug!("{:?}", allocator);
+        unsafe {
+            // Invalid arguments
+            assert!(allocator.allocate_aligned(0, PAGE_SIZE).is_none());
+            assert!(allocator
+                .allocate_aligned(PAGE_SIZE, PAGE_SIZE + 1)
+                .is_none());
+            assert!(allocator
+                .allocate_aligned(PAGE_SIZE, CELL_SIZE / 2)
+                .is_none());
+
+            let page = allocator
+                .allocate_aligned(PAGE_SIZE, PAGE_SIZE)
+                .expect("Failed to allocate page")
+                .as_ptr() as usize;
+            assert_eq!(page % PAGE_SIZE, 0);
+            assert!(page > region_start && page + PAGE_SIZE <= region_end);
+
+            // The cells skipped before the page are still allocatable
+            let prefix = allocator
+                .allocate_aligned(CELL_SIZE, CELL_SIZE)
+                .expect("Failed to allocate from prefix")
+                .as_ptr() as usize;
+            assert!(prefix > region_start && prefix < page);
+
+            // The cells after the page are still allocatable. This is too large to fit in the prefix
+            let suffix = allocator
+                .allocate_aligned(PAGE_SIZE / 2, CELL_SIZE)
+                .expect("Failed to allocate from suffix")
+                .as_ptr() as usize;
+            assert!(suffix >= page + PAGE_SIZE && suffix < region_end);
+
+            // There is not enough room left for another page
+            assert!(allocator.allocate_aligned(PAGE_SIZE, PAGE_SIZE).is_none());
+        }
     }
 }
@@ -0,0 +1,92 @@
+//! Checked construction of slices from raw pointers, such as buffers returned by firmware.
+
+use core::{mem, slice};
+
+/// The error type returned by [`checked_slice`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RawSliceError {
+    /// The pointer is null.
+    NullPointer,
+    /// The pointer is not aligned to the element type.
+    MisalignedPointer,
+    /// The slice would extend past the end of the address space, or is larger than `isize::MAX`
+    /// bytes.
+    Overflow,
+}
+
+/// Returns the slice of `len` elements that starts at `ptr`.
+///
+/// Unlike [`slice::from_raw_parts`], the pointer and length are checked before the slice is
+/// created.
+///
+/// # Errors
+///
+/// * [`RawSliceError::NullPointer`]: `ptr` is null
+/// * [`RawSliceError::MisalignedPointer`]: `ptr` is not aligned to `T`
+/// * [`RawSliceError::Overflow`]: the slice would wrap around the end of the address space, or is
+/// too large to be a slice
+///
+/// # Safety
+///
+/// Other than the checks above, the safety requirements of [`slice::from_raw_parts`] must be met.
+/// The memory must be readable, initialized, and not mutated for the lifetime `'a`.
+pub unsafe fn checked_slice<'a, T>(ptr: *const T, len: usize) -> Result<&'a [T], RawSliceError> {
+    if ptr.is_null() {
+        return Err(RawSliceError::NullPointer);
+    }
+    if !ptr.is_aligned() {
+        return Err(RawSliceError::MisalignedPointer);
+    }
+
+    let size = len
+        .checked_mul(mem::size_of::<T>())
+        .filter(|&size| size <= isize::MAX as usize)
+        .ok_or(RawSliceError::Overflow)?;
+    if (ptr as usize).checked_add(size).is_none() {
+        return Err(RawSliceError::Overflow);
+    }
+
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+
+    /// Ensures that:
+    ///
+    /// * Valid pointers and lengths return the same slice as `slice::from_raw_parts`
+    /// * Empty slices are allowed
+    /// * Null, misaligned and overflowing slices are rejected
+    #[test]
+    fn checked_slices() {
+        let data: [u32; 4] = [1, 2, 3, 4];
+
+        assert_eq!(
+            unsafe { checked_slice(data.as_ptr(), data.len()) },
+            Ok(&data[..])
+        );
+        assert_eq!(unsafe { checked_slice(data.as_ptr(), 0) }, Ok(&[][..]));
+
+        assert_eq!(
+            unsafe { checked_slice(ptr::null::<u32>(), 0) },
+            Err(RawSliceError::NullPointer)
+        );
+        let misaligned = data.as_ptr().cast::<u8>().wrapping_add(1).cast::<u32>();
+        assert_eq!(
+            unsafe { checked_slice(misaligned, 1) },
+            Err(RawSliceError::MisalignedPointer)
+        );
+
+        let near_end = (usize::MAX - 7) as *const u32;
+        assert_eq!(
+            unsafe { checked_slice(near_end, 4) },
+            Err(RawSliceError::Overflow)
+        );
+        assert_eq!(
+            unsafe { checked_slice(data.as_ptr(), usize::MAX / 2) },
+            Err(RawSliceError::Overflow)
+        );
+    }
+}
@@ -2,9 +2,12 @@
 
 use core::{
     alloc::{AllocError, Allocator, Layout},
-    cell::UnsafeCell,
+    array,
+    cell::{Cell, UnsafeCell},
     fmt::Debug,
+    hint, mem,
     ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, Ordering},
 };
 #[cfg(not(test))]
 use log::debug;
@@ -20,16 +23,93 @@ pub enum SlabAllocatorError {
     InvalidAlignment,
     /// The allocator's storage was too small to contain a bitmap and a single slab.
     StorageTooSmall,
+    /// The allocator already has [`MAX_SEGMENTS`] storage regions, so it cannot grow.
+    TooManySegments,
     /// The storage could not be divided into slabs; the storage's size should be divisible by
     /// the size of a single slab without any remainder.
     NonDivisibleSize,
 }
 
-// TODO: See if `slab_layout` can be implemented as a constant generic argument?
+/// The error type returned when deallocating a slab with [`SlabAllocator::try_deallocate`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DeallocError {
+    /// The pointer is not contained in any of the allocator's slabs.
+    PointerOutOfRange,
+    /// The pointer is contained in the allocator's buffer, but does not point to the start of
+    /// a slab.
+    MisalignedPointer,
+    /// The layout does not fit inside the allocator's slabs.
+    LayoutMismatch,
+    /// The slab is not currently allocated; it has either already been freed or was never
+    /// allocated.
+    DoubleFree,
+}
+
+/// The layout of every slab in a [`GenericSlabAllocator`].
+///
+/// This is implemented for [`Layout`], which is chosen at runtime, and for [`ConstLayout`],
+/// which is chosen at compile time.
+pub trait SlabLayout: Copy + Debug {
+    /// Returns the layout of a single slab.
+    fn layout(&self) -> Layout;
+}
+
+impl SlabLayout for Layout {
+    #[inline(always)]
+    fn layout(&self) -> Layout {
+        *self
+    }
+}
+
+/// A slab layout that is known at compile time, with a size of `SIZE` and an alignment of
+/// `ALIGN`.
+///
+/// As this type has no size, a [`ConstSlabAllocator`] does not need to store its slab layout,
+/// and any calculations using the slab layout can be constant-folded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConstLayout<const SIZE: usize, const ALIGN: usize>;
+
+impl<const SIZE: usize, const ALIGN: usize> ConstLayout<SIZE, ALIGN> {
+    /// The layout of a single slab.
+    ///
+    /// Fails to compile if `SIZE` and `ALIGN` do not make a valid [`Layout`].
+    pub const LAYOUT: Layout = match Layout::from_size_align(SIZE, ALIGN) {
+        Ok(layout) => layout,
+        Err(_) => panic!("Invalid slab layout"),
+    };
+}
+
+impl<const SIZE: usize, const ALIGN: usize> SlabLayout for ConstLayout<SIZE, ALIGN> {
+    #[inline(always)]
+    fn layout(&self) -> Layout {
+        Self::LAYOUT
+    }
+}
+
+/// A slab allocator with a slab layout that is chosen at runtime.
+///
+/// See [`SlabAllocator::new`] for how to initialize this allocator.
+pub type SlabAllocator = GenericSlabAllocator<Layout>;
+
+/// A slab allocator with a slab size of `SLAB_SIZE` and slab alignment of `SLAB_ALIGN`, both
+/// known at compile time.
+///
+/// See [`ConstSlabAllocator::new`] for how to initialize this allocator.
+pub type ConstSlabAllocator<const SLAB_SIZE: usize, const SLAB_ALIGN: usize> =
+    GenericSlabAllocator<ConstLayout<SLAB_SIZE, SLAB_ALIGN>>;
+
+/// The maximum number of storage regions in a single [`GenericSlabAllocator`], including the
+/// storage that it was initialized with.
+///
+/// See [`GenericSlabAllocator::grow`] for how to add storage regions.
+pub const MAX_SEGMENTS: usize = 4;
+
 // TODO: See what can be done to ensure that the allocator is not freed before its slabs are freed.
 /// A slab allocator can allocate evenly distributed memory chunks of the same size; called "slabs".
 ///
-/// Each slab has the same [`Layout`] (meaning alignment and size).
+/// Each slab has the same [`Layout`] (meaning alignment and size). The layout can either be
+/// chosen at runtime by using a [`SlabAllocator`] or at compile time by using a
+/// [`ConstSlabAllocator`].
 ///
 /// # Constraints
 ///
@@ -38,92 +118,409 @@ pub enum SlabAllocatorError {
 /// allocator.
 ///
 /// See [`SlabAllocator::new`] for an example of initializing this allocator using raw memory.
+///
+/// # Segments
+///
+/// Up to [`MAX_SEGMENTS`] storage regions can be used by a single allocator. Each region is a
+/// separate segment with its own bitmap, and slabs are numbered across every segment in the order
+/// that the segments were added.
+///
+/// # Zeroed Memory
+///
+/// Every free slab is always zeroed out; storage is zeroed when it is added to the allocator, and
+/// each slab is zeroed again when it is freed. Because of this, [`Allocator::allocate_zeroed`]
+/// does not need to clear the memory that it returns.
 #[derive(Debug)]
-pub struct SlabAllocator {
-    // `UnsafeCell<[u8]>` is used instead of `[u8]` so that `SlabAllocator::allocate()` can get
-    // a mutable reference to the bitmap and allocation slabs. Without `UnsafeCell`,
-    // `SlabAllocator::allocate()` would not be able to mutate its own storage because it does
-    // not have a mutable reference to itself (`&mut self`).
-    //
-    // `NonNull<UnsafeCell>` is used instead of `&UnsafeCell` so that this allocator does not
-    // need any lifetime annotations.
-    allocated_storage: NonNull<UnsafeCell<[u8]>>,
-    slab_layout: Layout,
+pub struct GenericSlabAllocator<L: SlabLayout> {
+    /// The first segment always exists. Any further segments are added in order by
+    /// [`Self::grow`], so there is never a gap between two segments.
+    segments: [Option<SlabSegment<L>>; MAX_SEGMENTS],
+    /// The number of slabs that are allocated, counting each slab of a contiguous run.
+    ///
+    /// This mirrors the bitmaps, but is kept outside of the storage so that it can still be
+    /// checked after the storage has been freed.
+    outstanding: Cell<usize>,
 }
 
 // Since it uses interior mutability without any locking mechanism, this slab allocator should
 // not be shared between multiple threads.
-impl !Send for SlabAllocator {}
-impl !Sync for SlabAllocator {}
+impl<L: SlabLayout> !Send for GenericSlabAllocator<L> {}
+impl<L: SlabLayout> !Sync for GenericSlabAllocator<L> {}
 
-impl SlabAllocator {
-    /// Returns the bitmap used for keeping track of free slabs.
-    fn bitmap(&self) -> &[u8] {
-        unsafe { &self.storage()[self.buffer_size()..] }
+impl<L: SlabLayout> GenericSlabAllocator<L> {
+    /// Allocates a single slab, only if `layout` exactly matches this allocator's slab layout.
+    ///
+    /// Unlike [`Allocator::allocate`], this does not allow allocations that are smaller than a
+    /// slab.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AllocError`] if:
+    ///
+    /// * `layout` does not match this slab allocator's slab layout; `(layout != self.layout())`
+    /// * There are no free slabs
+    pub fn allocate_exact(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if self.layout() != layout {
+            return Err(AllocError);
+        }
+
+        self.allocate(layout)
+    }
+
+    /// Allocates `count` adjacent slabs as a single slice of `count * slab_size` bytes.
+    ///
+    /// The first run of `count` free slabs is used. A run may span multiple bytes of the bitmap,
+    /// but it never includes the masked bits after the last usable slab, and it never spans
+    /// multiple segments.
+    ///
+    /// The returned slice must be freed using [`Self::deallocate_contiguous`] with the same
+    /// `count`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AllocError`] if:
+    ///
+    /// * `count` is zero
+    /// * There are not `count` adjacent free slabs
+    pub fn allocate_contiguous(&self, count: usize) -> Result<NonNull<[u8]>, AllocError> {
+        if count == 0 {
+            return Err(AllocError);
+        }
+
+        let run = self
+            .segments()
+            .find_map(|segment| segment.allocate_run(count))
+            .ok_or(AllocError)?;
+        self.outstanding.set(self.outstanding.get() + count);
+        Ok(run)
     }
 
-    /// Returns the mutable bitmap used for keeping track of free slabs.
-    fn bitmap_mut(&self) -> &mut [u8] {
-        unsafe { &mut self.storage_mut()[self.buffer_size()..] }
+    /// Returns the number of slabs that are not currently allocated.
+    pub fn available(&self) -> usize {
+        self.capacity() - self.used()
     }
 
-    /// Returns the number of usable bits in the bitmap.
+    /// Returns the total number of slabs controlled by this allocator.
+    pub fn capacity(&self) -> usize {
+        self.segments().map(SlabSegment::capacity).sum()
+    }
+
+    /// Finds the first free slab, marks it as allocated, and returns its index.
     ///
-    /// Each usable bit corresponds to a single slab in the allocator's buffer. Unusable bits
-    /// do not have any corresponding slab in the buffer and cannot be used for allocation.
+    /// Earlier segments are always used before later ones.
+    fn claim_slab(&self) -> Option<usize> {
+        let mut first_index = 0;
+        for segment in self.segments() {
+            if let Some(slab_index) = segment.claim_slab() {
+                return Some(first_index + slab_index);
+            }
+            first_index += segment.capacity();
+        }
+
+        // No memory is available
+        None
+    }
+
+    /// Returns true if `alloc_ptr` points to the start of one of this allocator's slabs.
     ///
-    /// All bits after the last usable bit are marked with a `1` on initialization; signifying
-    /// that they have no corresponding usable slab.
-    fn bitmap_bits(&self) -> usize {
-        self.buffer_size() / self.slab_layout.size()
+    /// This does not check whether the slab is currently allocated. It can be used to find which
+    /// of multiple allocators a pointer should be deallocated with.
+    pub fn contains(&self, alloc_ptr: NonNull<u8>) -> bool {
+        self.slab_index(alloc_ptr).is_ok()
     }
 
-    /// Returns the size of the bitmap in bytes.
+    /// Returns the segment that this allocator was initialized with.
+    fn first_segment(&self) -> &SlabSegment<L> {
+        // The first segment is always initialized
+        self.segments[0].as_ref().unwrap()
+    }
+
+    /// Returns true if an allocation using `layout` fits inside a single slab.
+    fn fits(&self, layout: Layout) -> bool {
+        let slab_layout = self.layout();
+        layout.size() <= slab_layout.size() && layout.align() <= slab_layout.align()
+    }
+
+    /// Adds `extra_storage` to this allocator as a new segment with its own bitmap.
     ///
-    /// This calculation includes any unusable bits.
-    fn bitmap_size(&self) -> usize {
-        let slab_count = unsafe { self.storage().len() / self.slab_layout.size() };
+    /// The new slabs are only used once every slab in the existing segments is allocated. This is
+    /// unrelated to [`Allocator::grow`], which grows a single allocation. Since method call syntax
+    /// resolves to [`Allocator::grow`] when it is in scope, call this as
+    /// `GenericSlabAllocator::grow(&mut allocator, extra_storage)`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`SlabAllocator::new`] for `extra_storage`, using this
+    /// allocator's slab layout. Also returns [`SlabAllocatorError::TooManySegments`] if this
+    /// allocator already has [`MAX_SEGMENTS`] segments.
+    ///
+    /// # Safety
+    ///
+    /// `extra_storage` must not be used by anything other than this allocator for as long as the
+    /// allocator, or any of its slabs, are in use.
+    pub unsafe fn grow(&mut self, extra_storage: &mut [u8]) -> Result<(), SlabAllocatorError> {
+        let slab_layout = self.first_segment().slab_layout;
+        let free_segment = self
+            .segments
+            .iter_mut()
+            .find(|segment| segment.is_none())
+            .ok_or(SlabAllocatorError::TooManySegments)?;
+
+        *free_segment = Some(SlabSegment::init(extra_storage, slab_layout)?);
+        Ok(())
+    }
 
-        const BITS: usize = u8::BITS as usize;
-        let bitmap_size = slab_count / BITS;
-        // If the slab count is not divisible by `8` without a remainder, then an extra byte is added
-        // to the bitmap's size to account for the remaining bits.
-        if slab_count % BITS != 0 {
-            bitmap_size + 1
-        } else {
-            bitmap_size
+    /// Initializes a new slab allocator backed by `storage`, with each slab having the same
+    /// `slab_layout`.
+    ///
+    /// See [`SlabAllocator::new`] for more details.
+    unsafe fn init(storage: &mut [u8], slab_layout: L) -> Result<Self, SlabAllocatorError> {
+        let mut segments = array::from_fn(|_| None);
+        segments[0] = Some(SlabSegment::init(storage, slab_layout)?);
+
+        Ok(Self {
+            segments,
+            outstanding: Cell::new(0),
+        })
+    }
+
+    /// Frees `count` adjacent slabs that were allocated by [`Self::allocate_contiguous`].
+    ///
+    /// The allocator is left unchanged when an error is returned.
+    ///
+    /// # Errors
+    ///
+    /// * [`DeallocError::PointerOutOfRange`]: `alloc_ptr` is not contained in any slab, or the
+    /// `count` slabs starting at `alloc_ptr` go past the last slab of its segment
+    /// * [`DeallocError::MisalignedPointer`]: `alloc_ptr` does not point to the start of a slab
+    /// * [`DeallocError::DoubleFree`]: any of the slabs are not currently allocated
+    ///
+    /// # Safety
+    ///
+    /// The freed slabs are zeroed out, so they must not be used after this function returns
+    /// successfully.
+    pub unsafe fn deallocate_contiguous(
+        &self,
+        alloc_ptr: NonNull<u8>,
+        count: usize,
+    ) -> Result<(), DeallocError> {
+        let run_start = self.slab_index(alloc_ptr)?;
+        let (segment, segment_index) = self.locate(run_start);
+        if count == 0 || count > segment.capacity() - segment_index {
+            return Err(DeallocError::PointerOutOfRange);
+        }
+
+        let run = run_start..run_start + count;
+        if !run
+            .clone()
+            .all(|slab_index| self.is_slab_allocated(slab_index))
+        {
+            return Err(DeallocError::DoubleFree);
+        }
+
+        // Zero out freed memory so it cannot be leaked
+        ptr::write_bytes(alloc_ptr.as_ptr(), 0, count * self.layout().size());
+
+        for slab_index in run {
+            self.release_slab(slab_index);
         }
+        self.outstanding.set(self.outstanding.get() - count);
+
+        Ok(())
     }
 
-    /// Returns the buffer used for slab allocation.
-    fn buffer(&self) -> &[u8] {
-        unsafe { &self.storage()[..self.buffer_size()] }
+    /// Returns true if the slab at `slab_index` is currently allocated.
+    fn is_slab_allocated(&self, slab_index: usize) -> bool {
+        let (segment, slab_index) = self.locate(slab_index);
+        segment.is_slab_allocated(slab_index)
     }
 
-    /// Returns the mutable buffer used for slab allocation.
-    fn buffer_mut(&self) -> &mut [u8] {
-        unsafe { &mut self.storage_mut()[..self.buffer_size()] }
+    /// Returns an iterator over a pointer to the start of every slab that is currently allocated,
+    /// in the order of the slabs' indices.
+    ///
+    /// Only usable slabs are checked, so the masked bits at the end of each bitmap are never
+    /// returned.
+    ///
+    /// Slabs must not be allocated or freed while the iterator is in use. The bitmap is changed
+    /// through interior mutability, so doing so while it is being read is undefined behavior.
+    pub fn iter_allocated(&self) -> impl Iterator<Item = NonNull<u8>> + '_ {
+        self.segments().flat_map(|segment| {
+            (0..segment.capacity())
+                .filter(|&slab_index| segment.is_slab_allocated(slab_index))
+                .map(|slab_index| segment.slab(slab_index, 0).cast())
+        })
     }
 
-    /// Returns the size of the allocator's slab buffer in bytes.
-    fn buffer_size(&self) -> usize {
-        unsafe { self.storage().len() - self.bitmap_size() }
+    /// Returns the layout of a single slab.
+    #[inline(always)]
+    fn layout(&self) -> Layout {
+        self.first_segment().layout()
     }
 
-    /// Returns the total number of slabs controlled by this allocator.
-    pub fn capacity(&self) -> usize {
-        self.buffer_size() / self.slab_layout.size()
+    /// Returns the segment that contains the slab at `slab_index`, along with the slab's index
+    /// in that segment.
+    fn locate(&self, mut slab_index: usize) -> (&SlabSegment<L>, usize) {
+        for segment in self.segments() {
+            if slab_index < segment.capacity() {
+                return (segment, slab_index);
+            }
+            slab_index -= segment.capacity();
+        }
+
+        panic!("Slab index out of range");
+    }
+
+    /// Returns an iterator over every initialized segment, in the order they were added.
+    fn segments(&self) -> impl Iterator<Item = &SlabSegment<L>> {
+        self.segments.iter().map_while(Option::as_ref)
+    }
+
+    /// Returns the number of slabs that are currently allocated.
+    pub fn used(&self) -> usize {
+        self.segments().map(SlabSegment::used).sum()
+    }
+
+    /// Marks the slab at `slab_index` as free.
+    fn release_slab(&self, slab_index: usize) {
+        let (segment, slab_index) = self.locate(slab_index);
+        segment.release_slab(slab_index);
+    }
+
+    /// Frees every slab at once, and zeroes out every segment's storage.
+    ///
+    /// The bitmaps are restored to the same state as when each segment was added, so the full
+    /// capacity can be allocated again.
+    ///
+    /// # Safety
+    ///
+    /// Only call when no allocations are outstanding. Any allocation that is still in use becomes
+    /// dangling, and its memory can be handed out again.
+    pub unsafe fn reset(&self) {
+        for segment in self.segments() {
+            segment.reset();
+        }
+        self.outstanding.set(0);
+    }
+
+    /// Returns the first `len` bytes of the slab at `slab_index`.
+    fn slab(&self, slab_index: usize, len: usize) -> NonNull<[u8]> {
+        let (segment, slab_index) = self.locate(slab_index);
+        segment.slab(slab_index, len)
+    }
+
+    /// Returns the alignment of a single slab in bytes.
+    pub fn slab_align(&self) -> usize {
+        self.layout().align()
+    }
+
+    /// Returns the index of the slab that `alloc_ptr` points to.
+    ///
+    /// # Errors
+    ///
+    /// * [`DeallocError::PointerOutOfRange`]: `alloc_ptr` is not contained in any slab
+    /// * [`DeallocError::MisalignedPointer`]: `alloc_ptr` does not point to the start of a slab
+    fn slab_index(&self, alloc_ptr: NonNull<u8>) -> Result<usize, DeallocError> {
+        let mut first_index = 0;
+        for segment in self.segments() {
+            match segment.slab_index(alloc_ptr) {
+                Ok(slab_index) => return Ok(first_index + slab_index),
+                Err(DeallocError::PointerOutOfRange) => first_index += segment.capacity(),
+                Err(error) => return Err(error),
+            }
+        }
+
+        Err(DeallocError::PointerOutOfRange)
+    }
+
+    /// Returns the layout of a single slab.
+    pub fn slab_layout(&self) -> Layout {
+        self.layout()
     }
 
+    /// Returns the size of a single slab in bytes.
+    pub fn slab_size(&self) -> usize {
+        self.layout().size()
+    }
+
+    /// Frees the slab that `alloc_ptr` points to.
+    ///
+    /// Unlike [`Allocator::deallocate`], an invalid deallocation returns an error instead of
+    /// being ignored. The allocator is left unchanged when an error is returned.
+    ///
+    /// # Errors
+    ///
+    /// * [`DeallocError::LayoutMismatch`]: `layout` does not fit inside this allocator's slabs
+    /// * [`DeallocError::PointerOutOfRange`]: `alloc_ptr` is not contained in any slab
+    /// * [`DeallocError::MisalignedPointer`]: `alloc_ptr` does not point to the start of a slab
+    /// * [`DeallocError::DoubleFree`]: the slab is not currently allocated
+    ///
+    /// # Safety
+    ///
+    /// The freed slab is zeroed out, so it must not be used after this function returns
+    /// successfully.
+    pub unsafe fn try_deallocate(
+        &self,
+        alloc_ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), DeallocError> {
+        #[cfg(feature = "verbose-alloc")]
+        debug!("Dealloc {alloc_ptr:#?}");
+
+        if !self.fits(layout) {
+            return Err(DeallocError::LayoutMismatch);
+        }
+
+        let slab_index = self.slab_index(alloc_ptr)?;
+
+        // A slab that is already free would otherwise be silently "freed" again
+        if !self.is_slab_allocated(slab_index) {
+            return Err(DeallocError::DoubleFree);
+        }
+
+        // Zero out freed memory so it cannot be leaked
+        ptr::write_bytes(alloc_ptr.as_ptr(), 0, self.layout().size());
+
+        self.release_slab(slab_index);
+        self.outstanding.set(self.outstanding.get() - 1);
+
+        Ok(())
+    }
+}
+
+// Dropping the allocator while any of its slabs are in use usually means that its storage is
+// about to be freed underneath them. Debug builds check for this so that it is caught early.
+//
+// Only the outstanding count is checked, since the storage may already have been freed.
+#[cfg(debug_assertions)]
+impl<L: SlabLayout> Drop for GenericSlabAllocator<L> {
+    fn drop(&mut self) {
+        // Avoid a double panic, which would abort the test instead of reporting the first failure
+        #[cfg(test)]
+        if std::thread::panicking() {
+            return;
+        }
+
+        debug_assert_eq!(
+            self.outstanding.get(),
+            0,
+            "Slab allocator dropped with slabs in use"
+        );
+    }
+}
+
+impl SlabAllocator {
     /// Initializes a new slab allocator backed by `storage`, with each slab having the same `slab_layout`.
     ///
     /// # Errors
     ///
-    /// [`SlabAllocatorError::InvalidSize`]:
+    /// [`SlabAllocatorError::StorageTooSmall`]:
+    ///
+    /// * `storage.len()` is not large enough to store a bitmap and at least one slab of size
+    ///   `slab_layout.size()`
+    ///
+    /// [`SlabAllocatorError::NonDivisibleSize`]:
     ///
     /// * `storage.len()` is not divisible by `slab_layout.size()`; `(storage.len() % slab_layout.size() != 0)`
-    /// * `storage.len()` is not large enough to store two slabs of size `slab_layout.size()`;
-    ///   `(storage.len() < slab_layout.size() * 2)`
     ///
     /// [`SlabAllocatorError::InvalidAlignment`]:
     ///
@@ -171,132 +568,600 @@ impl SlabAllocator {
         storage: &mut [u8],
         slab_layout: Layout,
     ) -> Result<SlabAllocator, SlabAllocatorError> {
-        let layout_size = slab_layout.size();
+        SlabAllocator::init(storage, slab_layout)
+    }
+}
+
+impl<const SLAB_SIZE: usize, const SLAB_ALIGN: usize> ConstSlabAllocator<SLAB_SIZE, SLAB_ALIGN> {
+    /// Initializes a new slab allocator backed by `storage`, with each slab having a size of
+    /// `SLAB_SIZE` and an alignment of `SLAB_ALIGN`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`SlabAllocator::new`], using `SLAB_SIZE` and `SLAB_ALIGN` as
+    /// the slab layout.
+    ///
+    /// # Safety
+    ///
+    /// `storage` must not be used by anything other than this allocator for as long as the
+    /// allocator, or any of its slabs, are in use.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::{vec, vec::Vec};
+    /// # use caliga_bootloader::developing_modules::slab_allocator::ConstSlabAllocator;
+    /// const MEMORY_SIZE: usize = 0x1000;
+    /// let mut backed_memory: Vec<u8> = vec![0; MEMORY_SIZE];
+    /// let slab_allocator = unsafe {
+    ///     ConstSlabAllocator::<8, 1>::new(&mut backed_memory[..])
+    ///         .expect("Failed to initialize slab allocator")
+    /// };
+    /// ```
+    pub unsafe fn new(storage: &mut [u8]) -> Result<Self, SlabAllocatorError> {
+        Self::init(storage, ConstLayout)
+    }
+}
+
+unsafe impl<L: SlabLayout> Allocator for GenericSlabAllocator<L> {
+    // Allocates a single slab. The returned slice is exactly `layout.size()` bytes long, which
+    // may be smaller than a slab.
+    //
+    // Returns [`AllocError`] if:
+    //
+    // * `layout` does not fit inside a slab; `(layout.size() > self.layout().size())` or
+    //   `(layout.align() > self.layout().align())`
+    // * There are no free slabs
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if !self.fits(layout) {
+            return Err(AllocError);
+        }
+
+        let slab_index = self.claim_slab().ok_or(AllocError)?;
+        self.outstanding.set(self.outstanding.get() + 1);
+        let slab = self.slab(slab_index, layout.size());
+        #[cfg(feature = "verbose-alloc")]
+        debug!("Alloc {:#?}", slab.as_ptr());
+        Ok(slab)
+    }
+
+    // Free slabs are always zeroed out, so a newly allocated slab does not need to be cleared.
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocate(layout)
+    }
+
+    // # Safety
+    //
+    // This function has certain constraints around its inputs that need to be followed:
+    //
+    // * `alloc_ptr` needs to point to a valid slab contained in this allocator's buffer
+    // * `layout` needs to be the same layout that the slab was allocated with
+    //
+    // Invalid deallocations are ignored; see [`SlabAllocator::try_deallocate`] for the errors
+    // that can be detected.
+    unsafe fn deallocate(&self, alloc_ptr: NonNull<u8>, layout: Layout) {
+        let _ = self.try_deallocate(alloc_ptr, layout);
+    }
+}
+
+/// A single storage region of a [`GenericSlabAllocator`].
+///
+/// The storage contains the segment's slabs, followed by a bitmap of which slabs are allocated.
+///
+/// # Aliasing
+///
+/// Slabs can be read and written by their owners at any time, even from other threads when used
+/// by a [`SyncSlabAllocator`]. Because of this, a reference to the whole storage, the whole
+/// buffer, or the whole bitmap is never created. Raw pointers are used instead, and each access
+/// only touches the bitmap byte or word, or the slabs, that it needs.
+#[derive(Debug)]
+struct SlabSegment<L: SlabLayout> {
+    // `UnsafeCell<[u8]>` is used instead of `[u8]` so that `SlabAllocator::allocate()` can write
+    // to the bitmap and allocation slabs. Without `UnsafeCell`, `SlabAllocator::allocate()`
+    // would not be able to mutate its own storage because it does not have a mutable reference
+    // to itself (`&mut self`).
+    //
+    // `NonNull<UnsafeCell>` is used instead of `&UnsafeCell` so that this allocator does not
+    // need any lifetime annotations.
+    allocated_storage: NonNull<UnsafeCell<[u8]>>,
+    slab_layout: L,
+}
+
+impl<L: SlabLayout> SlabSegment<L> {
+    /// Allocates the first run of `count` free slabs in this segment as a single slice.
+    ///
+    /// Only usable slabs are scanned, so the masked bits can never be part of a run.
+    fn allocate_run(&self, count: usize) -> Option<NonNull<[u8]>> {
+        let mut run_start = 0;
+        let mut run_len = 0;
+        for slab_index in 0..self.capacity() {
+            if self.is_slab_allocated(slab_index) {
+                run_start = slab_index + 1;
+                run_len = 0;
+                continue;
+            }
+
+            run_len += 1;
+            if run_len == count {
+                for slab_index in run_start..run_start + count {
+                    self.occupy_slab(slab_index);
+                }
+
+                let slab_size = self.layout().size();
+                let run = unsafe { self.buffer_ptr().add(run_start * slab_size) };
+                let run = ptr::slice_from_raw_parts_mut(run, count * slab_size);
+                // The storage is never null
+                return Some(unsafe { NonNull::new_unchecked(run) });
+            }
+        }
+
+        // No run of free slabs is long enough
+        None
+    }
+
+    /// Returns a pointer to the byte of the bitmap at `byte_idx`.
+    fn bitmap_byte(&self, byte_idx: usize) -> *mut u8 {
+        debug_assert!(byte_idx < self.bitmap_size());
+        unsafe { self.bitmap_ptr().add(byte_idx) }
+    }
+
+    /// Returns a pointer to the start of the bitmap used for keeping track of free slabs.
+    fn bitmap_ptr(&self) -> *mut u8 {
+        unsafe { self.storage_ptr().add(self.buffer_size()) }
+    }
+
+    /// Returns the number of usable bits in the bitmap.
+    ///
+    /// Each usable bit corresponds to a single slab in the allocator's buffer. Unusable bits
+    /// do not have any corresponding slab in the buffer and cannot be used for allocation.
+    ///
+    /// All bits after the last usable bit are marked with a `1` on initialization; signifying
+    /// that they have no corresponding usable slab.
+    fn bitmap_bits(&self) -> usize {
+        self.capacity()
+    }
+
+    /// Returns the size of the bitmap in bytes.
+    ///
+    /// This calculation includes any unusable bits.
+    fn bitmap_size(&self) -> usize {
+        let slab_count = self.capacity();
+
+        const BITS: usize = u8::BITS as usize;
+        let bitmap_size = slab_count / BITS;
+        // If the slab count is not divisible by `8` without a remainder, then an extra byte is added
+        // to the bitmap's size to account for the remaining bits.
+        if slab_count % BITS != 0 {
+            bitmap_size + 1
+        } else {
+            bitmap_size
+        }
+    }
+
+    /// Returns a pointer to the start of the buffer used for slab allocation.
+    fn buffer_ptr(&self) -> *mut u8 {
+        self.storage_ptr()
+    }
+
+    /// Returns the size of the allocator's slab buffer in bytes.
+    ///
+    /// This can include a few bytes after the last slab that are too small to be another slab.
+    fn buffer_size(&self) -> usize {
+        self.storage_size() - self.bitmap_size()
+    }
+
+    /// Returns the number of slabs in this segment.
+    fn capacity(&self) -> usize {
+        Self::slab_count(self.storage_size(), self.layout().size())
+    }
+
+    /// Finds the first free slab, marks it as allocated, and returns its index.
+    ///
+    /// The bitmap is scanned one `usize` word at a time. Only the unaligned bytes at the start
+    /// and end of the bitmap are scanned one byte at a time.
+    ///
+    /// The masked bits after the last usable bit are always set, so they are never claimed.
+    fn claim_slab(&self) -> Option<usize> {
+        const BITS: usize = u8::BITS as usize;
+        const WORD_SIZE: usize = mem::size_of::<usize>();
+
+        // The bitmap is split into unaligned bytes at the start and end, with aligned words
+        // between them
+        let bitmap = self.bitmap_ptr();
+        let bitmap_size = self.bitmap_size();
+        let words_start = bitmap.align_offset(WORD_SIZE).min(bitmap_size);
+        let words_end = words_start + (bitmap_size - words_start) / WORD_SIZE * WORD_SIZE;
+
+        for byte_idx in 0..words_start {
+            if let Some(slab_index) = self.claim_slab_in_byte(byte_idx) {
+                return Some(slab_index);
+            }
+        }
+
+        for byte_idx in (words_start..words_end).step_by(WORD_SIZE) {
+            // Any bit pattern is a valid `usize`, and this word was just aligned
+            let word = unsafe { bitmap.add(byte_idx).cast::<usize>() };
+            // The words are converted from little-endian so that their bit order matches the
+            // bit order of the individual bitmap bytes
+            let bitmap_word = usize::from_le(unsafe { word.read() });
+            if bitmap_word < usize::MAX {
+                let slab_bit = bitmap_word.trailing_ones() as usize;
+                unsafe { word.write((bitmap_word | 1 << slab_bit).to_le()) };
+                return Some(byte_idx * BITS + slab_bit);
+            }
+        }
+
+        for byte_idx in words_end..bitmap_size {
+            if let Some(slab_index) = self.claim_slab_in_byte(byte_idx) {
+                return Some(slab_index);
+            }
+        }
+
+        // No memory is available
+        None
+    }
+
+    /// Finds the first free slab in the bitmap byte at `byte_idx`, marks it as allocated, and
+    /// returns its index.
+    fn claim_slab_in_byte(&self, byte_idx: usize) -> Option<usize> {
+        let bitmap_part = self.bitmap_byte(byte_idx);
+        let bits = unsafe { bitmap_part.read() };
+        if bits == u8::MAX {
+            return None;
+        }
+
+        let slab_bit = bits.trailing_ones() as usize;
+        unsafe { bitmap_part.write(bits | 1 << slab_bit) };
+        Some(byte_idx * u8::BITS as usize + slab_bit)
+    }
+
+    /// Initializes a new segment backed by `storage`, with each slab having the same
+    /// `slab_layout`.
+    ///
+    /// See [`SlabAllocator::new`] for the errors that can be returned.
+    unsafe fn init(storage: &mut [u8], slab_layout: L) -> Result<Self, SlabAllocatorError> {
+        let layout = slab_layout.layout();
+        let layout_size = layout.size();
         let storage_size = storage.len();
-        if storage_size < layout_size * 2 {
+        if Self::slab_count(storage_size, layout_size) == 0 {
             return Err(SlabAllocatorError::StorageTooSmall);
         }
         if storage_size % layout_size != 0 {
             return Err(SlabAllocatorError::NonDivisibleSize);
         }
-        if !storage.as_ptr().is_aligned_to(slab_layout.align()) {
+        if !storage.as_ptr().is_aligned_to(layout.align()) {
             return Err(SlabAllocatorError::InvalidAlignment);
         }
 
-        storage.fill(0);
+        let segment = Self {
+            allocated_storage: NonNull::new(storage as *mut [u8] as *mut UnsafeCell<[u8]>).unwrap(),
+            slab_layout,
+        };
+        segment.reset();
+
+        debug!(
+            "{:#?}, storage_size: {:?}, slab_count: {:#?}, buffer_size: {:#?}, bitmap_size: {:#?}",
+            segment,
+            segment.storage_size(),
+            segment.bitmap_bits(),
+            segment.buffer_size(),
+            segment.bitmap_size()
+        );
+
+        Ok(segment)
+    }
+
+    /// Returns true if the slab at `slab_index` is currently allocated.
+    fn is_slab_allocated(&self, slab_index: usize) -> bool {
+        let byte_idx = slab_index / u8::BITS as usize;
+        let bit_idx = slab_index % u8::BITS as usize;
+        let bitmap_part = unsafe { self.bitmap_byte(byte_idx).read() };
+        bitmap_part & (1 << bit_idx) != 0
+    }
+
+    /// Returns the layout of a single slab.
+    #[inline(always)]
+    fn layout(&self) -> Layout {
+        self.slab_layout.layout()
+    }
+
+    /// Marks the slab at `slab_index` as allocated.
+    fn occupy_slab(&self, slab_index: usize) {
+        let byte_idx = slab_index / u8::BITS as usize;
+        let bit_idx = slab_index % u8::BITS as usize;
+        unsafe { *self.bitmap_byte(byte_idx) |= 1 << bit_idx };
+    }
+
+    /// Returns the number of slabs that are currently allocated.
+    ///
+    /// Only the usable bits of the bitmap are counted; the masked bits after the last usable bit
+    /// are never counted as allocated.
+    fn used(&self) -> usize {
+        const BITS: usize = u8::BITS as usize;
+        let usable_bits = self.bitmap_bits();
+        let bitmap_part = |byte_idx| unsafe { self.bitmap_byte(byte_idx).read() };
+
+        // Count every byte that only contains usable bits
+        let full_bytes = usable_bits / BITS;
+        let mut used: usize = (0..full_bytes)
+            .map(|byte_idx| bitmap_part(byte_idx).count_ones() as usize)
+            .sum();
+
+        // Count the usable bits of the first partially-unusable byte
+        let remaining_bits = usable_bits % BITS;
+        if remaining_bits != 0 {
+            let usable_mask = !(u8::MAX << remaining_bits);
+            used += (bitmap_part(full_bytes) & usable_mask).count_ones() as usize;
+        }
+
+        used
+    }
+
+    /// Marks the slab at `slab_index` as free.
+    fn release_slab(&self, slab_index: usize) {
+        let byte_idx = slab_index / u8::BITS as usize;
+        let bit_idx = slab_index % u8::BITS as usize;
+
+        // Zero out part of bitmap to indicate that the slab is free
+        unsafe { *self.bitmap_byte(byte_idx) &= !(1 << bit_idx) };
+    }
+
+    /// Zeroes out the whole storage, then masks the bits after the last usable bit of the bitmap.
+    ///
+    /// This leaves every slab free.
+    fn reset(&self) {
+        unsafe { ptr::write_bytes(self.storage_ptr(), 0, self.storage_size()) };
+
+        const U8_MAX: u8 = u8::MAX;
+        let slab_count = self.capacity();
+        let unmasked_bits_count = self.bitmap_bits() % u8::BITS as usize;
+        let mut masked_bytes_start = slab_count / u8::BITS as usize;
+
+        // Mask the first partially-unusable byte of the bitmap
+        if unmasked_bits_count != 0 {
+            // Part of this byte might still have usable bits, so `u8::MAX` needs
+            // to be shifted to unset those usable bits.
+            unsafe {
+                self.bitmap_byte(masked_bytes_start)
+                    .write(U8_MAX << unmasked_bits_count)
+            };
+            masked_bytes_start += 1;
+        }
+
+        // Mask any further unusable bits
+        for byte_idx in masked_bytes_start..self.bitmap_size() {
+            unsafe { self.bitmap_byte(byte_idx).write(U8_MAX) };
+        }
+    }
+
+    /// Returns the first `len` bytes of the slab at `slab_index`.
+    fn slab(&self, slab_index: usize, len: usize) -> NonNull<[u8]> {
+        debug_assert!(slab_index < self.capacity());
+        debug_assert!(len <= self.layout().size());
+
+        let slab_start = unsafe { self.buffer_ptr().add(slab_index * self.layout().size()) };
+        // The storage is never null
+        unsafe { NonNull::new_unchecked(ptr::slice_from_raw_parts_mut(slab_start, len)) }
+    }
+
+    /// Returns the number of slabs of `slab_size` bytes that fit in `storage_size` bytes, along
+    /// with the bitmap that tracks them.
+    ///
+    /// The slab count and bitmap size depend on each other, since every slab needs a bit in the
+    /// bitmap and the bitmap takes space away from the slabs. Each slab uses `8 * slab_size + 1`
+    /// bits of storage, so the largest count that fits is `8 * storage_size / (8 * slab_size + 1)`.
+    /// Rounding the bitmap up to whole bytes never pushes it past the storage, because the total
+    /// size is a whole number of bytes that is less than `storage_size + 1`.
+    fn slab_count(storage_size: usize, slab_size: usize) -> usize {
+        const BITS: u128 = u8::BITS as u128;
+        (storage_size as u128 * BITS / (slab_size as u128 * BITS + 1)) as usize
+    }
+
+    /// Returns the index of the slab that `alloc_ptr` points to.
+    ///
+    /// # Errors
+    ///
+    /// * [`DeallocError::PointerOutOfRange`]: `alloc_ptr` is not contained in any slab
+    /// * [`DeallocError::MisalignedPointer`]: `alloc_ptr` does not point to the start of a slab
+    fn slab_index(&self, alloc_ptr: NonNull<u8>) -> Result<usize, DeallocError> {
+        let alloc_ptr = alloc_ptr.as_ptr() as *const u8;
+        let slab_size = self.layout().size();
+        let buffer_start = self.buffer_ptr() as *const u8;
+        let buffer_end = buffer_start.wrapping_add(self.capacity() * slab_size);
+        if alloc_ptr < buffer_start || alloc_ptr >= buffer_end {
+            return Err(DeallocError::PointerOutOfRange);
+        }
+
+        // This is safe because `alloc_ptr` was just checked to be inside of the buffer
+        let offset = unsafe { alloc_ptr.sub_ptr(buffer_start) };
+        if offset % slab_size != 0 {
+            return Err(DeallocError::MisalignedPointer);
+        }
+
+        Ok(offset / slab_size)
+    }
+
+    /// Returns a pointer to the start of the allocator's storage. It contains the allocator's
+    /// slabs and bitmap.
+    fn storage_ptr(&self) -> *mut u8 {
+        UnsafeCell::raw_get(self.allocated_storage.as_ptr()).cast()
+    }
+
+    /// Returns the size of the allocator's storage in bytes.
+    fn storage_size(&self) -> usize {
+        // A reference to the `UnsafeCell` does not assume anything about its contents, so it can
+        // exist while slabs are being written to
+        unsafe { mem::size_of_val(self.allocated_storage.as_ref()) }
+    }
+}
+
+/// A thread-safe wrapper around a [`GenericSlabAllocator`].
+///
+/// The wrapped allocator's bitmap is protected by a simple spinlock, so this allocator can be
+/// shared between multiple threads or CPU cores. The lock is only held while the bitmap is being
+/// read or changed, and while a freed slab is being zeroed out.
+#[derive(Debug)]
+pub struct SyncSlabAllocator<L: SlabLayout = Layout> {
+    slab_allocator: GenericSlabAllocator<L>,
+    locked: AtomicBool,
+}
+
+// The wrapped allocator's bitmap is only ever accessed while holding the lock, and each slab is
+// only accessed by its owner.
+unsafe impl<L: SlabLayout> Send for SyncSlabAllocator<L> {}
+unsafe impl<L: SlabLayout> Sync for SyncSlabAllocator<L> {}
+
+impl<L: SlabLayout> SyncSlabAllocator<L> {
+    /// Returns the total number of slabs controlled by this allocator.
+    pub fn capacity(&self) -> usize {
+        self.slab_allocator.capacity()
+    }
+
+    /// Returns true if `alloc_ptr` points to the start of one of this allocator's slabs.
+    ///
+    /// See [`GenericSlabAllocator::contains`] for more details.
+    pub fn contains(&self, alloc_ptr: NonNull<u8>) -> bool {
+        // Only the storage's bounds are read, so the lock is not needed
+        self.slab_allocator.contains(alloc_ptr)
+    }
+
+    /// Spins until the lock is acquired.
+    fn lock(&self) {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+    }
+
+    /// Wraps `slab_allocator` so that it can be shared between threads.
+    pub fn new(slab_allocator: GenericSlabAllocator<L>) -> Self {
+        Self {
+            slab_allocator,
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Frees every slab at once.
+    ///
+    /// See [`GenericSlabAllocator::reset`] for more details.
+    ///
+    /// # Safety
+    ///
+    /// Only call when no allocations are outstanding, on any thread.
+    pub unsafe fn reset(&self) {
+        self.lock();
+        self.slab_allocator.reset();
+        self.unlock();
+    }
+
+    /// Returns the alignment of a single slab in bytes.
+    pub fn slab_align(&self) -> usize {
+        self.slab_allocator.slab_align()
+    }
 
-        let slab_allocator = SlabAllocator {
-            allocated_storage: NonNull::new(storage as *mut [u8] as *mut UnsafeCell<[u8]>).unwrap(),
-            slab_layout,
-        };
+    /// Returns the layout of a single slab.
+    pub fn slab_layout(&self) -> Layout {
+        self.slab_allocator.slab_layout()
+    }
 
-        const U8_MAX: u8 = u8::MAX;
-        let slab_count = slab_allocator.capacity();
-        let unmasked_bits_count = slab_allocator.bitmap_bits() % u8::BITS as usize;
-        let masked_bytes_start = slab_count / u8::BITS as usize;
-        let bitmap = slab_allocator.bitmap_mut();
+    /// Returns the size of a single slab in bytes.
+    pub fn slab_size(&self) -> usize {
+        self.slab_allocator.slab_size()
+    }
 
-        // Mask the first partially-unusable byte of the bitmap
-        if unmasked_bits_count != 0 {
-            // Part of this byte might still have usable bits, so `u8::MAX` needs
-            // to be shifted to unset those usable bits.
-            *&mut bitmap[masked_bytes_start] = U8_MAX << unmasked_bits_count;
+    /// Frees the slab that `alloc_ptr` points to.
+    ///
+    /// See [`GenericSlabAllocator::try_deallocate`] for more details.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`GenericSlabAllocator::try_deallocate`].
+    ///
+    /// # Safety
+    ///
+    /// The freed slab is zeroed out, so it must not be used after this function returns
+    /// successfully.
+    pub unsafe fn try_deallocate(
+        &self,
+        alloc_ptr: NonNull<u8>,
+        layout: Layout,
+    ) -> Result<(), DeallocError> {
+        #[cfg(feature = "verbose-alloc")]
+        debug!("Dealloc {alloc_ptr:#?}");
+
+        let slab_allocator = &self.slab_allocator;
+        if !slab_allocator.fits(layout) {
+            return Err(DeallocError::LayoutMismatch);
         }
-
-        // Mask any further unusable bits
-        if masked_bytes_start < bitmap.len() - 1 {
-            for bitmap_part in bitmap[masked_bytes_start + 1..].iter_mut() {
-                *bitmap_part = U8_MAX;
-            }
+        let slab_index = slab_allocator.slab_index(alloc_ptr)?;
+
+        // The lock is held from the check until the slab is released, so that only one of
+        // multiple threads freeing the same slab can succeed. Otherwise, a losing thread could
+        // zero out and release the slab after it was already allocated again by another thread.
+        self.lock();
+        if !slab_allocator.is_slab_allocated(slab_index) {
+            self.unlock();
+            return Err(DeallocError::DoubleFree);
         }
 
-        debug!(
-            "{:#?}, storage_size: {:?}, slab_count: {:#?}, buffer_size: {:#?}, bitmap_size: {:#?}",
-            slab_allocator,
-            slab_allocator.storage().len(),
-            slab_allocator.bitmap_bits(),
-            slab_allocator.buffer_size(),
-            slab_allocator.bitmap_size()
-        );
+        // Zero out freed memory so it cannot be leaked
+        ptr::write_bytes(alloc_ptr.as_ptr(), 0, slab_allocator.layout().size());
+
+        slab_allocator.release_slab(slab_index);
+        let outstanding = &slab_allocator.outstanding;
+        outstanding.set(outstanding.get() - 1);
+        self.unlock();
 
-        Ok(slab_allocator)
+        Ok(())
     }
 
-    /// Returns the allocator's storage. It contains the allocator's slabs and bitmap.
-    unsafe fn storage(&self) -> &[u8] {
-        &*self.allocated_storage.as_ref().get()
+    /// Releases the lock.
+    fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
     }
 
-    /// Returns the allocator's mutable storage. It contains the allocator's slabs and bitmap.
-    unsafe fn storage_mut(&self) -> &mut [u8] {
-        &mut *self.allocated_storage.as_ref().get()
+    /// Returns the number of slabs that are currently allocated.
+    pub fn used(&self) -> usize {
+        self.lock();
+        let used = self.slab_allocator.used();
+        self.unlock();
+        used
     }
 }
 
-unsafe impl Allocator for SlabAllocator {
-    // Returns [`AllocError`] if:
-    //
-    // * `layout` does not match this slab allocator's slab layout; `(layout != self.slab_layout)`
+unsafe impl<L: SlabLayout> Allocator for SyncSlabAllocator<L> {
+    // See [`GenericSlabAllocator::allocate`].
     fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
-        if self.slab_layout != layout {
+        let slab_allocator = &self.slab_allocator;
+        if !slab_allocator.fits(layout) {
             return Err(AllocError);
         }
 
-        let bitmap = self.bitmap_mut();
-        for (i, bitmap_part) in bitmap.iter_mut().enumerate() {
-            if *bitmap_part < u8::MAX {
-                let slab_bit = (*bitmap_part).trailing_ones() as usize;
-                assert!(slab_bit < u8::BITS as usize);
-                let slab_index = i * u8::BITS as usize + slab_bit;
-
-                // Set bitmap to indicate that the memory location is now used
-                *bitmap_part |= 1 << slab_bit;
-
-                let slab_size = self.slab_layout.size();
-                let slab_start = slab_index * slab_size;
-                let slab_end = slab_start + slab_size;
-                let slab = &mut self.buffer_mut()[slab_start..slab_end];
-                debug!("Alloc {:#?}", slab.as_ptr());
-                return Ok(NonNull::new(slab).unwrap());
-            }
+        self.lock();
+        let slab_index = slab_allocator.claim_slab();
+        if slab_index.is_some() {
+            let outstanding = &slab_allocator.outstanding;
+            outstanding.set(outstanding.get() + 1);
         }
+        self.unlock();
 
-        // No memory is available
-        return Err(AllocError);
+        let slab = slab_allocator.slab(slab_index.ok_or(AllocError)?, layout.size());
+        #[cfg(feature = "verbose-alloc")]
+        debug!("Alloc {:#?}", slab.as_ptr());
+        Ok(slab)
     }
 
-    // # Safety
-    //
-    // This function has certain constraints around its inputs that need to be followed:
-    //
-    // * `alloc_ptr` needs to point to a valid slab contained in this allocator's buffer
-    // * `layout` needs to match this allocator's slab layout
-    unsafe fn deallocate(&self, alloc_ptr: NonNull<u8>, layout: Layout) {
-        debug!("Dealloc {:#?}", alloc_ptr);
-
-        // Ensure deallocation is valid
-        // TODO: Remove assertions; they could be used to accidentally or maliciously
-        //       crash the entire bootloader/kernel by using invalid deallocations
-        let alloc_ptr = alloc_ptr.as_ptr() as *const u8;
-        assert!(alloc_ptr >= self.buffer().as_ptr());
-        assert!(alloc_ptr < self.bitmap().as_ptr());
-        assert_eq!(self.slab_layout, layout);
-
-        // Calculate indices for the bit that corresponds to this memory location
-        let offset = alloc_ptr.sub_ptr(self.buffer().as_ptr());
-        let slab_index = offset / self.slab_layout.size();
-        let byte_idx = slab_index / u8::BITS as usize;
-        let bit_idx = slab_index % u8::BITS as usize;
-
-        // Ensure the index is valid
-        let bitmap = self.bitmap_mut();
-        assert!(byte_idx < bitmap.len());
-
-        // Zero out part of bitmap to indicate that the slab is free
-        bitmap[byte_idx] &= !(1 << bit_idx);
+    // See [`GenericSlabAllocator::allocate_zeroed`].
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        self.allocate(layout)
+    }
 
-        // Zero out freed memory so it cannot be leaked
-        ptr::write_bytes(alloc_ptr as *mut u8, 0, self.slab_layout.size());
+    // See [`GenericSlabAllocator::deallocate`].
+    unsafe fn deallocate(&self, alloc_ptr: NonNull<u8>, layout: Layout) {
+        let _ = self.try_deallocate(alloc_ptr, layout);
     }
 }
 
@@ -306,7 +1171,9 @@ unsafe impl Allocator for SlabAllocator {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{boxed::Box, collections::VecDeque, mem, vec, vec::Vec};
+    use std::{
+        boxed::Box, collections::VecDeque, mem, slice, sync::Barrier, thread, vec, vec::Vec,
+    };
 
     /// A `SlabAllocator` that uses a `Vec` to store its allocations
     #[allow(dead_code)]
@@ -384,8 +1251,8 @@ mod tests {
             }
 
             // Ensure allocations are set correctly
-            for i in 0..capacity {
-                assert_eq!(i as DataType, *saved_allocations[i]);
+            for (i, alloc) in saved_allocations.iter().enumerate() {
+                assert_eq!(i as DataType, **alloc);
             }
 
             // This allocation is expected to fail because there should be no more room for allocations
@@ -424,8 +1291,8 @@ mod tests {
             }
 
             // Ensure allocations are set correctly
-            for i in 0..SLAB_COUNT {
-                assert_eq!(i as DataType, *saved_allocations[i]);
+            for (i, alloc) in saved_allocations.iter().enumerate() {
+                assert_eq!(i as DataType, **alloc);
             }
 
             // Free even-indexed slabs
@@ -508,6 +1375,81 @@ mod tests {
         assert_eq!(*data, 0);
     }
 
+    /// Ensures that:
+    ///
+    /// * The entire capacity of a large allocator can be filled
+    /// * Slabs are allocated in order when none have been freed
+    /// * The masked bits at the end of the bitmap are never allocated
+    #[test]
+    fn large_allocator() {
+        type DataType = u8;
+        const STORAGE_SIZE: usize = 0x10000;
+        let alloc = init_slab_alloc::<DataType>(STORAGE_SIZE);
+        let slab_allocator = &alloc.slab_allocator;
+        let layout = alloc.layout;
+        let capacity = slab_allocator.capacity();
+        let buffer_start = slab_allocator.first_segment().buffer_ptr();
+
+        for i in 0..capacity {
+            let allocated = slab_allocator.allocate(layout).expect("Failed to allocate");
+            assert_eq!(allocated.as_ptr() as *mut u8, buffer_start.wrapping_add(i));
+        }
+        assert_eq!(slab_allocator.used(), capacity);
+
+        // Allocator should be full
+        slab_allocator
+            .allocate(layout)
+            .expect_err("Should have failed to allocate");
+
+        let first_slab = NonNull::new(buffer_start as *mut u8).unwrap();
+        unsafe { slab_allocator.deallocate_contiguous(first_slab, capacity) }
+            .expect("Failed to free slabs");
+    }
+
+    /// Ensures that:
+    ///
+    /// * A slab allocator with a compile-time layout can be used
+    /// * A compile-time layout does not take up any space in the allocator
+    /// * The slab size, alignment and layout match the compile-time layout
+    /// * Proper errors are returned for invalid storage
+    #[test]
+    fn const_layout() {
+        type DataType = u32;
+        const SIZE: usize = mem::size_of::<DataType>();
+        const ALIGN: usize = mem::align_of::<DataType>();
+        const SLAB_COUNT: usize = 16;
+        assert_eq!(
+            mem::size_of::<ConstSlabAllocator<SIZE, ALIGN>>(),
+            MAX_SEGMENTS * mem::size_of::<NonNull<UnsafeCell<[u8]>>>() + mem::size_of::<usize>()
+        );
+
+        let mut storage: Vec<u8> = vec![0; SLAB_COUNT * SIZE];
+        let slab_allocator = unsafe {
+            ConstSlabAllocator::<SIZE, ALIGN>::new(&mut storage[..])
+                .expect("Failed to create allocator")
+        };
+        assert_eq!(slab_allocator.slab_size(), SIZE);
+        assert_eq!(slab_allocator.slab_align(), ALIGN);
+        assert_eq!(slab_allocator.slab_layout(), Layout::new::<DataType>());
+        let mut saved_allocations: Vec<Box<DataType, &ConstSlabAllocator<SIZE, ALIGN>>> = vec![];
+        for i in 0..slab_allocator.capacity() {
+            let alloc =
+                Box::try_new_in(i as DataType, &slab_allocator).expect("Failed to allocate");
+            saved_allocations.push(alloc);
+        }
+        assert_eq!(slab_allocator.used(), slab_allocator.capacity());
+        Box::try_new_in(0, &slab_allocator).expect_err("Should have failed to allocate");
+        saved_allocations.clear();
+        assert_eq!(slab_allocator.used(), 0);
+
+        let mut storage: Vec<u8> = vec![0; SIZE];
+        let alloc_err = unsafe {
+            ConstSlabAllocator::<SIZE, ALIGN>::new(&mut storage[..])
+                .expect_err("Should have failed to create allocator")
+        };
+        assert_eq!(alloc_err, SlabAllocatorError::StorageTooSmall);
+    }
+
     /// Ensures that proper errors are returned for:
     ///
     /// * An invalid size
@@ -546,6 +1488,612 @@ mod tests {
         assert_eq!(alloc_err, SlabAllocatorError::InvalidAlignment);
     }
 
+    /// Ensures that:
+    ///
+    /// * `used` counts every allocated slab
+    /// * `used` and `available` are updated after slabs are freed
+    /// * The masked bits at the end of the bitmap are never counted as used
+    #[test]
+    fn used_slabs() {
+        type DataType = u8;
+        // A slab count that is not divisible by 8, so that the bitmap has masked bits
+        const SLAB_COUNT: usize = 21;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let capacity = slab_allocator.capacity();
+
+        // Nothing should be allocated yet
+        assert_eq!(slab_allocator.used(), 0);
+        assert_eq!(slab_allocator.available(), capacity);
+
+        // Fill allocator
+        let mut saved_allocations: Vec<Box<DataType, &SlabAllocator>> = vec![];
+        for i in 0..capacity {
+            let alloc = Box::try_new_in(i as DataType, slab_allocator).expect("Failed to allocate");
+            saved_allocations.push(alloc);
+        }
+        assert_eq!(slab_allocator.used(), capacity);
+        assert_eq!(slab_allocator.available(), 0);
+
+        // Free half of the allocations
+        let freed = capacity / 2;
+        saved_allocations.truncate(capacity - freed);
+        assert_eq!(slab_allocator.used(), capacity - freed);
+        assert_eq!(slab_allocator.available(), freed);
+
+        // Free the rest
+        saved_allocations.clear();
+        assert_eq!(slab_allocator.used(), 0);
+        assert_eq!(slab_allocator.available(), capacity);
+    }
+
+    /// Ensures that:
+    ///
+    /// * The bitmap only takes space away from the slabs that it needs to track
+    /// * Every slab that fits next to its bitmap can be allocated, without overlapping the bitmap
+    /// * Storage that cannot fit a bitmap and a single slab is rejected
+    #[test]
+    fn bitmap_overhead() {
+        type DataType = u8;
+        // The 9 slabs would need a 2 byte bitmap, but 8 slabs only need a 1 byte bitmap
+        const SLAB_COUNT: usize = 9;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        assert_eq!(slab_allocator.capacity(), 8);
+
+        let mut saved_allocations: Vec<Box<DataType, &SlabAllocator>> = vec![];
+        for _ in 0..slab_allocator.capacity() {
+            let alloc = Box::try_new_in(DataType::MAX, slab_allocator).expect("Failed to allocate");
+            saved_allocations.push(alloc);
+        }
+        assert_eq!(slab_allocator.used(), 8);
+        Box::try_new_in(0, slab_allocator).expect_err("Should have failed to allocate");
+        saved_allocations.clear();
+        assert_eq!(slab_allocator.used(), 0);
+
+        // Each capacity is the largest one whose slabs and bitmap fit in the storage
+        let needed =
+            |slab_count: usize, slab_size: usize| slab_count * slab_size + (slab_count + 7) / 8;
+        for slab_size in [1, 3, 8] {
+            for storage_size in (slab_size * 2..=slab_size * 80).step_by(slab_size) {
+                let mut storage: Vec<u8> = vec![0; storage_size];
+                let layout = Layout::from_size_align(slab_size, 1).unwrap();
+                let slab_allocator = unsafe {
+                    SlabAllocator::new(&mut storage[..], layout)
+                        .expect("Failed to create allocator")
+                };
+                let capacity = slab_allocator.capacity();
+                assert!(needed(capacity, slab_size) <= storage_size);
+                assert!(needed(capacity + 1, slab_size) > storage_size);
+            }
+        }
+
+        let mut storage: Vec<u8> = vec![0; mem::size_of::<DataType>()];
+        let alloc_err = unsafe {
+            SlabAllocator::new(&mut storage[..], Layout::new::<DataType>())
+                .expect_err("Should have failed to create allocator")
+        };
+        assert_eq!(alloc_err, SlabAllocatorError::StorageTooSmall);
+    }
+
+    /// Ensures that `contains`:
+    ///
+    /// * Owns a pointer to the start of the first and last slabs
+    /// * Does not own a pointer to the middle of a slab
+    /// * Does not own a pointer to the bitmap or outside of the storage
+    #[test]
+    fn contains_pointers() {
+        type DataType = u32;
+        const SLAB_COUNT: usize = 8;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let slab_size = alloc.layout.size();
+        let buffer_start = slab_allocator.first_segment().buffer_ptr();
+        let last_slab = buffer_start.wrapping_add((slab_allocator.capacity() - 1) * slab_size);
+        let bitmap_start = slab_allocator.first_segment().bitmap_ptr();
+
+        assert!(slab_allocator.contains(NonNull::new(buffer_start).unwrap()));
+        assert!(slab_allocator.contains(NonNull::new(last_slab).unwrap()));
+        assert!(!slab_allocator.contains(NonNull::new(buffer_start.wrapping_add(1)).unwrap()));
+        assert!(!slab_allocator.contains(NonNull::new(bitmap_start).unwrap()));
+        assert!(
+            !slab_allocator.contains(NonNull::new(buffer_start.wrapping_sub(slab_size)).unwrap())
+        );
+    }
+
+    /// Ensures that proper errors are returned for:
+    ///
+    /// * Freeing a slab twice
+    /// * Freeing a slab that was never allocated
+    /// * Freeing a pointer outside of the slab buffer
+    /// * Freeing a pointer that is not at the start of a slab
+    /// * Freeing with a `Layout` that does not match the allocator
+    #[test]
+    fn invalid_deallocations() {
+        type DataType = u16;
+        const SLAB_COUNT: usize = 8;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let layout = alloc.layout;
+
+        let allocated = slab_allocator
+            .allocate(layout)
+            .expect("Failed to allocate")
+            .cast::<u8>();
+        let unallocated = unsafe { NonNull::new_unchecked(allocated.as_ptr().add(layout.size())) };
+        let misaligned = unsafe { NonNull::new_unchecked(allocated.as_ptr().add(1)) };
+        let bitmap = NonNull::new(slab_allocator.first_segment().bitmap_ptr()).unwrap();
+
+        unsafe {
+            assert_eq!(
+                slab_allocator.try_deallocate(allocated, Layout::new::<u32>()),
+                Err(DeallocError::LayoutMismatch)
+            );
+            assert_eq!(
+                slab_allocator.try_deallocate(bitmap, layout),
+                Err(DeallocError::PointerOutOfRange)
+            );
+            assert_eq!(
+                slab_allocator.try_deallocate(misaligned, layout),
+                Err(DeallocError::MisalignedPointer)
+            );
+            assert_eq!(
+                slab_allocator.try_deallocate(unallocated, layout),
+                Err(DeallocError::DoubleFree)
+            );
+
+            // None of the invalid deallocations should have freed the slab
+            assert_eq!(slab_allocator.used(), 1);
+            assert_eq!(slab_allocator.try_deallocate(allocated, layout), Ok(()));
+            assert_eq!(slab_allocator.used(), 0);
+
+            assert_eq!(
+                slab_allocator.try_deallocate(allocated, layout),
+                Err(DeallocError::DoubleFree)
+            );
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * Multiple adjacent slabs can be allocated as one slice
+    /// * A run of free slabs can cross a bitmap byte boundary
+    /// * A run never includes the masked bits at the end of the bitmap
+    /// * Freeing a run frees exactly those slabs and zeroes their memory
+    #[test]
+    fn contiguous_allocations() {
+        type DataType = u32;
+        const SLAB_COUNT: usize = 12;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let layout = alloc.layout;
+        let capacity = slab_allocator.capacity();
+        let slab_size = layout.size();
+
+        assert!(slab_allocator.allocate_contiguous(0).is_err());
+        assert!(slab_allocator.allocate_contiguous(capacity + 1).is_err());
+
+        // Occupy the first 6 slabs so the next run of 4 crosses the byte boundary at slab 8
+        let head = slab_allocator
+            .allocate_contiguous(6)
+            .expect("Failed to allocate");
+        assert_eq!(head.len(), 6 * slab_size);
+        let run = slab_allocator
+            .allocate_contiguous(4)
+            .expect("Failed to allocate");
+        assert_eq!(run.len(), 4 * slab_size);
+        assert_eq!(
+            run.as_ptr().cast::<u8>(),
+            head.as_ptr().cast::<u8>().wrapping_add(6 * slab_size)
+        );
+        assert_eq!(slab_allocator.used(), 10);
+
+        // Only the usable slabs after the run are free; the masked bits are not
+        let remaining = capacity - 10;
+        assert!(slab_allocator.allocate_contiguous(remaining + 1).is_err());
+
+        unsafe {
+            run.cast::<u8>().as_ptr().write_bytes(u8::MAX, run.len());
+            let run_ptr = run.cast::<u8>();
+            assert_eq!(
+                slab_allocator.deallocate_contiguous(run_ptr, capacity),
+                Err(DeallocError::PointerOutOfRange)
+            );
+            assert_eq!(slab_allocator.deallocate_contiguous(run_ptr, 4), Ok(()));
+            assert!(run.as_ref().iter().all(|byte| *byte == 0));
+            assert_eq!(
+                slab_allocator.deallocate_contiguous(run_ptr, 4),
+                Err(DeallocError::DoubleFree)
+            );
+        }
+        assert_eq!(slab_allocator.used(), 6);
+
+        // The freed run can be allocated again, along with the rest of the usable slabs
+        let tail = slab_allocator
+            .allocate_contiguous(capacity - 6)
+            .expect("Failed to allocate");
+        assert_eq!(tail.as_ptr().cast::<u8>(), run.as_ptr().cast::<u8>());
+        assert_eq!(slab_allocator.available(), 0);
+
+        unsafe {
+            assert_eq!(slab_allocator.deallocate_contiguous(head.cast(), 6), Ok(()));
+            assert_eq!(
+                slab_allocator.deallocate_contiguous(tail.cast(), capacity - 6),
+                Ok(())
+            );
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * More storage can be added to a full allocator
+    /// * Slabs in the first segment are used before slabs in later segments
+    /// * Slabs in every segment can be found and freed
+    /// * A run of contiguous slabs never spans two segments
+    /// * Proper errors are returned for invalid extra storage
+    #[test]
+    fn grow_allocator() {
+        type DataType = u64;
+        const SLAB_COUNT: usize = 8;
+        const STORAGE_SIZE: usize = SLAB_COUNT * mem::size_of::<DataType>();
+        // The extra storage needs to outlive the allocator, and storage of `DataType` is used so
+        // that it is properly aligned
+        let mut extra_storage: Vec<DataType> = vec![0; SLAB_COUNT];
+        let mut extra_storages: Vec<Vec<DataType>> =
+            (0..MAX_SEGMENTS - 1).map(|_| vec![0; SLAB_COUNT]).collect();
+        let mut alloc = init_slab_alloc::<DataType>(STORAGE_SIZE);
+        let layout = alloc.layout;
+        let first_capacity = alloc.slab_allocator.capacity();
+        let first_slabs: Vec<NonNull<[u8]>> = (0..first_capacity)
+            .map(|_| {
+                alloc
+                    .slab_allocator
+                    .allocate(layout)
+                    .expect("Failed to allocate")
+            })
+            .collect();
+        assert!(alloc.slab_allocator.allocate(layout).is_err());
+
+        let extra_range = extra_storage.as_ptr_range();
+        unsafe {
+            let extra = slice::from_raw_parts_mut(extra_storage.as_mut_ptr().cast(), STORAGE_SIZE);
+            GenericSlabAllocator::grow(&mut alloc.slab_allocator, extra)
+                .expect("Failed to grow allocator");
+        }
+        assert_eq!(alloc.slab_allocator.capacity(), first_capacity * 2);
+        assert_eq!(alloc.slab_allocator.used(), first_capacity);
+        assert!(alloc
+            .slab_allocator
+            .allocate_contiguous(first_capacity + 1)
+            .is_err());
+
+        let slab_allocator = &alloc.slab_allocator;
+        let grown_slab = slab_allocator
+            .allocate(layout)
+            .expect("Failed to allocate")
+            .cast::<u8>();
+        assert!(extra_range.contains(&(grown_slab.as_ptr() as *const DataType)));
+        assert!(slab_allocator.contains(grown_slab));
+        unsafe {
+            let first_slab = first_slabs[0].cast::<u8>();
+            assert_eq!(slab_allocator.try_deallocate(first_slab, layout), Ok(()));
+            assert_eq!(slab_allocator.try_deallocate(grown_slab, layout), Ok(()));
+            assert_eq!(
+                slab_allocator.try_deallocate(grown_slab, layout),
+                Err(DeallocError::DoubleFree)
+            );
+            assert_eq!(
+                slab_allocator.allocate(layout).map(NonNull::cast::<u8>),
+                Ok(first_slab)
+            );
+        }
+
+        let mut misaligned_storage: Vec<DataType> = vec![0; SLAB_COUNT + 1];
+        let grow_err = unsafe {
+            let misaligned = misaligned_storage.as_mut_ptr().cast::<u8>().add(1);
+            GenericSlabAllocator::grow(
+                &mut alloc.slab_allocator,
+                slice::from_raw_parts_mut(misaligned, STORAGE_SIZE),
+            )
+            .expect_err("Should have failed to grow allocator")
+        };
+        assert_eq!(grow_err, SlabAllocatorError::InvalidAlignment);
+
+        let grow_results: Vec<Result<(), SlabAllocatorError>> = extra_storages
+            .iter_mut()
+            .map(|storage| unsafe {
+                let extra = slice::from_raw_parts_mut(storage.as_mut_ptr().cast(), STORAGE_SIZE);
+                GenericSlabAllocator::grow(&mut alloc.slab_allocator, extra)
+            })
+            .collect();
+        assert_eq!(
+            grow_results[..],
+            [Ok(()), Ok(()), Err(SlabAllocatorError::TooManySegments)]
+        );
+
+        for slab in first_slabs {
+            unsafe { alloc.slab_allocator.deallocate(slab.cast(), layout) };
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * Freed slabs are zeroed out
+    /// * `allocate_zeroed` returns zeroed memory when reusing a freed slab
+    /// * Slabs freed by a `SyncSlabAllocator` are zeroed out
+    #[test]
+    fn zeroed_allocations() {
+        type DataType = u64;
+        const SLAB_COUNT: usize = 4;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let layout = alloc.layout;
+
+        let slab = slab_allocator.allocate(layout).expect("Failed to allocate");
+        unsafe {
+            slab.cast::<u8>().as_ptr().write_bytes(u8::MAX, slab.len());
+            slab_allocator.deallocate(slab.cast(), layout);
+        }
+        let zeroed = slab_allocator
+            .allocate_zeroed(layout)
+            .expect("Failed to allocate");
+        assert_eq!(zeroed.cast::<u8>(), slab.cast::<u8>());
+        assert!(unsafe { zeroed.as_ref() }.iter().all(|byte| *byte == 0));
+        unsafe { slab_allocator.deallocate(zeroed.cast(), layout) };
+
+        let mut storage: Vec<u8> = vec![0; SLAB_COUNT * mem::size_of::<DataType>()];
+        let sync_allocator = SyncSlabAllocator::new(unsafe {
+            SlabAllocator::new(&mut storage[..], layout).expect("Failed to create allocator")
+        });
+        let boxed = Box::try_new_in(DataType::MAX, &sync_allocator).expect("Failed to allocate");
+        let boxed_ptr = &*boxed as *const DataType;
+        drop(boxed);
+        let zeroed =
+            Box::<DataType, _>::try_new_zeroed_in(&sync_allocator).expect("Failed to allocate");
+        assert_eq!(zeroed.as_ref().as_ptr(), boxed_ptr);
+        assert_eq!(unsafe { zeroed.assume_init_read() }, 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * `iter_allocated` returns the address of every allocated slab, in order
+    /// * Free slabs are skipped
+    #[test]
+    fn iter_allocated() {
+        type DataType = u64;
+        const SLAB_COUNT: usize = 8;
+        const FREED: [usize; 3] = [1, 4, 5];
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let layout = alloc.layout;
+        let capacity = slab_allocator.capacity();
+
+        assert_eq!(slab_allocator.iter_allocated().count(), 0);
+
+        let slabs: Vec<NonNull<u8>> = (0..capacity)
+            .map(|_| {
+                slab_allocator
+                    .allocate(layout)
+                    .expect("Failed to allocate")
+                    .cast()
+            })
+            .collect();
+        for slab_index in FREED {
+            unsafe { slab_allocator.deallocate(slabs[slab_index], layout) };
+        }
+
+        // Slabs are allocated in order, so the first slab is at the start of the buffer
+        let buffer_start = slabs[0].as_ptr();
+        let expected: Vec<NonNull<u8>> = (0..capacity)
+            .filter(|slab_index| !FREED.contains(slab_index))
+            .map(|slab_index| {
+                NonNull::new(buffer_start.wrapping_add(slab_index * layout.size())).unwrap()
+            })
+            .collect();
+        let allocated: Vec<NonNull<u8>> = slab_allocator.iter_allocated().collect();
+        assert_eq!(allocated, expected);
+
+        for slab in allocated {
+            unsafe { slab_allocator.deallocate(slab, layout) };
+        }
+        assert_eq!(slab_allocator.iter_allocated().count(), 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * `reset` frees every slab and zeroes out their memory
+    /// * The full capacity can be allocated again after a reset, but no more
+    #[test]
+    fn reset() {
+        type DataType = u64;
+        // Not a multiple of 8 slabs, so that the last byte of the bitmap is partially masked
+        const SLAB_COUNT: usize = 13;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+        let layout = alloc.layout;
+        let capacity = slab_allocator.capacity();
+
+        for _ in 0..2 {
+            let slabs: Vec<NonNull<[u8]>> = (0..capacity)
+                .map(|_| slab_allocator.allocate(layout).expect("Failed to allocate"))
+                .collect();
+            slab_allocator
+                .allocate(layout)
+                .expect_err("Should have failed to allocate");
+            for slab in slabs.iter() {
+                unsafe { slab.cast::<u8>().as_ptr().write_bytes(u8::MAX, slab.len()) };
+            }
+            assert_eq!(slab_allocator.used(), capacity);
+
+            unsafe { slab_allocator.reset() };
+            assert_eq!(slab_allocator.used(), 0);
+            assert_eq!(slab_allocator.capacity(), capacity);
+            assert!(slabs
+                .iter()
+                .all(|slab| unsafe { slab.as_ref() }.iter().all(|byte| *byte == 0)));
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * Dropping an allocator while a slab is still allocated fails a debug assertion
+    /// * The check does not read from storage that has already been freed
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Slab allocator dropped with slabs in use")]
+    fn drop_with_outstanding_allocations() {
+        type DataType = u64;
+        const SLAB_COUNT: usize = 8;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        alloc
+            .slab_allocator
+            .allocate(alloc.layout)
+            .expect("Failed to allocate");
+
+        let VecSlabAlloc {
+            slab_allocator,
+            storage,
+            ..
+        } = alloc;
+        drop(storage);
+        drop(slab_allocator);
+    }
+
+    /// Ensures that:
+    ///
+    /// * A `Layout` that is smaller than a slab can be allocated
+    /// * The allocated slice is exactly the size of the smaller `Layout`
+    /// * `allocate_exact` only allows the slab layout
+    #[test]
+    fn smaller_allocations() {
+        type DataType = u64;
+        const SLAB_COUNT: usize = 8;
+        let alloc = init_slab_alloc::<DataType>(SLAB_COUNT * mem::size_of::<DataType>());
+        let slab_allocator = &alloc.slab_allocator;
+
+        // A `u8` fits inside of a `u64` slab
+        let allocated = Box::try_new_in(0xdau8, slab_allocator).expect("Failed to allocate");
+        assert_eq!(*allocated, 0xda);
+        assert_eq!(slab_allocator.used(), 1);
+        drop(allocated);
+        assert_eq!(slab_allocator.used(), 0);
+
+        let small_layout = Layout::new::<u16>();
+        let allocated = slab_allocator
+            .allocate(small_layout)
+            .expect("Failed to allocate");
+        assert_eq!(allocated.len(), small_layout.size());
+        unsafe { slab_allocator.deallocate(allocated.cast::<u8>(), small_layout) };
+        assert_eq!(slab_allocator.used(), 0);
+
+        slab_allocator
+            .allocate_exact(small_layout)
+            .expect_err("Should have failed to allocate");
+        let allocated = slab_allocator
+            .allocate_exact(alloc.layout)
+            .expect("Failed to allocate");
+        assert_eq!(allocated.len(), alloc.layout.size());
+        unsafe { slab_allocator.deallocate(allocated.cast::<u8>(), alloc.layout) };
+    }
+
+    /// Ensures that:
+    ///
+    /// * Multiple threads can allocate and free slabs at the same time
+    /// * No slab is handed out to two threads at once
+    /// * All slabs are free after every thread is finished
+    #[test]
+    fn sync_allocations() {
+        type DataType = u64;
+        const THREAD_COUNT: usize = 4;
+        const HELD_ALLOCATIONS: usize = 8;
+        const ITERATIONS: usize = 1000;
+        let mut storage: Vec<u8> =
+            vec![0; (THREAD_COUNT * HELD_ALLOCATIONS + 2) * mem::size_of::<DataType>()];
+        let slab_allocator = unsafe {
+            SlabAllocator::new(&mut storage[..], Layout::new::<DataType>())
+                .expect("Failed to create allocator")
+        };
+        let slab_allocator = SyncSlabAllocator::new(slab_allocator);
+
+        thread::scope(|scope| {
+            for thread_id in 0..THREAD_COUNT {
+                let slab_allocator = &slab_allocator;
+                scope.spawn(move || {
+                    let mut saved_allocations: VecDeque<Box<DataType, &SyncSlabAllocator>> =
+                        VecDeque::new();
+                    for i in 0..ITERATIONS {
+                        let data = (thread_id * ITERATIONS + i) as DataType;
+                        let alloc =
+                            Box::try_new_in(data, slab_allocator).expect("Failed to allocate");
+                        saved_allocations.push_back(alloc);
+
+                        // Another thread writing to the same slab would change its data
+                        if saved_allocations.len() > HELD_ALLOCATIONS {
+                            let alloc = saved_allocations.pop_front().unwrap();
+                            let expected = thread_id * ITERATIONS + i - HELD_ALLOCATIONS;
+                            assert_eq!(*alloc, expected as DataType);
+                        }
+                    }
+                });
+            }
+        });
+
+        assert_eq!(slab_allocator.used(), 0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Only one of multiple threads freeing the same slab at once succeeds
+    /// * The other threads get `DoubleFree`, and the slab is only released once
+    #[test]
+    fn sync_double_free() {
+        type DataType = u64;
+        const THREAD_COUNT: usize = 4;
+        const ITERATIONS: usize = 1000;
+        const SLAB_COUNT: usize = 8;
+        let mut storage: Vec<u8> = vec![0; SLAB_COUNT * mem::size_of::<DataType>()];
+        let slab_allocator = unsafe {
+            SlabAllocator::new(&mut storage[..], Layout::new::<DataType>())
+                .expect("Failed to create allocator")
+        };
+        let slab_allocator = SyncSlabAllocator::new(slab_allocator);
+        let layout = slab_allocator.slab_layout();
+
+        for _ in 0..ITERATIONS {
+            let slab = slab_allocator
+                .allocate(layout)
+                .expect("Failed to allocate")
+                .cast::<u8>();
+            // `NonNull` is not `Send`, so the address is shared instead
+            let slab_addr = slab.addr();
+            let barrier = Barrier::new(THREAD_COUNT);
+
+            let results: Vec<Result<(), DeallocError>> = thread::scope(|scope| {
+                let threads: Vec<_> = (0..THREAD_COUNT)
+                    .map(|_| {
+                        let slab_allocator = &slab_allocator;
+                        let barrier = &barrier;
+                        scope.spawn(move || {
+                            let slab = NonNull::new(slab_addr.get() as *mut u8).unwrap();
+                            barrier.wait();
+                            unsafe { slab_allocator.try_deallocate(slab, layout) }
+                        })
+                    })
+                    .collect();
+                threads
+                    .into_iter()
+                    .map(|thread| thread.join().unwrap())
+                    .collect()
+            });
+
+            assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+            assert!(results
+                .iter()
+                .all(|result| matches!(result, Ok(()) | Err(DeallocError::DoubleFree))));
+            assert_eq!(slab_allocator.used(), 0);
+        }
+    }
+
     /// Ensures that proper errors are returned for:
     ///
     /// * Using an invalid `Layout` for an allocation
@@ -561,4 +2109,78 @@ mod tests {
         // such as allocating a float (align 4) with a u8 allocator (align 1)
         Box::try_new_in(3.14159, slab_allocator).expect_err("Should have failed to allocate");
     }
+
+    /// A xorshift PRNG, so that randomized tests are repeatable
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        /// Returns a number in `0..bound`
+        fn below(&mut self, bound: usize) -> usize {
+            (self.next() % bound as u64) as usize
+        }
+    }
+
+    /// Ensures that, over thousands of random allocations and frees:
+    ///
+    /// * `used` always matches the number of live allocations
+    /// * Allocations only fail when every slab is in use
+    /// * No two live allocations overlap, and no allocation is written over by another
+    /// * The masked bits of capacities that are not a multiple of 8 are never allocated
+    #[test]
+    fn randomized_allocations() {
+        type DataType = u64;
+        const OPERATIONS: usize = 5000;
+        const SLAB_COUNTS: [usize; 5] = [2, 13, 63, 65, 203];
+
+        let mut rng = XorShift(0x5eed_cafe_f00d_d00d);
+        for slab_count in SLAB_COUNTS {
+            let alloc = init_slab_alloc::<DataType>(slab_count * mem::size_of::<DataType>());
+            let slab_allocator = &alloc.slab_allocator;
+            let layout = alloc.layout;
+            let capacity = slab_allocator.capacity();
+
+            // Each live allocation along with the value that was written to it
+            let mut live: Vec<(NonNull<u8>, u64)> = vec![];
+            for _ in 0..OPERATIONS {
+                // Allocate more often than free, so that the allocator regularly fills up
+                if rng.below(5) < 3 {
+                    match slab_allocator.allocate(layout) {
+                        Ok(slab) => {
+                            assert!(live.len() < capacity);
+                            let slab = slab.cast::<u8>();
+                            let value = rng.next();
+                            unsafe { slab.cast::<u64>().as_ptr().write(value) };
+                            live.push((slab, value));
+                        }
+                        Err(AllocError) => assert_eq!(live.len(), capacity),
+                    }
+                } else if !live.is_empty() {
+                    let (slab, value) = live.swap_remove(rng.below(live.len()));
+                    assert_eq!(unsafe { slab.cast::<u64>().as_ptr().read() }, value);
+                    unsafe { slab_allocator.deallocate(slab, layout) };
+                }
+
+                assert_eq!(slab_allocator.used(), live.len());
+                let mut addrs: Vec<usize> =
+                    live.iter().map(|(slab, _)| slab.addr().get()).collect();
+                addrs.sort_unstable();
+                assert!(addrs
+                    .windows(2)
+                    .all(|pair| pair[1] - pair[0] >= layout.size()));
+            }
+
+            for (slab, value) in live.drain(..) {
+                assert_eq!(unsafe { slab.cast::<u64>().as_ptr().read() }, value);
+                unsafe { slab_allocator.deallocate(slab, layout) };
+            }
+            assert_eq!(slab_allocator.used(), 0);
+        }
+    }
 }
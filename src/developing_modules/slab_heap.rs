@@ -0,0 +1,287 @@
+//! A general-purpose heap built from slab allocators of different sizes.
+
+use core::{
+    alloc::{Allocator, GlobalAlloc, Layout},
+    array,
+    cell::UnsafeCell,
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+};
+
+use super::slab_allocator::{SlabAllocator, SlabAllocatorError, SyncSlabAllocator};
+
+/// The slab size of each size class. Each slab is aligned to its own size.
+pub const SIZE_CLASSES: [usize; 7] = [16, 32, 64, 128, 256, 512, 1024];
+
+/// The largest slab size, which every size class's storage is aligned to.
+const MAX_SIZE_CLASS: usize = SIZE_CLASSES[SIZE_CLASSES.len() - 1];
+
+/// A heap that can be used as a [`GlobalAlloc`].
+///
+/// Half of the heap's storage is split evenly between a [`SyncSlabAllocator`] for each of the
+/// [`SIZE_CLASSES`]. Each allocation is routed to the smallest size class that fits it, or to the
+/// next larger size class if it is full. Allocations that do not fit in any size class are taken
+/// from a bump region in the other half of the storage, which is never freed.
+///
+/// The heap must be initialized with [`GlobalSlabHeap::init`]; until then, every allocation
+/// fails.
+///
+/// # Examples
+///
+/// ```
+/// # use std::{alloc::{GlobalAlloc, Layout}, vec};
+/// # use caliga_bootloader::developing_modules::slab_heap::GlobalSlabHeap;
+/// static HEAP: GlobalSlabHeap = GlobalSlabHeap::new();
+///
+/// // This memory could come from anywhere, as long as it is never used by anything else
+/// let storage = vec![0u8; 0x10000].leak();
+/// unsafe {
+///     HEAP.init(storage).expect("Failed to initialize heap");
+///     assert!(!HEAP.alloc(Layout::new::<u64>()).is_null());
+/// }
+/// ```
+pub struct GlobalSlabHeap {
+    initialized: AtomicBool,
+    size_classes: UnsafeCell<Option<[SyncSlabAllocator; SIZE_CLASSES.len()]>>,
+    /// The address of the next free byte in the bump region.
+    bump_next: AtomicUsize,
+    bump_end: AtomicUsize,
+}
+
+// The size classes are only changed by `init`, before the heap is shared. Everything else is
+// either atomic or protected by the slab allocators' locks.
+unsafe impl Sync for GlobalSlabHeap {}
+
+impl GlobalSlabHeap {
+    /// Returns an uninitialized heap.
+    pub const fn new() -> Self {
+        Self {
+            initialized: AtomicBool::new(false),
+            size_classes: UnsafeCell::new(None),
+            bump_next: AtomicUsize::new(0),
+            bump_end: AtomicUsize::new(0),
+        }
+    }
+
+    /// Initializes the heap so that it allocates from `storage`.
+    ///
+    /// # Errors
+    ///
+    /// * [`SlabAllocatorError::StorageTooSmall`]: `storage` cannot fit two slabs of every size
+    ///   class along with their bitmaps
+    ///
+    /// # Panics
+    ///
+    /// Panics if the heap was already initialized.
+    ///
+    /// # Safety
+    ///
+    /// This must only be called once, before the heap is used by multiple threads.
+    pub unsafe fn init(&self, storage: &'static mut [u8]) -> Result<(), SlabAllocatorError> {
+        if self.initialized.load(Ordering::Acquire) {
+            panic!("Slab heap was initialized more than once");
+        }
+
+        // Every size class's storage needs to be aligned to its slab size
+        let offset = storage.as_ptr().align_offset(MAX_SIZE_CLASS);
+        let storage = storage
+            .get_mut(offset..)
+            .ok_or(SlabAllocatorError::StorageTooSmall)?;
+        let class_storage_size =
+            storage.len() / 2 / SIZE_CLASSES.len() / MAX_SIZE_CLASS * MAX_SIZE_CLASS;
+        if class_storage_size == 0 {
+            return Err(SlabAllocatorError::StorageTooSmall);
+        }
+
+        let (class_storage, bump_region) =
+            storage.split_at_mut(class_storage_size * SIZE_CLASSES.len());
+        let mut chunks = class_storage.chunks_exact_mut(class_storage_size);
+        let size_classes = array::from_fn(|index| {
+            let slab_size = SIZE_CLASSES[index];
+            let slab_layout = Layout::from_size_align(slab_size, slab_size).unwrap();
+            SlabAllocator::new(chunks.next().unwrap(), slab_layout)
+        });
+        if let Some(Err(error)) = size_classes.iter().find(|result| result.is_err()) {
+            return Err(*error);
+        }
+
+        *self.size_classes.get() =
+            Some(size_classes.map(|result| SyncSlabAllocator::new(result.unwrap())));
+        let bump_start = bump_region.as_mut_ptr() as usize;
+        self.bump_next.store(bump_start, Ordering::Relaxed);
+        self.bump_end
+            .store(bump_start + bump_region.len(), Ordering::Relaxed);
+        self.initialized.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Allocates from the bump region, or returns null if there is not enough space left.
+    fn bump_alloc(&self, layout: Layout) -> *mut u8 {
+        let end = self.bump_end.load(Ordering::Relaxed);
+        let mut next = self.bump_next.load(Ordering::Relaxed);
+        loop {
+            let Some(allocation_end) = next
+                .checked_add(layout.align() - 1)
+                .map(|start| start & !(layout.align() - 1))
+                .and_then(|start| start.checked_add(layout.size()))
+            else {
+                return ptr::null_mut();
+            };
+            if allocation_end > end {
+                return ptr::null_mut();
+            }
+
+            match self.bump_next.compare_exchange_weak(
+                next,
+                allocation_end,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return (allocation_end - layout.size()) as *mut u8,
+                Err(current) => next = current,
+            }
+        }
+    }
+
+    /// Returns the slab allocator for each size class, or `None` if the heap is uninitialized.
+    fn size_classes(&self) -> Option<&[SyncSlabAllocator; SIZE_CLASSES.len()]> {
+        if !self.initialized.load(Ordering::Acquire) {
+            return None;
+        }
+
+        unsafe { (*self.size_classes.get()).as_ref() }
+    }
+}
+
+unsafe impl GlobalAlloc for GlobalSlabHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let Some(size_classes) = self.size_classes() else {
+            return ptr::null_mut();
+        };
+
+        // Try the smallest size class that fits, followed by the larger ones
+        let fitting_classes = SIZE_CLASSES
+            .iter()
+            .zip(size_classes)
+            .skip_while(|(&slab_size, _)| layout.size() > slab_size || layout.align() > slab_size);
+        for (_, slab_allocator) in fitting_classes {
+            if let Ok(slab) = slab_allocator.allocate(layout) {
+                return slab.cast::<u8>().as_ptr();
+            }
+        }
+
+        self.bump_alloc(layout)
+    }
+
+    // Memory from the bump region is never freed.
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (Some(size_classes), Some(ptr)) = (self.size_classes(), NonNull::new(ptr)) else {
+            return;
+        };
+
+        if let Some(slab_allocator) = size_classes
+            .iter()
+            .find(|slab_allocator| slab_allocator.contains(ptr))
+        {
+            slab_allocator.deallocate(ptr, layout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{vec, vec::Vec};
+
+    const STORAGE_SIZE: usize = 0x10000;
+
+    /// Returns an initialized heap.
+    fn init_heap() -> GlobalSlabHeap {
+        let heap = GlobalSlabHeap::new();
+        let storage = vec![0u8; STORAGE_SIZE].leak();
+        unsafe { heap.init(storage) }.expect("Failed to initialize heap");
+        heap
+    }
+
+    /// Returns the index of the size class that `ptr` was allocated from.
+    fn size_class_of(heap: &GlobalSlabHeap, ptr: *mut u8) -> Option<usize> {
+        let ptr = NonNull::new(ptr).unwrap();
+        heap.size_classes()
+            .unwrap()
+            .iter()
+            .position(|slab_allocator| slab_allocator.contains(ptr))
+    }
+
+    /// Ensures that:
+    ///
+    /// * Allocations are routed to the smallest size class that fits
+    /// * Larger alignments use larger size classes
+    /// * Allocations larger than every size class use the bump region
+    /// * Freed slabs are reused
+    #[test]
+    fn route_allocations() {
+        let heap = init_heap();
+        unsafe {
+            let small = heap.alloc(Layout::from_size_align(16, 8).unwrap());
+            assert_eq!(size_class_of(&heap, small), Some(0));
+            let aligned = heap.alloc(Layout::from_size_align(16, 256).unwrap());
+            assert_eq!(size_class_of(&heap, aligned), Some(4));
+            let medium = heap.alloc(Layout::from_size_align(1000, 8).unwrap());
+            assert_eq!(size_class_of(&heap, medium), Some(6));
+
+            let large_layout = Layout::from_size_align(0x1000, 0x1000).unwrap();
+            let large = heap.alloc(large_layout);
+            assert!(!large.is_null());
+            assert!(large.is_aligned_to(0x1000));
+            assert_eq!(size_class_of(&heap, large), None);
+            heap.dealloc(large, large_layout);
+
+            heap.dealloc(small, Layout::from_size_align(16, 8).unwrap());
+            let reused = heap.alloc(Layout::from_size_align(12, 4).unwrap());
+            assert_eq!(reused, small);
+
+            heap.dealloc(reused, Layout::from_size_align(12, 4).unwrap());
+            heap.dealloc(aligned, Layout::from_size_align(16, 256).unwrap());
+            heap.dealloc(medium, Layout::from_size_align(1000, 8).unwrap());
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * Full size classes fall back to larger ones
+    /// * The bump region returns null when it runs out
+    /// * An uninitialized heap cannot allocate
+    #[test]
+    fn exhaustion() {
+        let heap = init_heap();
+        let layout = Layout::new::<u64>();
+        let mut allocations = Vec::new();
+        loop {
+            let ptr = unsafe { heap.alloc(layout) };
+            if ptr.is_null() {
+                break;
+            }
+            allocations.push(ptr);
+        }
+
+        let capacity: usize = heap
+            .size_classes()
+            .unwrap()
+            .iter()
+            .map(|slab_allocator| slab_allocator.capacity())
+            .sum();
+        assert!(allocations.len() > capacity);
+        for class in 0..SIZE_CLASSES.len() {
+            assert!(allocations
+                .iter()
+                .any(|&ptr| size_class_of(&heap, ptr) == Some(class)));
+        }
+        for ptr in allocations {
+            unsafe { heap.dealloc(ptr, layout) };
+        }
+
+        let heap = GlobalSlabHeap::new();
+        assert!(unsafe { heap.alloc(layout) }.is_null());
+    }
+}
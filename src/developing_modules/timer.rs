@@ -0,0 +1,93 @@
+//! A common interface for the counters used to measure time.
+//!
+//! See `x86_64::tsc::TscTimer` and `aarch64::generic_timer::GenericTimer` for the implementation
+//! on each architecture.
+
+/// A monotonic counter that ticks at a fixed rate.
+pub trait Timer {
+    /// Returns the current value of the counter.
+    fn now_ticks(&self) -> u64;
+
+    /// Returns the number of times that the counter ticks each second.
+    fn ticks_per_second(&self) -> u64;
+
+    /// Returns the current value of the counter in milliseconds.
+    ///
+    /// Counters usually start at zero when the CPU is reset, so this is roughly the time since boot.
+    fn now_ms(&self) -> u64 {
+        (self.now_ticks() as u128 * 1000 / self.ticks_per_second() as u128)
+            .try_into()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Busy-waits for at least `ms` milliseconds.
+    fn delay_ms(&self, ms: u64) {
+        let ticks = (ms as u128 * self.ticks_per_second() as u128 / 1000)
+            .try_into()
+            .unwrap_or(u64::MAX);
+        let start = self.now_ticks();
+        while self.now_ticks().wrapping_sub(start) < ticks {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// A timer that ticks once every time it is read.
+    struct CountingTimer {
+        ticks: Cell<u64>,
+    }
+
+    impl Timer for CountingTimer {
+        fn now_ticks(&self) -> u64 {
+            let ticks = self.ticks.get();
+            self.ticks.set(ticks.wrapping_add(1));
+            ticks
+        }
+
+        fn ticks_per_second(&self) -> u64 {
+            10_000
+        }
+    }
+
+    /// Ensures that:
+    ///
+    /// * `delay_ms` waits for the number of ticks in the delay, and no more
+    /// * A counter that wraps around during the delay is handled
+    #[test]
+    fn delay() {
+        let timer = CountingTimer {
+            ticks: Cell::new(0),
+        };
+        timer.delay_ms(25);
+        // The start is read once, then the counter is read until 250 ticks have passed
+        assert_eq!(timer.ticks.get(), 251);
+
+        let timer = CountingTimer {
+            ticks: Cell::new(u64::MAX - 5),
+        };
+        timer.delay_ms(1);
+        assert_eq!(timer.ticks.get(), 5);
+
+        timer.delay_ms(0);
+        assert_eq!(timer.ticks.get(), 7);
+    }
+
+    /// Ensures that `now_ms` converts the counter to whole milliseconds.
+    #[test]
+    fn now_ms() {
+        let timer = CountingTimer {
+            ticks: Cell::new(12_345),
+        };
+        assert_eq!(timer.now_ms(), 1234);
+
+        let timer = CountingTimer {
+            ticks: Cell::new(u64::MAX),
+        };
+        assert_eq!(timer.now_ms(), u64::MAX / 10);
+    }
+}
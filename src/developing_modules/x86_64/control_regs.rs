@@ -0,0 +1,198 @@
+//! Access to the x86_64 control registers (`CR0`, `CR2`, `CR3` and `CR4`).
+//!
+//! Control registers can only be accessed in ring 0, which is always the case in a bootloader.
+
+use core::{arch::asm, ops::BitOr};
+
+/// The flags of the `CR0` register.
+///
+/// Bits that do not have a constant here are kept as they are, so a value that was read can be
+/// changed and written back.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cr0Flags(u64);
+
+impl Cr0Flags {
+    /// Protected mode is enabled.
+    pub const PE: Self = Self(1);
+    /// Read-only pages cannot be written to in ring 0.
+    pub const WP: Self = Self(1 << 16);
+    /// Paging is enabled. Requires `PE` to be set.
+    pub const PG: Self = Self(1 << 31);
+
+    /// Returns flags with no bits set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns flags with the raw `bits` of the register.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw bits of the flags.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if every bit in `other` is also set in `self`.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Cr0Flags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// The flags of the `CR4` register.
+///
+/// Bits that do not have a constant here are kept as they are, so a value that was read can be
+/// changed and written back.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cr4Flags(u64);
+
+impl Cr4Flags {
+    /// 4MiB pages can be used with 32-bit paging. This is ignored when `PAE` is set.
+    pub const PSE: Self = Self(1 << 4);
+    /// Physical address extension, which is required for long mode paging.
+    pub const PAE: Self = Self(1 << 5);
+    /// Pages marked as global are not flushed from the TLB when `CR3` is written.
+    pub const PGE: Self = Self(1 << 7);
+
+    /// Returns flags with no bits set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns flags with the raw `bits` of the register.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    /// Returns the raw bits of the flags.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if every bit in `other` is also set in `self`.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Cr4Flags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Returns the flags in `CR0`.
+///
+/// # Safety
+///
+/// Must be run in ring 0.
+pub unsafe fn read_cr0() -> Cr0Flags {
+    let value: u64;
+    asm!("mov {}, cr0", out(reg) value, options(nomem, nostack, preserves_flags));
+    Cr0Flags(value)
+}
+
+/// Writes `flags` to `CR0`.
+///
+/// # Safety
+///
+/// Must be run in ring 0. Changing `PE` or `PG` changes how every memory access is translated, so
+/// the code and data that are in use must still be reachable afterwards. Enabling `PG` in long
+/// mode also requires `CR4.PAE` and valid page tables in `CR3`.
+pub unsafe fn write_cr0(flags: Cr0Flags) {
+    asm!("mov cr0, {}", in(reg) flags.0, options(nostack, preserves_flags));
+}
+
+/// Returns the linear address that caused the most recent page fault.
+///
+/// # Safety
+///
+/// Must be run in ring 0.
+pub unsafe fn read_cr2() -> u64 {
+    let value: u64;
+    asm!("mov {}, cr2", out(reg) value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+/// Returns the value of `CR3`, which contains the physical address of the current PML4 along with
+/// the PCID or cache control bits in its low 12 bits.
+///
+/// # Safety
+///
+/// Must be run in ring 0.
+pub unsafe fn read_cr3() -> u64 {
+    let value: u64;
+    asm!("mov {}, cr3", out(reg) value, options(nomem, nostack, preserves_flags));
+    value
+}
+
+/// Writes `value` to `CR3`, which switches to the page tables whose PML4 is at the physical
+/// address in `value`. This also flushes every non-global entry from the TLB.
+///
+/// # Safety
+///
+/// Must be run in ring 0. The new page tables must already map the current instruction pointer,
+/// the stack, and any other memory that is used after the switch, or the next access to them
+/// will fault. The PML4 must stay valid for as long as it is loaded.
+pub unsafe fn write_cr3(value: u64) {
+    asm!("mov cr3, {}", in(reg) value, options(nostack, preserves_flags));
+}
+
+/// Returns the flags in `CR4`.
+///
+/// # Safety
+///
+/// Must be run in ring 0.
+pub unsafe fn read_cr4() -> Cr4Flags {
+    let value: u64;
+    asm!("mov {}, cr4", out(reg) value, options(nomem, nostack, preserves_flags));
+    Cr4Flags(value)
+}
+
+/// Writes `flags` to `CR4`.
+///
+/// # Safety
+///
+/// Must be run in ring 0. Setting a bit for a feature that the CPU does not support causes a
+/// general protection fault, and clearing `PAE` while long mode paging is enabled is not allowed.
+pub unsafe fn write_cr4(flags: Cr4Flags) {
+    asm!("mov cr4, {}", in(reg) flags.0, options(nostack, preserves_flags));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures that:
+    ///
+    /// * Combined flags contain each of their parts
+    /// * Unknown bits are kept when flags are combined
+    #[test]
+    fn control_register_flags() {
+        let cr0 = Cr0Flags::PE | Cr0Flags::PG;
+        assert_eq!(cr0.bits(), 0x8000_0001);
+        assert!(cr0.contains(Cr0Flags::PG));
+        assert!(!cr0.contains(Cr0Flags::WP));
+        assert!(Cr0Flags::empty().contains(Cr0Flags::empty()));
+
+        // The extension type bit (4) has no constant
+        let cr0 = Cr0Flags::from_bits(1 << 4) | Cr0Flags::PE;
+        assert_eq!(cr0.bits(), 0b1_0001);
+
+        let cr4 = Cr4Flags::from_bits(1 << 9) | Cr4Flags::PAE;
+        assert_eq!(cr4.bits(), 0b10_0010_0000);
+        assert!(cr4.contains(Cr4Flags::PAE));
+        assert!(!cr4.contains(Cr4Flags::PSE | Cr4Flags::PAE));
+    }
+}
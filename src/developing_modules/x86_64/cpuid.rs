@@ -1,11 +1,43 @@
-// TODO: Ensure CPUID is supported by attempting to set bit 21 of EFLAGS
-use core::arch::x86_64::{__get_cpuid_max, CpuidResult, __cpuid_count};
+use core::arch::{
+    asm,
+    x86_64::{__cpuid_count, __get_cpuid_max, CpuidResult},
+};
 
 #[cfg(not(test))]
 use log::debug;
 #[cfg(test)]
 use std::println as debug;
 
+/// Returns true if the CPU supports the CPUID instruction.
+///
+/// This is checked by attempting to toggle the ID flag (bit 21) of EFLAGS; the flag can only be
+/// changed if CPUID is supported. EFLAGS is restored before returning.
+pub fn cpuid_supported() -> bool {
+    const EFLAGS_ID: u64 = 1 << 21;
+    let original: u64;
+    let toggled: u64;
+
+    unsafe {
+        asm!(
+            "pushfq",
+            "pop {original}",
+            "mov {toggled}, {original}",
+            "xor {toggled}, {id}",
+            "push {toggled}",
+            "popfq",
+            "pushfq",
+            "pop {toggled}",
+            "push {original}",
+            "popfq",
+            id = in(reg) EFLAGS_ID,
+            original = out(reg) original,
+            toggled = out(reg) toggled,
+        );
+    }
+
+    (original ^ toggled) & EFLAGS_ID != 0
+}
+
 /// Returns the maximum values for CPUID basic and extended functions, respectively.
 pub unsafe fn cpuid_max_values() -> (u32, u32) {
     let (basic, _) = __get_cpuid_max(0);
@@ -21,10 +53,203 @@ pub unsafe fn cpuid_address_width() -> (u8, u8) {
     (physical, linear)
 }
 
+/// Returns the frequency of the time stamp counter (TSC) in Hz.
+///
+/// This is calculated from leaf `0x15` as `crystal_hz * numerator / denominator`. Returns `None`
+/// if the leaf is out of range of `cpuid_max_values`, or if any of its fields are zero (which
+/// means that they are not enumerated by the CPU).
+///
+/// # Safety
+///
+/// The CPU must support the CPUID instruction.
+pub unsafe fn cpuid_tsc_frequency() -> Option<u64> {
+    let (basic, _) = cpuid_max_values();
+    if basic < 0x15 {
+        return None;
+    }
+
+    let CpuidResult {
+        eax: denominator,
+        ebx: numerator,
+        ecx: crystal_hz,
+        ..
+    } = __cpuid_count(0x15, 0);
+    if denominator == 0 || numerator == 0 || crystal_hz == 0 {
+        return None;
+    }
+    Some(crystal_hz as u64 * numerator as u64 / denominator as u64)
+}
+
+/// The feature flags reported by CPUID leaves `0x1` and `0x8000_0001`.
+///
+/// Flags from a leaf that is not supported by the CPU are always false.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CpuFeatures {
+    /// ECX of leaf `0x1`.
+    basic_ecx: u32,
+    /// EDX of leaf `0x1`.
+    basic_edx: u32,
+    /// EDX of leaf `0x8000_0001`.
+    extended_edx: u32,
+}
+
+impl CpuFeatures {
+    const BASIC_ECX_X2APIC: u32 = 1 << 21;
+    const BASIC_EDX_APIC: u32 = 1 << 9;
+    const BASIC_EDX_SSE2: u32 = 1 << 26;
+    const EXTENDED_EDX_NX: u32 = 1 << 20;
+    const EXTENDED_EDX_1GIB_PAGES: u32 = 1 << 26;
+
+    /// Returns true if 1GiB pages are supported.
+    pub fn has_1gib_pages(&self) -> bool {
+        self.extended_edx & Self::EXTENDED_EDX_1GIB_PAGES != 0
+    }
+
+    /// Returns true if the CPU has a local APIC.
+    pub fn has_apic(&self) -> bool {
+        self.basic_edx & Self::BASIC_EDX_APIC != 0
+    }
+
+    /// Returns true if the no-execute page bit is supported.
+    pub fn has_nx(&self) -> bool {
+        self.extended_edx & Self::EXTENDED_EDX_NX != 0
+    }
+
+    /// Returns true if SSE2 instructions are supported.
+    pub fn has_sse2(&self) -> bool {
+        self.basic_edx & Self::BASIC_EDX_SSE2 != 0
+    }
+
+    /// Returns true if the local APIC supports x2APIC mode.
+    pub fn has_x2apic(&self) -> bool {
+        self.basic_ecx & Self::BASIC_ECX_X2APIC != 0
+    }
+}
+
+/// Returns the feature flags reported by CPUID leaves `0x1` and `0x8000_0001`.
+///
+/// Leaves that are out of range of `cpuid_max_values` are not read.
+///
+/// # Safety
+///
+/// The CPU must support the CPUID instruction.
+pub unsafe fn cpuid_features() -> CpuFeatures {
+    let (basic, extended) = cpuid_max_values();
+    let mut features = CpuFeatures::default();
+
+    if basic >= 0x1 {
+        let CpuidResult { ecx, edx, .. } = __cpuid_count(0x1, 0);
+        features.basic_ecx = ecx;
+        features.basic_edx = edx;
+    }
+
+    if extended >= 0x8000_0001 {
+        let CpuidResult { edx, .. } = __cpuid_count(0x8000_0001, 0);
+        features.extended_edx = edx;
+    }
+
+    features
+}
+
+/// Returns the CPU's 12-byte vendor string (e.g. "GenuineIntel" or "AuthenticAMD").
+///
+/// # Safety
+///
+/// The CPU must support the CPUID instruction.
+pub unsafe fn cpuid_vendor() -> [u8; 12] {
+    let CpuidResult { ebx, ecx, edx, .. } = __cpuid_count(0, 0);
+
+    let mut vendor = [0; 12];
+    vendor[0..4].copy_from_slice(&ebx.to_le_bytes());
+    vendor[4..8].copy_from_slice(&edx.to_le_bytes());
+    vendor[8..12].copy_from_slice(&ecx.to_le_bytes());
+    vendor
+}
+
+/// Returns true if the CPU reports that it is running under a hypervisor.
+///
+/// This is bit 31 of ECX in leaf `0x1`, which is reserved on physical CPUs.
+///
+/// # Safety
+///
+/// The CPU must support the CPUID instruction.
+pub unsafe fn cpuid_hypervisor_present() -> bool {
+    const BASIC_ECX_HYPERVISOR: u32 = 1 << 31;
+
+    let (basic, _) = cpuid_max_values();
+    if basic < 0x1 {
+        return false;
+    }
+    let CpuidResult { ecx, .. } = __cpuid_count(0x1, 0);
+    ecx & BASIC_ECX_HYPERVISOR != 0
+}
+
+/// Returns the hypervisor's 12-byte vendor string (e.g. "KVMKVMKVM\0\0\0" or "TCGTCGTCGTCG").
+///
+/// The vendor string is read from leaf `0x4000_0000`. Returns `None` if the CPU does not report
+/// that it is running under a hypervisor.
+///
+/// # Safety
+///
+/// The CPU must support the CPUID instruction.
+pub unsafe fn cpuid_hypervisor_vendor() -> Option<[u8; 12]> {
+    if !cpuid_hypervisor_present() {
+        return None;
+    }
+
+    // Unlike leaf `0x0`, the vendor string is stored in EBX, ECX and EDX, in that order
+    let CpuidResult { ebx, ecx, edx, .. } = __cpuid_count(0x4000_0000, 0);
+    let mut vendor = [0; 12];
+    vendor[0..4].copy_from_slice(&ebx.to_le_bytes());
+    vendor[4..8].copy_from_slice(&ecx.to_le_bytes());
+    vendor[8..12].copy_from_slice(&edx.to_le_bytes());
+    Some(vendor)
+}
+
+/// Returns the CPU's 48-byte brand string, which is padded with null bytes.
+///
+/// The brand string is read from leaves `0x8000_0002` through `0x8000_0004`. If these leaves
+/// are not supported, the returned array is all zeroes.
+///
+/// # Safety
+///
+/// The CPU must support the CPUID instruction.
+pub unsafe fn cpuid_brand_string() -> [u8; 48] {
+    let mut brand = [0; 48];
+
+    let (_, extended) = cpuid_max_values();
+    if extended < 0x8000_0004 {
+        return brand;
+    }
+
+    for (leaf, brand_part) in (0x8000_0002..=0x8000_0004).zip(brand.chunks_exact_mut(16)) {
+        let CpuidResult { eax, ebx, ecx, edx } = __cpuid_count(leaf, 0);
+        let registers = [eax, ebx, ecx, edx];
+        for (register, bytes) in registers.iter().zip(brand_part.chunks_exact_mut(4)) {
+            bytes.copy_from_slice(&register.to_le_bytes());
+        }
+    }
+
+    brand
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Returns true if `byte` is a printable ASCII character or a space.
+    ///
+    /// Vendor strings can contain spaces, such as Zhaoxin's `"  Shanghai  "`.
+    fn is_printable(byte: &u8) -> bool {
+        *byte == b' ' || byte.is_ascii_graphic()
+    }
+
+    /// Ensures that the test host is detected as supporting CPUID, as every x86_64 CPU does.
+    #[test]
+    fn supported() {
+        assert!(cpuid_supported());
+    }
+
     /// A simple check to ensure that `cpuid_max_values`'s result is in a valid range.
     ///
     /// Since the max values are different on each CPU, this just ensures that the basic
@@ -67,4 +292,74 @@ mod tests {
         assert!(physical >= MIN_PHYSICAL && physical <= MAX_PHYSICAL);
         assert!(linear >= MIN_LINEAR && linear <= MAX_LINEAR);
     }
+
+    /// A simple check to ensure that `cpuid_tsc_frequency`'s result is in a valid range.
+    ///
+    /// Many CPUs (and hypervisors) do not enumerate the TSC frequency, so this only checks that a
+    /// reported frequency is in between 100MHz and 10GHz.
+    #[test]
+    fn tsc_frequency() {
+        let frequency = unsafe { cpuid_tsc_frequency() };
+        debug!("TSC Frequency: {frequency:?}");
+        const MIN_FREQUENCY: u64 = 100_000_000;
+        const MAX_FREQUENCY: u64 = 10_000_000_000;
+        if let Some(frequency) = frequency {
+            assert!((MIN_FREQUENCY..=MAX_FREQUENCY).contains(&frequency));
+        }
+    }
+
+    /// A simple check to ensure that `cpuid_features` reads the feature leaves.
+    ///
+    /// SSE2 is part of the base x86_64 instruction set, so it should always be supported.
+    #[test]
+    fn features() {
+        let features = unsafe { cpuid_features() };
+        debug!("{features:?}");
+        assert!(features.has_sse2());
+    }
+
+    /// A simple check to ensure that `cpuid_vendor` and `cpuid_brand_string` return readable strings.
+    ///
+    /// The vendor string should only contain printable ASCII characters. The brand string should only contain
+    /// printable ASCII characters, followed by null padding.
+    #[test]
+    fn vendor_and_brand() {
+        let vendor = unsafe { cpuid_vendor() };
+        let brand = unsafe { cpuid_brand_string() };
+        debug!(
+            "CPU {{ vendor: {:?}, brand: {:?} }}",
+            core::str::from_utf8(&vendor),
+            core::str::from_utf8(&brand)
+        );
+
+        assert!(vendor.iter().all(is_printable));
+
+        let brand_len = brand.iter().position(|byte| *byte == 0);
+        let (brand, padding) = brand.split_at(brand_len.unwrap_or(brand.len()));
+        assert!(brand.iter().all(is_printable));
+        assert!(padding.iter().all(|byte| *byte == 0));
+    }
+
+    /// A simple check to ensure that `cpuid_hypervisor_vendor` agrees with
+    /// `cpuid_hypervisor_present`.
+    ///
+    /// The test host may or may not be virtualized. If it is, the vendor string should only
+    /// contain printable ASCII characters, followed by null padding.
+    #[test]
+    fn hypervisor() {
+        let present = unsafe { cpuid_hypervisor_present() };
+        let vendor = unsafe { cpuid_hypervisor_vendor() };
+        debug!(
+            "Hypervisor {{ present: {present}, vendor: {:?} }}",
+            vendor.as_ref().map(|vendor| core::str::from_utf8(vendor))
+        );
+
+        assert_eq!(present, vendor.is_some());
+        if let Some(vendor) = vendor {
+            let vendor_len = vendor.iter().position(|byte| *byte == 0);
+            let (vendor, padding) = vendor.split_at(vendor_len.unwrap_or(vendor.len()));
+            assert!(vendor.iter().all(is_printable));
+            assert!(padding.iter().all(|byte| *byte == 0));
+        }
+    }
 }
@@ -0,0 +1,525 @@
+use core::{arch::asm, fmt};
+
+use crate::developing_modules::raw::checked_slice;
+
+#[cfg(not(test))]
+use log::debug;
+#[cfg(test)]
+use std::println as debug;
+
+/// The size of a single x86_64 IDT gate descriptor in bytes.
+const IDT_GATE_SIZE: usize = 16;
+/// The size of a single GDT segment descriptor in bytes.
+const GDT_DESCRIPTOR_SIZE: usize = 8;
+
+/// The error type returned when decoding a descriptor table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DescriptorTableError {
+    /// The table's size (`limit + 1`) is not a multiple of the descriptor size.
+    InvalidLimit { limit: u16 },
+    /// The table's base address is null, or the table would extend past the end of the address
+    /// space.
+    InvalidBase { base: u64 },
+    /// The table has no room for another descriptor.
+    TableFull,
+}
+
+/// A decoded 10-byte descriptor table register (GDTR or IDTR), as stored by `sgdt` or `sidt`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DescriptorTablePointer {
+    /// The size of the table in bytes, minus one.
+    pub limit: u16,
+    /// The linear address of the table.
+    pub base: u64,
+}
+
+impl DescriptorTablePointer {
+    /// Decodes a raw 10-byte descriptor table register.
+    pub fn from_bytes(raw: [u8; 10]) -> Self {
+        let [limit_low, limit_high, base @ ..] = raw;
+
+        Self {
+            limit: u16::from_le_bytes([limit_low, limit_high]),
+            base: u64::from_le_bytes(base),
+        }
+    }
+
+    /// Encodes this pointer as a raw 10-byte descriptor table register, as loaded by `lgdt` or
+    /// `lidt`.
+    pub fn to_bytes(&self) -> [u8; 10] {
+        let mut raw = [0; 10];
+        raw[..2].copy_from_slice(&self.limit.to_le_bytes());
+        raw[2..].copy_from_slice(&self.base.to_le_bytes());
+        raw
+    }
+
+    /// Returns the number of `descriptor_size` byte descriptors in the table.
+    ///
+    /// # Errors
+    ///
+    /// * [`DescriptorTableError::InvalidLimit`]: the table's size is not a multiple of
+    /// `descriptor_size`
+    pub fn descriptor_count(&self, descriptor_size: usize) -> Result<usize, DescriptorTableError> {
+        let size = self.limit as usize + 1;
+        if size % descriptor_size != 0 {
+            return Err(DescriptorTableError::InvalidLimit { limit: self.limit });
+        }
+
+        Ok(size / descriptor_size)
+    }
+}
+
+/// A decoded 8-byte segment descriptor from the Global Descriptor Table (GDT).
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct GdtDescriptor {
+    /// The 32-bit base address, split across bits 16 to 39 and 56 to 63 of the descriptor.
+    pub base: u32,
+    /// The 20-bit segment limit, split across bits 0 to 15 and 48 to 51 of the descriptor.
+    pub limit: u32,
+    /// The access byte, found in bits 40 to 47 of the descriptor.
+    pub access: u8,
+    /// The 4-bit flags, found in bits 52 to 55 of the descriptor.
+    pub flags: u8,
+}
+
+impl GdtDescriptor {
+    const ACCESS_PRESENT: u8 = 1 << 7;
+    const ACCESS_DPL_SHIFT: u8 = 5;
+    const ACCESS_DPL_MASK: u8 = 0b11;
+    const ACCESS_EXECUTABLE: u8 = 1 << 3;
+    const FLAGS_GRANULARITY: u8 = 1 << 3;
+    const FLAGS_LONG_MODE: u8 = 1 << 1;
+
+    /// The null descriptor, which must be the first entry of every GDT.
+    pub const NULL: Self = Self::from_u64(0);
+    /// A flat 64-bit code segment for ring 0.
+    pub const KERNEL_CODE: Self = Self::from_u64(0x00af_9a00_0000_ffff);
+    /// A flat data segment for ring 0.
+    pub const KERNEL_DATA: Self = Self::from_u64(0x00cf_9200_0000_ffff);
+
+    /// Decodes a raw 8-byte segment descriptor.
+    pub const fn from_u64(raw: u64) -> Self {
+        let base_low = (raw >> 16) & 0xff_ffff;
+        let base_high = (raw >> 56) & 0xff;
+        let limit_low = raw & 0xffff;
+        let limit_high = (raw >> 48) & 0xf;
+
+        Self {
+            base: (base_low | (base_high << 24)) as u32,
+            limit: (limit_low | (limit_high << 16)) as u32,
+            access: (raw >> 40) as u8,
+            flags: ((raw >> 52) & 0xf) as u8,
+        }
+    }
+
+    /// Encodes this descriptor as a raw 8-byte segment descriptor.
+    ///
+    /// This is the inverse of [`GdtDescriptor::from_u64`]. Only the low 20 bits of the limit and
+    /// the low 4 bits of the flags are used.
+    pub const fn to_u64(&self) -> u64 {
+        let base = self.base as u64;
+        let limit = self.limit as u64;
+
+        (limit & 0xffff)
+            | ((base & 0xff_ffff) << 16)
+            | ((self.access as u64) << 40)
+            | (((limit >> 16) & 0xf) << 48)
+            | (((self.flags & 0xf) as u64) << 52)
+            | (((base >> 24) & 0xff) << 56)
+    }
+
+    /// Returns the descriptor privilege level (ring 0 to 3).
+    pub const fn dpl(&self) -> u8 {
+        (self.access >> Self::ACCESS_DPL_SHIFT) & Self::ACCESS_DPL_MASK
+    }
+
+    /// Returns true if this is a code segment.
+    pub const fn executable(&self) -> bool {
+        self.access & Self::ACCESS_EXECUTABLE != 0
+    }
+
+    /// Returns true if the limit is scaled by 4KiB pages instead of bytes.
+    pub const fn granularity(&self) -> bool {
+        self.flags & Self::FLAGS_GRANULARITY != 0
+    }
+
+    /// Returns true if this is a 64-bit code segment.
+    pub const fn long_mode(&self) -> bool {
+        self.flags & Self::FLAGS_LONG_MODE != 0
+    }
+
+    /// Returns true if this segment is present.
+    pub const fn present(&self) -> bool {
+        self.access & Self::ACCESS_PRESENT != 0
+    }
+}
+
+impl fmt::Debug for GdtDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GdtDescriptor")
+            .field("base", &format_args!("{:#x}", self.base))
+            .field("limit", &format_args!("{:#x}", self.limit))
+            .field("present", &self.present())
+            .field("dpl", &self.dpl())
+            .field("executable", &self.executable())
+            .field("granularity", &self.granularity())
+            .field("long_mode", &self.long_mode())
+            .finish()
+    }
+}
+
+/// A Global Descriptor Table with room for up to `N` descriptors, built one descriptor at a time.
+///
+/// The table is stored inline, so it must be placed somewhere that does not move (such as a
+/// `static`) before it is loaded.
+#[derive(Clone, Debug)]
+#[repr(C, align(16))]
+pub struct GdtBuilder<const N: usize> {
+    entries: [u64; N],
+    len: usize,
+}
+
+impl<const N: usize> GdtBuilder<N> {
+    /// Returns an empty table.
+    pub const fn new() -> Self {
+        Self {
+            entries: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Returns the raw descriptors that have been pushed so far.
+    pub fn entries(&self) -> &[u64] {
+        &self.entries[..self.len]
+    }
+
+    /// Loads this table with `lgdt`, then reloads `CS` with `code_selector` and every data segment
+    /// register with `data_selector`.
+    ///
+    /// `CS` can only be changed by a far jump, call or return, so a far return to the next
+    /// instruction is used.
+    ///
+    /// # Safety
+    ///
+    /// Must be run in ring 0. The selectors must be for a 64-bit code segment and a data segment
+    /// in this table, and the table must not move or be changed for as long as it is loaded.
+    pub unsafe fn load(&self, code_selector: u16, data_selector: u16) {
+        let gdtr = self.pointer().to_bytes();
+        asm!(
+            "lgdt [{gdtr}]",
+            "push {code}",
+            "lea {return_addr}, [rip + 2f]",
+            "push {return_addr}",
+            "retfq",
+            "2:",
+            "mov ds, {data:x}",
+            "mov es, {data:x}",
+            "mov fs, {data:x}",
+            "mov gs, {data:x}",
+            "mov ss, {data:x}",
+            gdtr = in(reg) gdtr.as_ptr(),
+            code = in(reg) code_selector as u64,
+            data = in(reg) data_selector,
+            return_addr = out(reg) _,
+            options(preserves_flags),
+        );
+    }
+
+    /// Returns a pointer to this table, which can be loaded into the GDTR.
+    pub fn pointer(&self) -> DescriptorTablePointer {
+        DescriptorTablePointer {
+            limit: (self.len * GDT_DESCRIPTOR_SIZE).saturating_sub(1) as u16,
+            base: self.entries.as_ptr() as u64,
+        }
+    }
+
+    /// Adds `descriptor` to the end of the table, and returns its segment selector (with a
+    /// requested privilege level of 0).
+    ///
+    /// The first descriptor should be [`GdtDescriptor::NULL`].
+    ///
+    /// # Errors
+    ///
+    /// * [`DescriptorTableError::TableFull`]: the table already has `N` descriptors
+    pub fn push(&mut self, descriptor: GdtDescriptor) -> Result<u16, DescriptorTableError> {
+        let entry = self
+            .entries
+            .get_mut(self.len)
+            .ok_or(DescriptorTableError::TableFull)?;
+        *entry = descriptor.to_u64();
+
+        let selector = (self.len * GDT_DESCRIPTOR_SIZE) as u16;
+        self.len += 1;
+        Ok(selector)
+    }
+}
+
+impl<const N: usize> Default for GdtBuilder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A decoded 16-byte gate descriptor from the x86_64 Interrupt Descriptor Table (IDT).
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct IdtGateDescriptor {
+    /// The 64-bit handler address, split across bytes 0 to 1, 6 to 7, and 8 to 11.
+    pub offset: u64,
+    /// The code segment selector used when calling the handler.
+    pub selector: u16,
+    /// The Interrupt Stack Table index, or 0 if the current stack is used.
+    pub ist: u8,
+    /// The gate type, DPL, and present bit.
+    pub attributes: u8,
+}
+
+impl IdtGateDescriptor {
+    const ATTRIBUTES_PRESENT: u8 = 1 << 7;
+    const ATTRIBUTES_DPL_SHIFT: u8 = 5;
+    const ATTRIBUTES_DPL_MASK: u8 = 0b11;
+    const ATTRIBUTES_GATE_TYPE_MASK: u8 = 0xf;
+    const IST_MASK: u8 = 0b111;
+
+    /// Decodes a raw 16-byte gate descriptor.
+    pub const fn from_u128(raw: u128) -> Self {
+        let offset_low = raw & 0xffff;
+        let offset_middle = (raw >> 48) & 0xffff;
+        let offset_high = (raw >> 64) & 0xffff_ffff;
+
+        Self {
+            offset: (offset_low | (offset_middle << 16) | (offset_high << 32)) as u64,
+            selector: (raw >> 16) as u16,
+            ist: (raw >> 32) as u8 & Self::IST_MASK,
+            attributes: (raw >> 40) as u8,
+        }
+    }
+
+    /// Returns the descriptor privilege level (ring 0 to 3).
+    pub const fn dpl(&self) -> u8 {
+        (self.attributes >> Self::ATTRIBUTES_DPL_SHIFT) & Self::ATTRIBUTES_DPL_MASK
+    }
+
+    /// Returns the gate type; `0xe` for an interrupt gate and `0xf` for a trap gate.
+    pub const fn gate_type(&self) -> u8 {
+        self.attributes & Self::ATTRIBUTES_GATE_TYPE_MASK
+    }
+
+    /// Returns true if this gate is present.
+    pub const fn present(&self) -> bool {
+        self.attributes & Self::ATTRIBUTES_PRESENT != 0
+    }
+}
+
+impl fmt::Debug for IdtGateDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdtGateDescriptor")
+            .field("offset", &format_args!("{:#x}", self.offset))
+            .field("selector", &format_args!("{:#x}", self.selector))
+            .field("ist", &self.ist)
+            .field("gate_type", &format_args!("{:#x}", self.gate_type()))
+            .field("present", &self.present())
+            .field("dpl", &self.dpl())
+            .finish()
+    }
+}
+
+/// Returns the raw 10-byte Global Descriptor Table Register (GDTR), as stored by `sgdt`.
+///
+/// The result can be decoded with [`DescriptorTablePointer::from_bytes`].
+///
+/// # Safety
+///
+/// The current privilege level must allow `sgdt`; this is always true in a bootloader, which runs
+/// in ring 0.
+pub unsafe fn read_gdtr() -> [u8; 10] {
+    let mut gdtr = [0u8; 10];
+    asm!("sgdt [{}]", in(reg) gdtr.as_mut_ptr(), options(nostack, preserves_flags));
+    gdtr
+}
+
+/// Returns the raw 10-byte Interrupt Descriptor Table Register (IDTR), as stored by `sidt`.
+///
+/// The result can be decoded with [`DescriptorTablePointer::from_bytes`], or passed directly to
+/// [`print_idt`].
+///
+/// # Safety
+///
+/// The current privilege level must allow `sidt`; this is always true in a bootloader, which runs
+/// in ring 0.
+pub unsafe fn read_idtr() -> [u8; 10] {
+    let mut idtr = [0u8; 10];
+    asm!("sidt [{}]", in(reg) idtr.as_mut_ptr(), options(nostack, preserves_flags));
+    idtr
+}
+
+/// Logs every gate descriptor in the IDT pointed to by `idtr`.
+///
+/// # Errors
+///
+/// * [`DescriptorTableError::InvalidLimit`]: the IDT's size is not a multiple of 16 bytes
+/// * [`DescriptorTableError::InvalidBase`]: the IDT's base address is null or out of range
+///
+/// # Safety
+///
+/// `idtr` must point to a readable IDT.
+pub unsafe fn print_idt(idtr: [u8; 10]) -> Result<(), DescriptorTableError> {
+    let idtr = DescriptorTablePointer::from_bytes(idtr);
+    let gate_count = idtr.descriptor_count(IDT_GATE_SIZE)?;
+    debug!("{idtr:?}");
+
+    let idt = checked_slice(idtr.base as *const [u8; IDT_GATE_SIZE], gate_count)
+        .map_err(|_| DescriptorTableError::InvalidBase { base: idtr.base })?;
+    for (vector, gate) in idt.iter().enumerate() {
+        let gate = IdtGateDescriptor::from_u128(u128::from_le_bytes(*gate));
+        debug!("{vector:#04x}: {gate:?}");
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures that the flat 64-bit code and data segments that are used by most kernels are
+    /// decoded correctly.
+    #[test]
+    fn flat_descriptors() {
+        let code = GdtDescriptor::from_u64(0x00af_9a00_0000_ffff);
+        assert_eq!(code.base, 0);
+        assert_eq!(code.limit, 0xfffff);
+        assert!(code.present());
+        assert_eq!(code.dpl(), 0);
+        assert!(code.executable());
+        assert!(code.granularity());
+        assert!(code.long_mode());
+
+        let data = GdtDescriptor::from_u64(0x00cf_f200_0000_ffff);
+        assert_eq!(data.base, 0);
+        assert_eq!(data.limit, 0xfffff);
+        assert!(data.present());
+        assert_eq!(data.dpl(), 3);
+        assert!(!data.executable());
+        assert!(data.granularity());
+        assert!(!data.long_mode());
+
+        assert!(!GdtDescriptor::from_u64(0).present());
+    }
+
+    /// Ensures that the base address and limit are reassembled from each of their parts.
+    #[test]
+    fn split_fields() {
+        let descriptor = GdtDescriptor::from_u64(0x1209_9234_5678_abcd);
+        assert_eq!(descriptor.base, 0x1234_5678);
+        assert_eq!(descriptor.limit, 0x9abcd);
+        assert_eq!(descriptor.access, 0x92);
+        assert_eq!(descriptor.flags, 0x0);
+    }
+
+    /// Ensures that:
+    ///
+    /// * Encoding a descriptor is the inverse of decoding it
+    /// * A flat GDT can be built, and its pointer covers every descriptor
+    /// * Descriptors cannot be pushed to a full table
+    #[test]
+    fn build_gdt() {
+        for raw in [
+            0,
+            0x00af_9a00_0000_ffff,
+            0x00cf_f200_0000_ffff,
+            0x1209_9234_5678_abcd,
+        ] {
+            assert_eq!(GdtDescriptor::from_u64(raw).to_u64(), raw);
+        }
+
+        let mut gdt = GdtBuilder::<3>::new();
+        assert_eq!(gdt.push(GdtDescriptor::NULL), Ok(0));
+        assert_eq!(gdt.push(GdtDescriptor::KERNEL_CODE), Ok(0x8));
+        assert_eq!(gdt.push(GdtDescriptor::KERNEL_DATA), Ok(0x10));
+        assert_eq!(
+            gdt.push(GdtDescriptor::KERNEL_DATA),
+            Err(DescriptorTableError::TableFull)
+        );
+        assert_eq!(
+            gdt.entries(),
+            [0, 0x00af_9a00_0000_ffff, 0x00cf_9200_0000_ffff]
+        );
+
+        let pointer = gdt.pointer();
+        assert_eq!(pointer.base, gdt.entries().as_ptr() as u64);
+        assert_eq!(pointer.descriptor_count(GDT_DESCRIPTOR_SIZE), Ok(3));
+        assert_eq!(
+            DescriptorTablePointer::from_bytes(pointer.to_bytes()),
+            pointer
+        );
+    }
+
+    /// Ensures that a descriptor table register is decoded, and that its limit is checked.
+    #[test]
+    fn table_pointer() {
+        let raw = [0xff, 0x0f, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11];
+        let pointer = DescriptorTablePointer::from_bytes(raw);
+        assert_eq!(pointer.limit, 0xfff);
+        assert_eq!(pointer.base, 0x1122_3344_5566_7788);
+        assert_eq!(pointer.descriptor_count(IDT_GATE_SIZE), Ok(256));
+
+        let pointer = DescriptorTablePointer {
+            limit: 0x17,
+            base: 0,
+        };
+        assert_eq!(pointer.descriptor_count(8), Ok(3));
+        assert_eq!(
+            pointer.descriptor_count(IDT_GATE_SIZE),
+            Err(DescriptorTableError::InvalidLimit { limit: 0x17 })
+        );
+    }
+
+    /// Ensures that an IDT gate's offset is reassembled from each of its parts.
+    #[test]
+    fn idt_gate() {
+        let gate = IdtGateDescriptor::from_u128(0x0000_0000_1234_5678_9abc_8e02_0008_def0);
+        assert_eq!(gate.offset, 0x1234_5678_9abc_def0);
+        assert_eq!(gate.selector, 0x8);
+        assert_eq!(gate.ist, 2);
+        assert_eq!(gate.gate_type(), 0xe);
+        assert!(gate.present());
+        assert_eq!(gate.dpl(), 0);
+    }
+
+    /// Ensures that `print_idt` reads every gate in a table, and rejects an invalid limit or base.
+    #[test]
+    fn print_table() {
+        let idt = [[0u8; IDT_GATE_SIZE]; 4];
+        let base = (idt.as_ptr() as u64).to_le_bytes();
+        let mut idtr = [0u8; 10];
+        idtr[2..].copy_from_slice(&base);
+
+        idtr[..2].copy_from_slice(&((IDT_GATE_SIZE * idt.len() - 1) as u16).to_le_bytes());
+        assert_eq!(unsafe { print_idt(idtr) }, Ok(()));
+
+        idtr[..2].copy_from_slice(&(IDT_GATE_SIZE as u16).to_le_bytes());
+        assert_eq!(
+            unsafe { print_idt(idtr) },
+            Err(DescriptorTableError::InvalidLimit {
+                limit: IDT_GATE_SIZE as u16
+            })
+        );
+
+        let mut null_idtr = [0u8; 10];
+        null_idtr[..2].copy_from_slice(&((IDT_GATE_SIZE - 1) as u16).to_le_bytes());
+        assert_eq!(
+            unsafe { print_idt(null_idtr) },
+            Err(DescriptorTableError::InvalidBase { base: 0 })
+        );
+    }
+
+    /// Ensures that the GDTR and IDTR can be read.
+    ///
+    /// The values are not checked, as user mode may only be given placeholder values.
+    #[test]
+    fn read_registers() {
+        let gdtr = DescriptorTablePointer::from_bytes(unsafe { read_gdtr() });
+        let idtr = DescriptorTablePointer::from_bytes(unsafe { read_idtr() });
+        debug!("GDTR: {gdtr:x?}, IDTR: {idtr:x?}");
+    }
+}
@@ -1 +1,8 @@
-pub mod cpuid;
\ No newline at end of file
+pub mod control_regs;
+pub mod cpuid;
+pub mod descriptor_tables;
+pub mod msr;
+pub mod paging;
+pub mod pio;
+pub mod tsc;
+pub mod uart_16550;
\ No newline at end of file
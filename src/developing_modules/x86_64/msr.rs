@@ -0,0 +1,74 @@
+//! Access to x86_64 model-specific registers (MSRs).
+//!
+//! MSRs can only be accessed in ring 0, which is always the case in a bootloader.
+
+use core::arch::asm;
+
+/// The extended feature enable register, which controls long mode and no-execute pages.
+pub const IA32_EFER: u32 = 0xc000_0080;
+
+/// Set in [`IA32_EFER`] to enable long mode. Long mode becomes active once paging is enabled.
+pub const EFER_LME: u64 = 1 << 8;
+/// Set by the CPU in [`IA32_EFER`] while long mode is active.
+pub const EFER_LMA: u64 = 1 << 10;
+/// Set in [`IA32_EFER`] to allow pages to be marked as no-execute.
+pub const EFER_NXE: u64 = 1 << 11;
+
+/// Returns the value of the MSR at `msr`.
+///
+/// # Safety
+///
+/// Must be run in ring 0, and `msr` must be supported by the CPU. Reading an unsupported MSR
+/// causes a general protection fault.
+pub unsafe fn rdmsr(msr: u32) -> u64 {
+    let low: u32;
+    let high: u32;
+    asm!(
+        "rdmsr",
+        in("ecx") msr,
+        out("eax") low,
+        out("edx") high,
+        options(nomem, nostack, preserves_flags),
+    );
+
+    (high as u64) << 32 | low as u64
+}
+
+/// Writes `value` to the MSR at `msr`.
+///
+/// # Safety
+///
+/// Must be run in ring 0, and `msr` must be supported by the CPU. Writing an unsupported MSR, or
+/// setting a reserved bit, causes a general protection fault. Many MSRs change how the CPU
+/// behaves, so the caller must make sure that the new value is valid in the current state.
+pub unsafe fn wrmsr(msr: u32, value: u64) {
+    asm!(
+        "wrmsr",
+        in("ecx") msr,
+        in("eax") value as u32,
+        in("edx") (value >> 32) as u32,
+        options(nostack, preserves_flags),
+    );
+}
+
+/// Sets `EFER.NXE`, so that [`PageFlags::NO_EXECUTE`] can be used in page tables.
+///
+/// [`PageFlags::NO_EXECUTE`]: super::paging::PageFlags::NO_EXECUTE
+///
+/// # Safety
+///
+/// Must be run in ring 0, on a CPU that supports no-execute pages (CPUID leaf `0x8000_0001`, EDX
+/// bit 20).
+pub unsafe fn enable_nxe() {
+    wrmsr(IA32_EFER, rdmsr(IA32_EFER) | EFER_NXE);
+}
+
+/// Sets `EFER.LME`, so that long mode is activated the next time paging is enabled.
+///
+/// # Safety
+///
+/// Must be run in ring 0 while paging is disabled, on a CPU that supports long mode (CPUID leaf
+/// `0x8000_0001`, EDX bit 29).
+pub unsafe fn enable_long_mode() {
+    wrmsr(IA32_EFER, rdmsr(IA32_EFER) | EFER_LME);
+}
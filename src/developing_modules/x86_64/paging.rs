@@ -0,0 +1,350 @@
+//! 4-level x86_64 page tables.
+//!
+//! The bootloader runs with physical memory identity mapped, so page tables are accessed through
+//! their physical addresses.
+
+use core::{ops::BitOr, ptr};
+
+use crate::developing_modules::page_frame_allocator::PageFrameAllocator;
+
+/// The number of entries in a page table.
+pub const PAGE_TABLE_ENTRIES: usize = 512;
+
+const PAGE_SIZE_4KIB: u64 = 0x1000;
+const PAGE_SIZE_2MIB: u64 = 0x20_0000;
+
+/// The bits of a page table entry that contain a physical address.
+const ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+/// The flag that makes a page directory entry map a 2MiB page.
+const HUGE_PAGE: u64 = 1 << 7;
+
+/// The error type returned when mapping a page.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MapError {
+    /// The frame allocator does not use 4KiB frames.
+    UnsupportedPageSize,
+    /// A frame for a new page table could not be allocated.
+    OutOfFrames,
+    /// The virtual or physical address is not aligned to the page size.
+    Misaligned,
+    /// The virtual address is not canonical.
+    NonCanonicalAddress,
+    /// The physical address does not fit in a page table entry, which holds up to 52 bits.
+    PhysicalAddressTooLarge,
+    /// The virtual address is already mapped, or is part of a larger page.
+    AlreadyMapped,
+}
+
+/// The flags of a page table entry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PageFlags(u64);
+
+impl PageFlags {
+    /// The page is mapped.
+    pub const PRESENT: Self = Self(1);
+    /// The page can be written to.
+    pub const WRITABLE: Self = Self(1 << 1);
+    /// The page can be accessed outside of ring 0.
+    pub const USER: Self = Self(1 << 2);
+    /// Instructions cannot be fetched from the page. Requires `EFER.NXE` to be set.
+    pub const NO_EXECUTE: Self = Self(1 << 63);
+
+    /// Returns flags with no bits set.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns the raw bits of the flags.
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns true if every bit in `other` is also set in `self`.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for PageFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A single level of the page table hierarchy (PML4, PDPT, PD or PT).
+#[derive(Clone, Debug)]
+#[repr(C, align(4096))]
+pub struct PageTable {
+    pub entries: [u64; PAGE_TABLE_ENTRIES],
+}
+
+/// Maps pages in a 4-level page table hierarchy, allocating new page tables as needed.
+pub struct Mapper<'a> {
+    pml4: *mut PageTable,
+    frame_allocator: &'a mut PageFrameAllocator,
+}
+
+impl<'a> Mapper<'a> {
+    /// Returns a mapper with an empty PML4 allocated from `frame_allocator`.
+    ///
+    /// # Safety
+    ///
+    /// The frames returned by `frame_allocator` must be identity mapped.
+    pub unsafe fn new(frame_allocator: &'a mut PageFrameAllocator) -> Result<Self, MapError> {
+        if frame_allocator.page_size().size() as u64 != PAGE_SIZE_4KIB {
+            return Err(MapError::UnsupportedPageSize);
+        }
+
+        let pml4 = allocate_table(frame_allocator)?;
+        Ok(Self {
+            pml4,
+            frame_allocator,
+        })
+    }
+
+    /// Returns the physical address of the PML4, which can be loaded into `CR3`.
+    pub fn pml4_addr(&self) -> u64 {
+        self.pml4 as u64
+    }
+
+    /// Maps the 4KiB page at `virt` to the frame at `phys`.
+    ///
+    /// [`PageFlags::PRESENT`] is always set.
+    ///
+    /// # Safety
+    ///
+    /// If this hierarchy is active, changing it can change memory that is in use.
+    pub unsafe fn map_4kib(
+        &mut self,
+        virt: u64,
+        phys: u64,
+        flags: PageFlags,
+    ) -> Result<(), MapError> {
+        check_addresses(virt, phys, PAGE_SIZE_4KIB)?;
+
+        let pdpt = self.next_table(self.pml4, table_index(virt, 3), flags)?;
+        let pd = self.next_table(pdpt, table_index(virt, 2), flags)?;
+        let pt = self.next_table(pd, table_index(virt, 1), flags)?;
+        set_entry(pt, table_index(virt, 0), phys | flags.bits())
+    }
+
+    /// Maps the 2MiB page at `virt` to the frames starting at `phys`.
+    ///
+    /// [`PageFlags::PRESENT`] is always set.
+    ///
+    /// # Safety
+    ///
+    /// If this hierarchy is active, changing it can change memory that is in use.
+    pub unsafe fn map_2mib(
+        &mut self,
+        virt: u64,
+        phys: u64,
+        flags: PageFlags,
+    ) -> Result<(), MapError> {
+        check_addresses(virt, phys, PAGE_SIZE_2MIB)?;
+
+        let pdpt = self.next_table(self.pml4, table_index(virt, 3), flags)?;
+        let pd = self.next_table(pdpt, table_index(virt, 2), flags)?;
+        set_entry(pd, table_index(virt, 1), phys | flags.bits() | HUGE_PAGE)
+    }
+
+    /// Returns the physical address that `virt` is mapped to.
+    pub fn translate(&self, virt: u64) -> Option<u64> {
+        let mut table = self.pml4;
+        for level in (0..4).rev() {
+            let entry = unsafe { (*table).entries[table_index(virt, level)] };
+            if entry & PageFlags::PRESENT.bits() == 0 {
+                return None;
+            }
+
+            // Level 1 entries with the huge page flag map 2MiB pages
+            let is_huge = level == 1 && entry & HUGE_PAGE != 0;
+            if level == 0 || is_huge {
+                let page_size = if is_huge {
+                    PAGE_SIZE_2MIB
+                } else {
+                    PAGE_SIZE_4KIB
+                };
+                return Some((entry & ADDRESS_MASK & !(page_size - 1)) + (virt & (page_size - 1)));
+            }
+            table = (entry & ADDRESS_MASK) as *mut PageTable;
+        }
+
+        None
+    }
+
+    /// Returns the table referenced by entry `index` of `table`, allocating it if it does not
+    /// exist.
+    ///
+    /// The entry is given any of the writable and user flags in `flags`, so that access is only
+    /// restricted by the final entry.
+    unsafe fn next_table(
+        &mut self,
+        table: *mut PageTable,
+        index: usize,
+        flags: PageFlags,
+    ) -> Result<*mut PageTable, MapError> {
+        let entry = &mut (*table).entries[index];
+        let permissions = flags.bits() & (PageFlags::WRITABLE | PageFlags::USER).bits();
+
+        if *entry & PageFlags::PRESENT.bits() == 0 {
+            let next = allocate_table(self.frame_allocator)?;
+            *entry = next as u64 | PageFlags::PRESENT.bits() | permissions;
+            return Ok(next);
+        }
+        if *entry & HUGE_PAGE != 0 {
+            return Err(MapError::AlreadyMapped);
+        }
+
+        *entry |= permissions;
+        Ok((*entry & ADDRESS_MASK) as *mut PageTable)
+    }
+}
+
+/// Allocates a zeroed page table.
+unsafe fn allocate_table(
+    frame_allocator: &mut PageFrameAllocator,
+) -> Result<*mut PageTable, MapError> {
+    let frame = frame_allocator.alloc_frame().ok_or(MapError::OutOfFrames)?;
+    let table = frame.start() as *mut PageTable;
+    ptr::write_bytes(table, 0, 1);
+    Ok(table)
+}
+
+/// Sets entry `index` of `table` to `value`, marking it as present.
+unsafe fn set_entry(table: *mut PageTable, index: usize, value: u64) -> Result<(), MapError> {
+    let entry = &mut (*table).entries[index];
+    if *entry & PageFlags::PRESENT.bits() != 0 {
+        return Err(MapError::AlreadyMapped);
+    }
+
+    *entry = value | PageFlags::PRESENT.bits();
+    Ok(())
+}
+
+/// Ensures that `virt` is canonical, that `phys` fits in an entry, and that both addresses are
+/// aligned to `page_size`.
+fn check_addresses(virt: u64, phys: u64, page_size: u64) -> Result<(), MapError> {
+    // Bits 48 to 63 must be copies of bit 47
+    if ((virt << 16) as i64 >> 16) as u64 != virt {
+        return Err(MapError::NonCanonicalAddress);
+    }
+    if virt % page_size != 0 || phys % page_size != 0 {
+        return Err(MapError::Misaligned);
+    }
+    // Any higher bits would be OR'd into the no-execute or reserved bits of the entry
+    if phys & !ADDRESS_MASK != 0 {
+        return Err(MapError::PhysicalAddressTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Returns the index into the page table at `level` (0 for a PT, 3 for a PML4) for `virt`.
+fn table_index(virt: u64, level: u32) -> usize {
+    ((virt >> (12 + 9 * level)) & 0x1ff) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::developing_modules::page_frame_allocator::PageSize;
+    use std::vec;
+
+    /// Returns a frame allocator with `frames` frames, one of which is used by its bitmap.
+    fn frame_allocator(frames: usize) -> PageFrameAllocator {
+        let buffer = vec![0u8; (frames + 1) * PAGE_SIZE_4KIB as usize].leak();
+        let offset = buffer.as_ptr().align_offset(PAGE_SIZE_4KIB as usize);
+        let memory_map = [(
+            buffer.as_ptr() as usize + offset,
+            frames * PAGE_SIZE_4KIB as usize,
+        )];
+        unsafe { PageFrameAllocator::new(&memory_map, PageSize::default()) }.unwrap()
+    }
+
+    /// Ensures that:
+    ///
+    /// * 4KiB and 2MiB pages can be mapped and translated
+    /// * Page tables are only allocated when needed
+    /// * Intermediate entries get the permissions of the pages below them
+    #[test]
+    fn map_pages() {
+        let mut frame_allocator = frame_allocator(8);
+        let mut mapper = unsafe { Mapper::new(&mut frame_allocator) }.unwrap();
+        let flags = PageFlags::WRITABLE | PageFlags::NO_EXECUTE;
+
+        unsafe {
+            mapper
+                .map_4kib(0xffff_8000_0000_1000, 0x1234_5000, flags)
+                .unwrap();
+            mapper
+                .map_4kib(0xffff_8000_0000_2000, 0x1234_6000, PageFlags::USER)
+                .unwrap();
+            mapper
+                .map_2mib(0x4000_0000, 0x20_0000, PageFlags::empty())
+                .unwrap();
+        }
+        assert_eq!(mapper.translate(0xffff_8000_0000_1abc), Some(0x1234_5abc));
+        assert_eq!(mapper.translate(0xffff_8000_0000_2000), Some(0x1234_6000));
+        assert_eq!(mapper.translate(0x4012_3456), Some(0x32_3456));
+        assert_eq!(mapper.translate(0xffff_8000_0000_3000), None);
+        assert_eq!(mapper.translate(0x4020_0000), None);
+
+        // PML4 + 3 tables for the higher half + 2 tables for the 2MiB page
+        let pml4 = unsafe { &*(mapper.pml4_addr() as *const PageTable) };
+        let pml4_entry = pml4.entries[table_index(0xffff_8000_0000_1000, 3)];
+        assert!(PageFlags(pml4_entry)
+            .contains(PageFlags::PRESENT | PageFlags::WRITABLE | PageFlags::USER));
+        assert!(!PageFlags(pml4_entry).contains(PageFlags::NO_EXECUTE));
+        assert_eq!(frame_allocator.free_frames(), 1);
+    }
+
+    /// Ensures that proper errors are returned.
+    #[test]
+    fn map_errors() {
+        let mut frame_allocator = frame_allocator(5);
+        let mut mapper = unsafe { Mapper::new(&mut frame_allocator) }.unwrap();
+        let flags = PageFlags::WRITABLE;
+
+        unsafe {
+            assert_eq!(
+                mapper.map_4kib(0x1001, 0x1000, flags),
+                Err(MapError::Misaligned)
+            );
+            assert_eq!(
+                mapper.map_2mib(0x20_0000, 0x1000, flags),
+                Err(MapError::Misaligned)
+            );
+            assert_eq!(
+                mapper.map_4kib(0x8000_0000_0000, 0x1000, flags),
+                Err(MapError::NonCanonicalAddress)
+            );
+            assert_eq!(
+                mapper.map_4kib(0x1000, 1 << 52, flags),
+                Err(MapError::PhysicalAddressTooLarge)
+            );
+            assert_eq!(
+                mapper.map_2mib(0x20_0000, 0x8000_0000_0000_0000, flags),
+                Err(MapError::PhysicalAddressTooLarge)
+            );
+
+            mapper.map_2mib(0x20_0000, 0x20_0000, flags).unwrap();
+            assert_eq!(
+                mapper.map_4kib(0x20_1000, 0x1000, flags),
+                Err(MapError::AlreadyMapped)
+            );
+            assert_eq!(
+                mapper.map_2mib(0x20_0000, 0x40_0000, flags),
+                Err(MapError::AlreadyMapped)
+            );
+
+            mapper.map_4kib(0x40_0000, 0x1000, flags).unwrap();
+            assert_eq!(
+                mapper.map_4kib(0x8000_0000, 0x1000, flags),
+                Err(MapError::OutOfFrames)
+            );
+        }
+    }
+}
@@ -0,0 +1,118 @@
+/// This implementation is a shortened version of the RedoxOS implementation found here:
+///
+/// <https://gitlab.redox-os.org/redox-os/syscall/-/blob/master/src/io/pio.rs>
+use core::{arch::asm, marker::PhantomData};
+
+use crate::developing_modules::io::Io;
+
+/// A single x86 I/O port, accessed with the `in` and `out` instructions.
+#[derive(Clone, Copy, Debug)]
+pub struct Pio<T> {
+    port: u16,
+    value: PhantomData<T>,
+}
+
+impl<T> Pio<T> {
+    /// Returns a wrapper for the I/O port at `port`.
+    ///
+    /// # Safety
+    ///
+    /// `port` must be a valid I/O port for a device that accepts accesses of size `T`. Reading or
+    /// writing an I/O port can have side effects on the device.
+    pub const unsafe fn new(port: u16) -> Self {
+        Self {
+            port,
+            value: PhantomData,
+        }
+    }
+}
+
+impl Io for Pio<u8> {
+    type Value = u8;
+
+    #[inline(always)]
+    fn read(&self) -> u8 {
+        let value: u8;
+        unsafe {
+            asm!(
+                "in al, dx",
+                in("dx") self.port,
+                out("al") value,
+                options(nostack, nomem, preserves_flags),
+            );
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn write(&mut self, value: u8) {
+        unsafe {
+            asm!(
+                "out dx, al",
+                in("dx") self.port,
+                in("al") value,
+                options(nostack, nomem, preserves_flags),
+            );
+        }
+    }
+}
+
+impl Io for Pio<u16> {
+    type Value = u16;
+
+    #[inline(always)]
+    fn read(&self) -> u16 {
+        let value: u16;
+        unsafe {
+            asm!(
+                "in ax, dx",
+                in("dx") self.port,
+                out("ax") value,
+                options(nostack, nomem, preserves_flags),
+            );
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn write(&mut self, value: u16) {
+        unsafe {
+            asm!(
+                "out dx, ax",
+                in("dx") self.port,
+                in("ax") value,
+                options(nostack, nomem, preserves_flags),
+            );
+        }
+    }
+}
+
+impl Io for Pio<u32> {
+    type Value = u32;
+
+    #[inline(always)]
+    fn read(&self) -> u32 {
+        let value: u32;
+        unsafe {
+            asm!(
+                "in eax, dx",
+                in("dx") self.port,
+                out("eax") value,
+                options(nostack, nomem, preserves_flags),
+            );
+        }
+        value
+    }
+
+    #[inline(always)]
+    fn write(&mut self, value: u32) {
+        unsafe {
+            asm!(
+                "out dx, eax",
+                in("dx") self.port,
+                in("eax") value,
+                options(nostack, nomem, preserves_flags),
+            );
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! A timer based on the time stamp counter (TSC).
+
+use core::arch::x86_64::_rdtsc;
+
+use super::cpuid::cpuid_tsc_frequency;
+use crate::developing_modules::timer::Timer;
+
+/// A [`Timer`] that reads the CPU's time stamp counter.
+///
+/// The TSC is assumed to be invariant, meaning that it ticks at a constant rate regardless of the
+/// CPU's power state. This is the case on any recent CPU.
+#[derive(Clone, Copy, Debug)]
+pub struct TscTimer {
+    ticks_per_second: u64,
+}
+
+impl TscTimer {
+    /// Returns a [`TscTimer`] using the TSC frequency reported by CPUID.
+    ///
+    /// Returns `None` if the CPU does not report the TSC frequency. See [`cpuid_tsc_frequency`].
+    ///
+    /// # Safety
+    ///
+    /// The CPU must support the CPUID instruction.
+    pub unsafe fn new() -> Option<Self> {
+        cpuid_tsc_frequency().map(Self::with_frequency)
+    }
+
+    /// Returns a [`TscTimer`] for a TSC that ticks `ticks_per_second` times each second, such as
+    /// a frequency that was measured against another timer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ticks_per_second` is zero.
+    pub fn with_frequency(ticks_per_second: u64) -> Self {
+        assert_ne!(ticks_per_second, 0, "TSC frequency cannot be zero");
+        Self { ticks_per_second }
+    }
+}
+
+impl Timer for TscTimer {
+    fn now_ticks(&self) -> u64 {
+        unsafe { _rdtsc() }
+    }
+
+    fn ticks_per_second(&self) -> u64 {
+        self.ticks_per_second
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ensures that:
+    ///
+    /// * The TSC counts up
+    /// * A timer can be made with a given frequency
+    #[test]
+    fn tsc_timer() {
+        let timer = TscTimer::with_frequency(1_000_000_000);
+        assert_eq!(timer.ticks_per_second(), 1_000_000_000);
+
+        let start = timer.now_ticks();
+        timer.delay_ms(1);
+        assert!(timer.now_ticks() - start >= 1_000_000);
+    }
+}
@@ -0,0 +1,100 @@
+//! A driver for 16550-compatible UARTs, such as the serial ports of a PC.
+
+use core::fmt::{self, Write};
+
+use super::pio::Pio;
+use crate::developing_modules::io::{ReadOnly, ReadWrite, WriteOnly};
+
+/// The I/O port base of the first serial port.
+pub const COM1: u16 = 0x3f8;
+
+/// The fastest baud rate of a 16550, which is its clock rate divided by 16.
+pub const MAX_BAUD_RATE: u32 = 115_200;
+
+/// Set in the line control register to access the baud rate divisor instead of the data and
+/// interrupt enable registers.
+const LINE_CONTROL_DIVISOR_LATCH: u8 = 1 << 7;
+/// 8 data bits, no parity and 1 stop bit.
+const LINE_CONTROL_8N1: u8 = 0b11;
+/// Enables and clears both FIFOs, with an interrupt threshold of 14 bytes.
+const FIFO_CONTROL_ENABLE: u8 = 0xc7;
+/// Sets the DTR, RTS and OUT2 lines.
+const MODEM_CONTROL_READY: u8 = 0x0b;
+/// Set in the line status register when the transmitter can accept another byte.
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+/// A 16550 UART that is accessed through x86 I/O ports.
+pub struct Uart16550 {
+    /// The transmit and receive buffers, or the low byte of the baud rate divisor.
+    data: ReadWrite<Pio<u8>>,
+    /// The interrupt enable register, or the high byte of the baud rate divisor.
+    interrupt_enable: ReadWrite<Pio<u8>>,
+    fifo_control: WriteOnly<Pio<u8>>,
+    line_control: ReadWrite<Pio<u8>>,
+    modem_control: ReadWrite<Pio<u8>>,
+    line_status: ReadOnly<Pio<u8>>,
+}
+
+impl Uart16550 {
+    /// Returns a [`Uart16550`] for the UART whose registers start at the I/O port `port_base`.
+    ///
+    /// The UART is not configured until [`Uart16550::init`] is called.
+    ///
+    /// # Safety
+    ///
+    /// `port_base` must be the base of a 16550-compatible UART, such as [`COM1`]. If multiple
+    /// [`Uart16550`]s exist for the same UART, they can overwrite each other's configuration.
+    pub const unsafe fn new(port_base: u16) -> Self {
+        Self {
+            data: ReadWrite::new(Pio::new(port_base)),
+            interrupt_enable: ReadWrite::new(Pio::new(port_base + 1)),
+            fifo_control: WriteOnly::new(Pio::new(port_base + 2)),
+            line_control: ReadWrite::new(Pio::new(port_base + 3)),
+            modem_control: ReadWrite::new(Pio::new(port_base + 4)),
+            line_status: ReadOnly::new(Pio::new(port_base + 5)),
+        }
+    }
+
+    /// Configures the UART for `baud_rate` with 8 data bits, no parity and 1 stop bit (8N1).
+    ///
+    /// Interrupts are disabled, because bytes are only written by polling.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `baud_rate` is zero or greater than [`MAX_BAUD_RATE`].
+    pub fn init(&mut self, baud_rate: u32) {
+        assert!(
+            (1..=MAX_BAUD_RATE).contains(&baud_rate),
+            "Unsupported baud rate: {baud_rate}"
+        );
+        let divisor = (MAX_BAUD_RATE / baud_rate) as u16;
+
+        self.interrupt_enable.write(0);
+
+        self.line_control.write(LINE_CONTROL_DIVISOR_LATCH);
+        let [divisor_low, divisor_high] = divisor.to_le_bytes();
+        self.data.write(divisor_low);
+        self.interrupt_enable.write(divisor_high);
+        self.line_control.write(LINE_CONTROL_8N1);
+
+        self.fifo_control.write(FIFO_CONTROL_ENABLE);
+        self.modem_control.write(MODEM_CONTROL_READY);
+    }
+
+    /// Waits until the transmitter is ready, then writes `byte`.
+    pub fn write_byte(&mut self, byte: u8) {
+        while self.line_status.read() & LINE_STATUS_TRANSMIT_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        self.data.write(byte);
+    }
+}
+
+impl Write for Uart16550 {
+    fn write_str(&mut self, out_string: &str) -> fmt::Result {
+        for out_byte in out_string.bytes() {
+            self.write_byte(out_byte);
+        }
+        Ok(())
+    }
+}